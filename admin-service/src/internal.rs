@@ -0,0 +1,33 @@
+//! Course and enrollment lookups for teacher- and student-service, which check a course exists
+//! and who is enrolled before acting on it. Callers authenticate with a service token
+//! (`ServiceCaller`); these routes are not exposed through the ingress.
+
+use axum::{extract::State, Json};
+use shared::extract::Path;
+use shared::{ApiError, ServiceCaller};
+use uuid::Uuid;
+
+use crate::routes::{fetch_course, fetch_membership, Course, Membership};
+use crate::AppState;
+
+/// Course existence and archive state, e.g. for teacher-service before creating an assignment.
+pub async fn get_course(
+    State(state): State<AppState>,
+    caller: ServiceCaller,
+    Path(course_id): Path<Uuid>,
+) -> Result<Json<Course>, ApiError> {
+    caller.allow(&["teacher-service"])?;
+    let course = fetch_course(&state, course_id).await?;
+    Ok(Json(course.ok_or(ApiError::NotFound("course not found"))?))
+}
+
+/// Any user's membership; backs `shared::access::AdminMemberships`.
+pub async fn get_membership(
+    State(state): State<AppState>,
+    caller: ServiceCaller,
+    Path((course_id, user_id)): Path<(Uuid, String)>,
+) -> Result<Json<Membership>, ApiError> {
+    caller.allow(&["teacher-service", "student-service"])?;
+    let membership = fetch_membership(&state, course_id, user_id).await?;
+    Ok(Json(membership.ok_or(ApiError::NotFound("not enrolled"))?))
+}
@@ -1,3 +1,4 @@
+mod internal;
 mod routes;
 
 use axum::{routing::get, Router};
@@ -7,20 +8,47 @@ use tower::ServiceBuilder;
 use tower_http::timeout::TimeoutLayer;
 
 pub fn app(pool: PgPool) -> Router {
-    Router::new()
+    let router = Router::new()
         .route("/health", get(routes::health))
         .route("/ready", get(routes::ready))
-        .route("/api/admin/courses", axum::routing::post(routes::create_course))
+        .route(
+            "/api/admin/courses",
+            get(routes::list_courses).post(routes::create_course),
+        )
         .route(
             "/api/admin/courses/:course_id",
-            get(routes::get_course),
+            get(routes::get_course)
+                .patch(routes::update_course)
+                .delete(routes::delete_course),
+        )
+        .route(
+            "/api/admin/courses/:course_id/enrollments",
+            get(routes::list_enrollments).post(routes::enroll),
+        )
+        .route(
+            "/api/admin/courses/:course_id/enrollments/:user_id",
+            axum::routing::delete(routes::unenroll),
         )
-        .with_state(AppState { pool })
+        .route(
+            "/api/admin/courses/:course_id/members/:user_id",
+            get(routes::get_membership),
+        )
+        .route("/internal/courses/:course_id", get(internal::get_course))
+        .route(
+            "/internal/courses/:course_id/members/:user_id",
+            get(internal::get_membership),
+        )
+        .with_state(AppState { pool: pool.clone() })
         .layer(
             ServiceBuilder::new()
-                .layer(tower_http::trace::TraceLayer::new_for_http())
+                .layer(axum::middleware::from_fn(shared::request_id::propagate))
+                .layer(
+                    tower_http::trace::TraceLayer::new_for_http()
+                        .make_span_with(shared::trace_context::make_span),
+                )
                 .layer(TimeoutLayer::new(Duration::from_secs(30))),
-        )
+        );
+    shared::metrics::with_metrics(router, pool)
 }
 
 #[derive(Clone)]
@@ -1,11 +1,13 @@
 use admin_service::app;
 use shared::{init_tracing, Config};
-use std::net::SocketAddr;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let config = Config::from_env().map_err(|e| format!("config: {}", e))?;
-    init_tracing();
+    let _tracing = init_tracing(&config).map_err(|e| format!("tracing: {}", e))?;
+    shared::auth::init_verifier(&config.auth);
+    shared::policy::init_policy(&config.auth)?;
+    shared::service_auth::init_service_tokens(&config)?;
     tracing::info!("starting {} on port {}", config.service_name, config.http_port);
 
     let pool = sqlx::postgres::PgPoolOptions::new()
@@ -14,11 +16,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let app = app(pool);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.http_port));
-    axum::serve(
-        tokio::net::TcpListener::bind(addr).await?,
-        app.into_make_service(),
-    )
-    .await?;
+    shared::tls::serve(app, &config).await?;
     Ok(())
 }
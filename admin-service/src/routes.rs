@@ -1,26 +1,129 @@
-use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    Json,
-};
+use axum::{extract::State, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
-use shared::{AuthUser, Role};
+use shared::access::NoMemberships;
+use shared::extract::{Path, Query};
+use shared::pagination::{escape_like, Cursor};
+use shared::validation::{trim, trim_opt};
+use shared::{
+    perm, ApiError, AuthUser, Authorized, Page, PageParams, Permission, Resource, Role, ValidJson,
+};
 use uuid::Uuid;
+use validator::Validate;
 
 use crate::AppState;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct CreateCourseBody {
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(min = 1, max = 200))]
     pub name: String,
 }
 
+/// Partial update: omitted fields are left unchanged. `archived: true` retires the course,
+/// `archived: false` restores it.
+#[derive(Deserialize, Validate)]
+pub struct UpdateCourseBody {
+    #[serde(default, deserialize_with = "trim_opt")]
+    #[validate(length(min = 1, max = 200))]
+    pub name: Option<String>,
+    pub archived: Option<bool>,
+}
+
+/// Filters for `GET /api/admin/courses`; paging is handled by `PageParams`.
+#[derive(Deserialize)]
+pub struct ListCoursesFilter {
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Case-insensitive substring match on the course name.
+    pub q: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct Course {
     pub id: Uuid,
     pub name: String,
+    pub archived_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+type CourseRow = (
+    Uuid,
+    String,
+    Option<chrono::DateTime<chrono::Utc>>,
+    chrono::DateTime<chrono::Utc>,
+    chrono::DateTime<chrono::Utc>,
+);
+
+impl From<CourseRow> for Course {
+    fn from((id, name, archived_at, created_at, updated_at): CourseRow) -> Self {
+        Self {
+            id,
+            name,
+            archived_at,
+            created_at,
+            updated_at,
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EnrollBody {
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(min = 1, max = 128))]
+    pub user_id: String,
+    pub role: Role,
+}
+
+/// Filters for `GET /api/admin/courses/:course_id/enrollments`; paging is handled by `PageParams`.
+#[derive(Deserialize)]
+pub struct ListEnrollmentsFilter {
+    pub role: Option<Role>,
+}
+
+#[derive(Serialize)]
+pub struct Enrollment {
+    pub id: Uuid,
+    pub course_id: Uuid,
+    pub user_id: String,
+    pub role: Role,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Answer to "is `user_id` a member of `course_id`, and as what?".
+#[derive(Serialize)]
+pub struct Membership {
+    pub course_id: Uuid,
+    pub user_id: String,
+    pub role: Role,
+    pub course_archived: bool,
+}
+
+type EnrollmentRow = (Uuid, Uuid, String, String, chrono::DateTime<chrono::Utc>);
+
+impl TryFrom<EnrollmentRow> for Enrollment {
+    type Error = ApiError;
+
+    fn try_from(
+        (id, course_id, user_id, role, created_at): EnrollmentRow,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id,
+            course_id,
+            user_id,
+            role: parse_role(&role)?,
+            created_at,
+        })
+    }
+}
+
+fn parse_role(role: &str) -> Result<Role, ApiError> {
+    role.parse().map_err(|_| {
+        tracing::error!("admin.enrollments has unknown role {:?}", role);
+        ApiError::Internal("database error")
+    })
+}
+
 pub async fn health() -> &'static str {
     "ok"
 }
@@ -35,62 +138,303 @@ pub async fn ready(State(state): State<AppState>) -> Result<&'static str, Status
 
 pub async fn create_course(
     State(state): State<AppState>,
-    AuthUser(auth): AuthUser,
-    Json(body): Json<CreateCourseBody>,
-) -> Result<(StatusCode, Json<Course>), (StatusCode, &'static str)> {
-    if auth.role != Role::Admin {
-        return Err((StatusCode::FORBIDDEN, "admin role required"));
-    }
+    _: Authorized<perm::CourseWrite>,
+    ValidJson(body): ValidJson<CreateCourseBody>,
+) -> Result<(StatusCode, Json<Course>), ApiError> {
     let id = Uuid::new_v4();
     let now = chrono::Utc::now();
     sqlx::query(
         r#"
-        INSERT INTO admin.courses (id, name, created_at)
-        VALUES ($1, $2, $3)
+        INSERT INTO admin.courses (id, name, created_at, updated_at)
+        VALUES ($1, $2, $3, $3)
         "#,
     )
     .bind(id)
     .bind(&body.name)
     .bind(now)
     .execute(&state.pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("create_course: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "database error")
-    })?;
+    .await?;
     Ok((
         StatusCode::CREATED,
         Json(Course {
             id,
             name: body.name,
+            archived_at: None,
             created_at: now,
+            updated_at: now,
         }),
     ))
 }
 
+pub async fn list_courses(
+    State(state): State<AppState>,
+    _: Authorized<perm::CourseList>,
+    page: PageParams,
+    Query(filter): Query<ListCoursesFilter>,
+) -> Result<Json<Page<Course>>, ApiError> {
+    let sql = format!(
+        r#"
+        SELECT id, name, archived_at, created_at, updated_at
+        FROM admin.courses
+        WHERE deleted_at IS NULL
+          AND ($1 OR archived_at IS NULL)
+          AND ($2::TEXT IS NULL OR name ILIKE '%' || $2 || '%' ESCAPE '\')
+          AND {}
+        {}
+        LIMIT $5
+        "#,
+        page.keyset_predicate(3),
+        page.order_by(),
+    );
+    let rows = sqlx::query_as::<_, CourseRow>(&sql)
+        .bind(filter.include_archived)
+        .bind(filter.q.as_deref().map(escape_like))
+        .bind(page.cursor_created_at())
+        .bind(page.cursor_id())
+        .bind(page.fetch_limit())
+        .fetch_all(&state.pool)
+        .await?;
+    let items = rows.into_iter().map(Course::from).collect();
+    Ok(Json(Page::from_rows(items, &page, |c: &Course| {
+        Cursor::new(c.created_at, c.id)
+    })))
+}
+
 pub async fn get_course(
     State(state): State<AppState>,
-    AuthUser(auth): AuthUser,
+    _: Authorized<perm::CourseRead>,
+    Path(course_id): Path<Uuid>,
+) -> Result<Json<Course>, ApiError> {
+    let course = fetch_course(&state, course_id).await?;
+    Ok(Json(course.ok_or(ApiError::NotFound("course not found"))?))
+}
+
+pub(crate) async fn fetch_course(
+    state: &AppState,
+    course_id: Uuid,
+) -> Result<Option<Course>, ApiError> {
+    let row = sqlx::query_as::<_, CourseRow>(
+        r#"
+        SELECT id, name, archived_at, created_at, updated_at
+        FROM admin.courses
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(course_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    Ok(row.map(Course::from))
+}
+
+pub async fn update_course(
+    State(state): State<AppState>,
+    _: Authorized<perm::CourseWrite>,
     Path(course_id): Path<Uuid>,
-) -> Result<Json<Course>, (StatusCode, &'static str)> {
-    // Admin: full access. Teacher: allowed for course-existence check (e.g. when creating assignment).
-    if auth.role != Role::Admin && auth.role != Role::Teacher {
-        return Err((StatusCode::FORBIDDEN, "admin or teacher role required"));
+    ValidJson(body): ValidJson<UpdateCourseBody>,
+) -> Result<Json<Course>, ApiError> {
+    let now = chrono::Utc::now();
+    // Archiving an already archived course keeps the original archived_at.
+    let row = sqlx::query_as::<_, CourseRow>(
+        r#"
+        UPDATE admin.courses
+        SET name = COALESCE($2, name),
+            archived_at = CASE
+                WHEN $3::BOOLEAN IS NULL THEN archived_at
+                WHEN $3 THEN COALESCE(archived_at, $4)
+                ELSE NULL
+            END,
+            updated_at = $4
+        WHERE id = $1 AND deleted_at IS NULL
+        RETURNING id, name, archived_at, created_at, updated_at
+        "#,
+    )
+    .bind(course_id)
+    .bind(body.name.as_deref())
+    .bind(body.archived)
+    .bind(now)
+    .fetch_optional(&state.pool)
+    .await?;
+    let row = row.ok_or(ApiError::NotFound("course not found"))?;
+    Ok(Json(Course::from(row)))
+}
+
+/// Soft delete: the row is kept for referential history but hidden from every read path.
+pub async fn delete_course(
+    State(state): State<AppState>,
+    _: Authorized<perm::CourseWrite>,
+    Path(course_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let now = chrono::Utc::now();
+    let result = sqlx::query(
+        r#"
+        UPDATE admin.courses
+        SET deleted_at = $2, updated_at = $2
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(course_id)
+    .bind(now)
+    .execute(&state.pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("course not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn enroll(
+    State(state): State<AppState>,
+    _: Authorized<perm::EnrollmentWrite>,
+    Path(course_id): Path<Uuid>,
+    ValidJson(body): ValidJson<EnrollBody>,
+) -> Result<(StatusCode, Json<Enrollment>), ApiError> {
+    if body.role == Role::Admin {
+        return Err(ApiError::BadRequest("role must be teacher or student"));
+    }
+    let archived_at = sqlx::query_scalar::<_, Option<chrono::DateTime<chrono::Utc>>>(
+        "SELECT archived_at FROM admin.courses WHERE id = $1 AND deleted_at IS NULL",
+    )
+    .bind(course_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound("course not found"))?;
+    if archived_at.is_some() {
+        return Err(ApiError::Conflict("course is archived"));
     }
-    let row = sqlx::query_as::<_, (Uuid, String, chrono::DateTime<chrono::Utc>)>(
-        "SELECT id, name, created_at FROM admin.courses WHERE id = $1",
+
+    let id = Uuid::new_v4();
+    let now = chrono::Utc::now();
+    let result = sqlx::query(
+        r#"
+        INSERT INTO admin.enrollments (id, course_id, user_id, role, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (course_id, user_id) DO NOTHING
+        "#,
+    )
+    .bind(id)
+    .bind(course_id)
+    .bind(&body.user_id)
+    .bind(body.role.as_str())
+    .bind(now)
+    .execute(&state.pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::Conflict("user already enrolled"));
+    }
+    Ok((
+        StatusCode::CREATED,
+        Json(Enrollment {
+            id,
+            course_id,
+            user_id: body.user_id,
+            role: body.role,
+            created_at: now,
+        }),
+    ))
+}
+
+pub async fn list_enrollments(
+    State(state): State<AppState>,
+    _: Authorized<perm::EnrollmentRead>,
+    Path(course_id): Path<Uuid>,
+    page: PageParams,
+    Query(filter): Query<ListEnrollmentsFilter>,
+) -> Result<Json<Page<Enrollment>>, ApiError> {
+    sqlx::query_scalar::<_, i32>(
+        "SELECT 1 FROM admin.courses WHERE id = $1 AND deleted_at IS NULL",
+    )
+    .bind(course_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound("course not found"))?;
+    let sql = format!(
+        r#"
+        SELECT id, course_id, user_id, role, created_at
+        FROM admin.enrollments
+        WHERE course_id = $1
+          AND ($2::TEXT IS NULL OR role = $2)
+          AND {}
+        {}
+        LIMIT $5
+        "#,
+        page.keyset_predicate(3),
+        page.order_by(),
+    );
+    let rows = sqlx::query_as::<_, EnrollmentRow>(&sql)
+        .bind(course_id)
+        .bind(filter.role.map(|r| r.as_str()))
+        .bind(page.cursor_created_at())
+        .bind(page.cursor_id())
+        .bind(page.fetch_limit())
+        .fetch_all(&state.pool)
+        .await?;
+    let items = rows
+        .into_iter()
+        .map(Enrollment::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(Page::from_rows(items, &page, |e: &Enrollment| {
+        Cursor::new(e.created_at, e.id)
+    })))
+}
+
+pub async fn unenroll(
+    State(state): State<AppState>,
+    _: Authorized<perm::EnrollmentWrite>,
+    Path((course_id, user_id)): Path<(Uuid, String)>,
+) -> Result<StatusCode, ApiError> {
+    let result = sqlx::query("DELETE FROM admin.enrollments WHERE course_id = $1 AND user_id = $2")
+        .bind(course_id)
+        .bind(&user_id)
+        .execute(&state.pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("enrollment not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_membership(
+    State(state): State<AppState>,
+    AuthUser(auth): AuthUser,
+    Path((course_id, user_id)): Path<(Uuid, String)>,
+) -> Result<Json<Membership>, ApiError> {
+    // With `enrollment:read`: any user. Otherwise only their own membership; the other services
+    // use `/internal/courses/:course_id/members/:user_id` instead.
+    let resource = Resource::new("membership", format!("{}/{}", course_id, user_id))
+        .owned_by(&user_id)
+        .or_permission(Permission::EnrollmentRead)
+        .deny_as(ApiError::Forbidden(
+            "cannot look up another user's membership",
+        ));
+    auth.authorize(&resource, &NoMemberships).await?;
+    let membership = fetch_membership(&state, course_id, user_id).await?;
+    Ok(Json(membership.ok_or(ApiError::NotFound("not enrolled"))?))
+}
+
+pub(crate) async fn fetch_membership(
+    state: &AppState,
+    course_id: Uuid,
+    user_id: String,
+) -> Result<Option<Membership>, ApiError> {
+    let row = sqlx::query_as::<_, (String, Option<chrono::DateTime<chrono::Utc>>)>(
+        r#"
+        SELECT e.role, c.archived_at
+        FROM admin.enrollments e
+        JOIN admin.courses c ON c.id = e.course_id
+        WHERE e.course_id = $1 AND e.user_id = $2 AND c.deleted_at IS NULL
+        "#,
     )
     .bind(course_id)
+    .bind(&user_id)
     .fetch_optional(&state.pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("get_course: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "database error")
-    })?;
-    let (id, name, created_at) = row.ok_or((StatusCode::NOT_FOUND, "course not found"))?;
-    Ok(Json(Course {
-        id,
-        name,
-        created_at,
+    .await?;
+    let Some((role, archived_at)) = row else {
+        return Ok(None);
+    };
+    Ok(Some(Membership {
+        course_id,
+        user_id,
+        role: parse_role(&role)?,
+        course_archived: archived_at.is_some(),
     }))
 }
@@ -0,0 +1,53 @@
+pub mod password;
+mod routes;
+pub mod tokens;
+mod users;
+
+use axum::{
+    routing::{get, patch, post},
+    Router,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokens::TokenIssuer;
+use tower::ServiceBuilder;
+use tower_http::timeout::TimeoutLayer;
+
+pub use users::bootstrap_admin;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub tokens: Arc<TokenIssuer>,
+    /// Published at `/api/auth/jwks.json` when access tokens are signed RS256.
+    pub jwks: Option<Arc<serde_json::Value>>,
+}
+
+pub fn app(pool: PgPool, tokens: Arc<TokenIssuer>, jwks: Option<Arc<serde_json::Value>>) -> Router {
+    let router = Router::new()
+        .route("/health", get(routes::health))
+        .route("/ready", get(routes::ready))
+        .route("/api/auth/login", post(routes::login))
+        .route("/api/auth/refresh", post(routes::refresh))
+        .route("/api/auth/logout", post(routes::logout))
+        .route("/api/auth/revoked", get(routes::revoked))
+        .route("/api/auth/jwks.json", get(routes::jwks))
+        .route("/api/auth/users", post(users::create_user))
+        .route("/api/auth/users/:user_id", patch(users::update_user))
+        .with_state(AppState {
+            pool: pool.clone(),
+            tokens,
+            jwks,
+        })
+        .layer(
+            ServiceBuilder::new()
+                .layer(axum::middleware::from_fn(shared::request_id::propagate))
+                .layer(
+                    tower_http::trace::TraceLayer::new_for_http()
+                        .make_span_with(shared::trace_context::make_span),
+                )
+                .layer(TimeoutLayer::new(Duration::from_secs(30))),
+        );
+    shared::metrics::with_metrics(router, pool)
+}
@@ -0,0 +1,39 @@
+use auth_service::{app, bootstrap_admin, tokens::TokenIssuer};
+use shared::{init_tracing, Config};
+use std::sync::Arc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::from_env().map_err(|e| format!("config: {}", e))?;
+    let _tracing = init_tracing(&config).map_err(|e| format!("tracing: {}", e))?;
+    shared::auth::init_verifier(&config.auth);
+    shared::policy::init_policy(&config.auth)?;
+    tracing::info!("starting {} on port {}", config.service_name, config.http_port);
+
+    let tokens = TokenIssuer::from_env(&config.auth).map_err(|e| format!("signing key: {}", e))?;
+    let jwks = match std::env::var("AUTH_JWKS_FILE") {
+        Ok(path) => {
+            let body = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+            Some(Arc::new(serde_json::from_slice(&body)?))
+        }
+        Err(_) => None,
+    };
+
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .connect(&config.database_url)
+        .await?;
+
+    if let Ok(spec) = std::env::var("AUTH_BOOTSTRAP_ADMIN") {
+        let (username, password) = spec
+            .split_once(':')
+            .ok_or("AUTH_BOOTSTRAP_ADMIN must be username:password")?;
+        if bootstrap_admin(&pool, username, password).await? {
+            tracing::info!("created bootstrap admin {:?}", username);
+        }
+    }
+
+    let app = app(pool, Arc::new(tokens), jwks);
+
+    shared::tls::serve(app, &config).await?;
+    Ok(())
+}
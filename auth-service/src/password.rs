@@ -0,0 +1,42 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand_core::OsRng;
+use std::sync::LazyLock;
+
+/// Verified against when the username is unknown, so a failed login takes as long whether or
+/// not the account exists.
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| hash_blocking("not-a-real-password"));
+
+fn hash_blocking(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("argon2 hashing with default params")
+        .to_string()
+}
+
+/// Argon2id PHC string for `password`. Hashing is deliberately slow, so it runs off the
+/// async workers.
+pub async fn hash_password(password: String) -> String {
+    tokio::task::spawn_blocking(move || hash_blocking(&password))
+        .await
+        .expect("password hashing task")
+}
+
+/// Check `password` against a stored PHC string; `None` (no such user) always fails.
+pub async fn verify_password(password: String, hash: Option<String>) -> bool {
+    tokio::task::spawn_blocking(move || {
+        let known = hash.is_some();
+        let hash = hash.unwrap_or_else(|| DUMMY_HASH.clone());
+        let Ok(parsed) = PasswordHash::new(&hash) else {
+            tracing::error!("stored password hash is not a PHC string");
+            return false;
+        };
+        let ok = Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok();
+        known && ok
+    })
+    .await
+    .expect("password verification task")
+}
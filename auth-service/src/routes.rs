@@ -0,0 +1,277 @@
+use axum::{extract::State, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use shared::revocation::RevokedTokens;
+use shared::{ApiError, AuthUser, Role, ValidJson};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::password::verify_password;
+use crate::tokens::{hash_refresh_token, new_refresh_token};
+use crate::AppState;
+
+/// Only sizes are checked, so a malformed login still fails as wrong credentials.
+#[derive(Deserialize, Validate)]
+pub struct LoginBody {
+    #[validate(length(max = 64))]
+    pub username: String,
+    #[validate(length(max = 1024))]
+    pub password: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct RefreshBody {
+    #[validate(length(max = 256))]
+    pub refresh_token: String,
+}
+
+/// Optional body for `POST /api/auth/logout`; with a refresh token its whole rotation family
+/// is revoked as well.
+#[derive(Deserialize)]
+pub struct LogoutBody {
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub refresh_token: String,
+    pub refresh_expires_in: i64,
+}
+
+type RefreshTokenRow = (
+    Uuid,
+    Uuid,
+    Uuid,
+    chrono::DateTime<chrono::Utc>,
+    Option<chrono::DateTime<chrono::Utc>>,
+    Option<chrono::DateTime<chrono::Utc>>,
+);
+
+/// `(id, password_hash, roles, disabled_at)` of the user logging in.
+type CredentialsRow = (
+    Uuid,
+    String,
+    Vec<String>,
+    Option<chrono::DateTime<chrono::Utc>>,
+);
+
+pub(crate) fn parse_roles(roles: &[String]) -> Result<Vec<Role>, ApiError> {
+    roles
+        .iter()
+        .map(|role| {
+            role.parse().map_err(|_| {
+                tracing::error!("auth.users has unknown role {:?}", role);
+                ApiError::Internal("database error")
+            })
+        })
+        .collect()
+}
+
+pub async fn health() -> &'static str {
+    "ok"
+}
+
+pub async fn ready(State(state): State<AppState>) -> Result<&'static str, StatusCode> {
+    sqlx::query("SELECT 1")
+        .execute(&state.pool)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok("ok")
+}
+
+/// Store a new refresh token in `family_id` and sign an access token to go with it.
+async fn issue_tokens(
+    state: &AppState,
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    roles: &[Role],
+    family_id: Uuid,
+) -> Result<TokenResponse, ApiError> {
+    let refresh_token = new_refresh_token();
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(state.tokens.refresh_ttl_secs);
+    sqlx::query(
+        r#"
+        INSERT INTO auth.refresh_tokens (id, user_id, family_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(family_id)
+    .bind(hash_refresh_token(&refresh_token))
+    .bind(expires_at)
+    .execute(&mut **tx)
+    .await?;
+    let access = state.tokens.issue(user_id, roles).map_err(|e| {
+        tracing::error!("signing access token: {}", e);
+        ApiError::Internal("token signing failed")
+    })?;
+    Ok(TokenResponse {
+        access_token: access.token,
+        token_type: "Bearer",
+        expires_in: access.expires_in,
+        refresh_token,
+        refresh_expires_in: state.tokens.refresh_ttl_secs,
+    })
+}
+
+async fn revoke_family(
+    tx: &mut Transaction<'_, Postgres>,
+    family_id: Uuid,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE auth.refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(family_id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn login(
+    State(state): State<AppState>,
+    ValidJson(body): ValidJson<LoginBody>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let row: Option<CredentialsRow> = sqlx::query_as(
+        "SELECT id, password_hash, roles, disabled_at FROM auth.users WHERE username = $1",
+    )
+    .bind(&body.username)
+    .fetch_optional(&state.pool)
+    .await?;
+    let hash = row.as_ref().map(|(_, hash, _, _)| hash.clone());
+    let verified = verify_password(body.password, hash).await;
+    let Some((user_id, _, roles, disabled_at)) = row.filter(|_| verified) else {
+        return Err(ApiError::Unauthorized("invalid username or password"));
+    };
+    if disabled_at.is_some() {
+        return Err(ApiError::Unauthorized("account disabled"));
+    }
+    let roles = parse_roles(&roles)?;
+
+    let mut tx = state.pool.begin().await?;
+    let tokens = issue_tokens(&state, &mut tx, user_id, &roles, Uuid::new_v4()).await?;
+    tx.commit().await?;
+    tracing::info!(%user_id, "login");
+    Ok(Json(tokens))
+}
+
+/// Exchange a refresh token for a new access token and a new refresh token. Each refresh token
+/// works once: presenting one that was already exchanged means it was copied, so every token
+/// in its family is revoked and the user has to log in again.
+pub async fn refresh(
+    State(state): State<AppState>,
+    ValidJson(body): ValidJson<RefreshBody>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let mut tx = state.pool.begin().await?;
+    let row: Option<RefreshTokenRow> = sqlx::query_as(
+        r#"
+        SELECT id, user_id, family_id, expires_at, used_at, revoked_at
+        FROM auth.refresh_tokens
+        WHERE token_hash = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(hash_refresh_token(&body.refresh_token))
+    .fetch_optional(&mut *tx)
+    .await?;
+    let (id, user_id, family_id, expires_at, used_at, revoked_at) =
+        row.ok_or(ApiError::Unauthorized("invalid refresh token"))?;
+    if revoked_at.is_some() {
+        return Err(ApiError::Unauthorized("refresh token revoked"));
+    }
+    if used_at.is_some() {
+        tracing::warn!(%user_id, %family_id, "refresh token reused; revoking its family");
+        revoke_family(&mut tx, family_id).await?;
+        tx.commit().await?;
+        return Err(ApiError::Unauthorized("refresh token reused"));
+    }
+    if expires_at <= chrono::Utc::now() {
+        return Err(ApiError::Unauthorized("refresh token expired"));
+    }
+
+    let (roles, disabled_at): (Vec<String>, Option<chrono::DateTime<chrono::Utc>>) =
+        sqlx::query_as("SELECT roles, disabled_at FROM auth.users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+    if disabled_at.is_some() {
+        return Err(ApiError::Unauthorized("account disabled"));
+    }
+    let roles = parse_roles(&roles)?;
+
+    sqlx::query("UPDATE auth.refresh_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    let tokens = issue_tokens(&state, &mut tx, user_id, &roles, family_id).await?;
+    tx.commit().await?;
+    Ok(Json(tokens))
+}
+
+/// Revoke the presented access token until it expires, plus the refresh token family if one
+/// is given. Other services stop accepting the access token once their cached copy of
+/// `/api/auth/revoked` is refreshed.
+pub async fn logout(
+    State(state): State<AppState>,
+    AuthUser(auth): AuthUser,
+    body: Option<Json<LogoutBody>>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pool.begin().await?;
+    if let Some(jti) = &auth.jti {
+        let expires_at = chrono::DateTime::from_timestamp(auth.exp, 0)
+            .ok_or(ApiError::BadRequest("invalid token expiry"))?;
+        sqlx::query(
+            r#"
+            INSERT INTO auth.revoked_tokens (jti, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (jti) DO NOTHING
+            "#,
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+    // Expired tokens are rejected anyway; keep the published list small.
+    sqlx::query("DELETE FROM auth.revoked_tokens WHERE expires_at < NOW()")
+        .execute(&mut *tx)
+        .await?;
+    if let Some(refresh_token) = body.and_then(|Json(b)| b.refresh_token) {
+        let family: Option<(Uuid,)> = sqlx::query_as(
+            "SELECT family_id FROM auth.refresh_tokens WHERE token_hash = $1 AND user_id::text = $2",
+        )
+        .bind(hash_refresh_token(&refresh_token))
+        .bind(&auth.sub)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some((family_id,)) = family {
+            revoke_family(&mut tx, family_id).await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Published `jti` denylist: unexpired access tokens revoked by logout.
+pub async fn revoked(State(state): State<AppState>) -> Result<Json<RevokedTokens>, ApiError> {
+    let rows: Vec<(String,)> =
+        sqlx::query_as("SELECT jti FROM auth.revoked_tokens WHERE expires_at > NOW()")
+            .fetch_all(&state.pool)
+            .await?;
+    Ok(Json(RevokedTokens {
+        revoked: rows.into_iter().map(|(jti,)| jti).collect(),
+    }))
+}
+
+/// Public keys for RS256 access tokens, read from `AUTH_JWKS_FILE` at startup.
+pub async fn jwks(State(state): State<AppState>) -> Result<Json<serde_json::Value>, ApiError> {
+    state
+        .jwks
+        .as_deref()
+        .cloned()
+        .map(Json)
+        .ok_or(ApiError::NotFound("no JWKS configured"))
+}
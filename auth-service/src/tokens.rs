@@ -0,0 +1,115 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use shared::{AuthConfig, Role};
+use std::env;
+use uuid::Uuid;
+
+/// Access token claims; `sub`/`roles` are what `AuthUser` maps, `jti` is what logout revokes.
+#[derive(Serialize)]
+struct AccessClaims<'a> {
+    sub: String,
+    roles: &'a [Role],
+    iat: i64,
+    exp: i64,
+    jti: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    aud: &'a [String],
+}
+
+pub struct AccessToken {
+    pub token: String,
+    pub expires_in: i64,
+}
+
+/// Signs access tokens. HS256 with `JWT_SECRET` by default, so the other services verify them
+/// as before; with `AUTH_SIGNING_KEY_FILE` they are signed RS256 under `AUTH_SIGNING_KID` and
+/// the services verify them against the JWKS served at `/api/auth/jwks.json`.
+pub struct TokenIssuer {
+    key: EncodingKey,
+    header: Header,
+    issuer: Option<String>,
+    audience: Vec<String>,
+    pub access_ttl_secs: i64,
+    pub refresh_ttl_secs: i64,
+}
+
+fn secs_var(name: &str, default: i64) -> i64 {
+    env::var(name)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(default)
+}
+
+impl TokenIssuer {
+    /// `iss` is the `JWT_ISSUER` the services require; `aud` lists `AUTH_TOKEN_AUDIENCE`
+    /// (comma-separated) so one token is accepted by every service.
+    pub fn from_env(auth: &AuthConfig) -> Result<Self, String> {
+        let (key, header) = if let Ok(path) = env::var("AUTH_SIGNING_KEY_FILE") {
+            let pem = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+            let key = EncodingKey::from_rsa_pem(&pem).map_err(|e| format!("{}: {}", path, e))?;
+            let mut header = Header::new(Algorithm::RS256);
+            header.kid = Some(env::var("AUTH_SIGNING_KID").unwrap_or_else(|_| "auth-1".into()));
+            (key, header)
+        } else if let Some(secret) = &auth.jwt_secret {
+            (
+                EncodingKey::from_secret(secret.as_bytes()),
+                Header::new(Algorithm::HS256),
+            )
+        } else {
+            return Err("JWT_SECRET or AUTH_SIGNING_KEY_FILE must be set".into());
+        };
+        Ok(Self {
+            key,
+            header,
+            issuer: auth.issuer.clone(),
+            audience: env::var("AUTH_TOKEN_AUDIENCE")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            access_ttl_secs: secs_var("ACCESS_TOKEN_TTL_SECS", 15 * 60),
+            refresh_ttl_secs: secs_var("REFRESH_TOKEN_TTL_SECS", 30 * 24 * 60 * 60),
+        })
+    }
+
+    pub fn issue(
+        &self,
+        user_id: Uuid,
+        roles: &[Role],
+    ) -> Result<AccessToken, jsonwebtoken::errors::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let claims = AccessClaims {
+            sub: user_id.to_string(),
+            roles,
+            iat: now,
+            exp: now + self.access_ttl_secs,
+            jti: Uuid::new_v4().to_string(),
+            iss: self.issuer.as_deref(),
+            aud: &self.audience,
+        };
+        Ok(AccessToken {
+            token: encode(&self.header, &claims, &self.key)?,
+            expires_in: self.access_ttl_secs,
+        })
+    }
+}
+
+/// New opaque refresh token: 256 random bits, base64url.
+pub fn new_refresh_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Refresh tokens are stored as SHA-256 hex digests so a database leak cannot replay them.
+pub fn hash_refresh_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
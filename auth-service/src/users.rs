@@ -0,0 +1,154 @@
+use axum::{extract::State, http::StatusCode, Json};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use shared::extract::Path;
+use shared::validation::trim;
+use shared::{perm, ApiError, Authorized, Role, ValidJson};
+use sqlx::PgPool;
+use std::sync::LazyLock;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::password::hash_password;
+use crate::routes::parse_roles;
+use crate::AppState;
+
+/// Letters, digits and `.`, `_`, `@`, `-`, so e-mail addresses work as usernames.
+static USERNAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[A-Za-z0-9._@-]+$").unwrap());
+
+#[derive(Deserialize, Validate)]
+pub struct CreateUserBody {
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(min = 1, max = 64), regex(path = *USERNAME))]
+    pub username: String,
+    #[validate(length(min = 8, max = 1024))]
+    pub password: String,
+    #[validate(length(min = 1, message = "at least one role is required"))]
+    pub roles: Vec<Role>,
+}
+
+/// `disabled: true` blocks login and revokes the user's refresh tokens; `false` re-enables.
+#[derive(Deserialize, Validate)]
+pub struct UpdateUserBody {
+    pub disabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct User {
+    pub id: Uuid,
+    pub username: String,
+    pub roles: Vec<Role>,
+    pub disabled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+type UserRow = (
+    Uuid,
+    String,
+    Vec<String>,
+    Option<chrono::DateTime<chrono::Utc>>,
+    chrono::DateTime<chrono::Utc>,
+);
+
+impl TryFrom<UserRow> for User {
+    type Error = ApiError;
+
+    fn try_from(
+        (id, username, roles, disabled_at, created_at): UserRow,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id,
+            username,
+            roles: parse_roles(&roles)?,
+            disabled_at,
+            created_at,
+        })
+    }
+}
+
+const USER_COLUMNS: &str = "id, username, roles, disabled_at, created_at";
+
+pub async fn create_user(
+    State(state): State<AppState>,
+    _: Authorized<perm::UserManage>,
+    ValidJson(body): ValidJson<CreateUserBody>,
+) -> Result<(StatusCode, Json<User>), ApiError> {
+    let mut roles: Vec<&str> = Vec::new();
+    for role in &body.roles {
+        if !roles.contains(&role.as_str()) {
+            roles.push(role.as_str());
+        }
+    }
+    let password_hash = hash_password(body.password).await;
+    let row: Option<UserRow> = sqlx::query_as(&format!(
+        r#"
+        INSERT INTO auth.users (id, username, password_hash, roles)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING {USER_COLUMNS}
+        "#
+    ))
+    .bind(Uuid::new_v4())
+    .bind(&body.username)
+    .bind(password_hash)
+    .bind(&roles)
+    .fetch_optional(&state.pool)
+    .await?;
+    let user = row.ok_or(ApiError::Conflict("username already taken"))?;
+    Ok((StatusCode::CREATED, Json(user.try_into()?)))
+}
+
+pub async fn update_user(
+    State(state): State<AppState>,
+    _: Authorized<perm::UserManage>,
+    Path(user_id): Path<Uuid>,
+    ValidJson(body): ValidJson<UpdateUserBody>,
+) -> Result<Json<User>, ApiError> {
+    let mut tx = state.pool.begin().await?;
+    let row: Option<UserRow> = sqlx::query_as(&format!(
+        r#"
+        UPDATE auth.users
+        SET disabled_at = CASE WHEN $2 THEN COALESCE(disabled_at, NOW()) END
+        WHERE id = $1
+        RETURNING {USER_COLUMNS}
+        "#
+    ))
+    .bind(user_id)
+    .bind(body.disabled)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let user = row.ok_or(ApiError::NotFound("user not found"))?;
+    if body.disabled {
+        sqlx::query(
+            "UPDATE auth.refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(Json(user.try_into()?))
+}
+
+/// Create the first admin account from `AUTH_BOOTSTRAP_ADMIN` (`username:password`). Does
+/// nothing once any user exists; returns whether an account was created.
+pub async fn bootstrap_admin(
+    pool: &PgPool,
+    username: &str,
+    password: &str,
+) -> Result<bool, sqlx::Error> {
+    let password_hash = hash_password(password.to_string()).await;
+    let result = sqlx::query(
+        r#"
+        INSERT INTO auth.users (id, username, password_hash, roles)
+        SELECT $1, $2, $3, ARRAY['admin']
+        WHERE NOT EXISTS (SELECT 1 FROM auth.users)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(username)
+    .bind(password_hash)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
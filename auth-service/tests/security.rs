@@ -0,0 +1,257 @@
+//! Password hashing, refresh token rotation and reuse detection, logout through the revocation
+//! list, and disabled accounts. The auth-service runs in-process on its own runtime; tests that
+//! need Postgres are ignored unless run with
+//! `TEST_DATABASE_URL=postgres://... cargo test -p auth-service -- --include-ignored`.
+
+use auth_service::password::{hash_password, verify_password};
+use auth_service::{app, tokens::TokenIssuer};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use shared::auth::init_verifier;
+use shared::{AuthConfig, Role};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const SECRET: &str = "auth-security-test-secret";
+const REVOCATION_CACHE_SECS: u64 = 1;
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../migrations/001_create_schemas.sql"),
+    include_str!("../../migrations/012_auth_users.sql"),
+    include_str!("../../migrations/013_auth_user_roles.sql"),
+];
+
+struct Server {
+    base: String,
+    issuer: Arc<TokenIssuer>,
+}
+
+/// The auth-service on `TEST_DATABASE_URL`, also serving the revocation list `AuthUser` reads.
+/// It gets a runtime of its own so it outlives the runtime of whichever test starts it.
+fn server() -> &'static Server {
+    static SERVER: OnceLock<Server> = OnceLock::new();
+    SERVER.get_or_init(|| {
+        let database_url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL");
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let config = AuthConfig {
+            jwt_secret: Some(SECRET.to_string()),
+            revocation_url: Some(format!("{}/api/auth/revoked", base)),
+            revocation_cache_secs: REVOCATION_CACHE_SECS,
+            ..Default::default()
+        };
+        init_verifier(&config);
+        let issuer = Arc::new(TokenIssuer::from_env(&config).unwrap());
+        let tokens = issuer.clone();
+        std::thread::spawn(move || {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(async move {
+                    let pool = sqlx::PgPool::connect(&database_url).await.unwrap();
+                    for migration in MIGRATIONS {
+                        sqlx::raw_sql(migration).execute(&pool).await.unwrap();
+                    }
+                    listener.set_nonblocking(true).unwrap();
+                    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                    axum::serve(listener, app(pool, tokens, None))
+                        .await
+                        .unwrap();
+                })
+        });
+        Server { base, issuer }
+    })
+}
+
+async fn post(path: &str, token: Option<&str>, body: Value) -> (StatusCode, Value) {
+    let mut request = reqwest::Client::new()
+        .post(format!("{}{}", server().base, path))
+        .json(&body);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.unwrap();
+    let status = response.status();
+    (status, response.json().await.unwrap_or(Value::Null))
+}
+
+fn admin_token() -> String {
+    server()
+        .issuer
+        .issue(Uuid::new_v4(), &[Role::Admin])
+        .unwrap()
+        .token
+}
+
+/// A new student account; returns `(id, username)`.
+async fn create_user(password: &str) -> (String, String) {
+    let username = format!("user-{}", Uuid::new_v4());
+    let (status, user) = post(
+        "/api/auth/users",
+        Some(&admin_token()),
+        json!({"username": username, "password": password, "roles": ["student"]}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{user}");
+    (user["id"].as_str().unwrap().to_string(), username)
+}
+
+async fn set_disabled(user_id: &str, disabled: bool) {
+    let response = reqwest::Client::new()
+        .patch(format!("{}/api/auth/users/{}", server().base, user_id))
+        .bearer_auth(admin_token())
+        .json(&json!({ "disabled": disabled }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn login(username: &str, password: &str) -> (StatusCode, Value) {
+    post(
+        "/api/auth/login",
+        None,
+        json!({"username": username, "password": password}),
+    )
+    .await
+}
+
+/// Access and refresh token of a successful login.
+async fn session(username: &str, password: &str) -> (String, String) {
+    let (status, tokens) = login(username, password).await;
+    assert_eq!(status, StatusCode::OK, "{tokens}");
+    (
+        tokens["access_token"].as_str().unwrap().to_string(),
+        tokens["refresh_token"].as_str().unwrap().to_string(),
+    )
+}
+
+async fn refresh(refresh_token: &str) -> (StatusCode, Value) {
+    post(
+        "/api/auth/refresh",
+        None,
+        json!({ "refresh_token": refresh_token }),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn passwords_verify_only_against_their_own_hash() {
+    let hash = hash_password("correct horse".to_string()).await;
+    assert!(hash.starts_with("$argon2id$"));
+    // Salted: the same password never hashes the same way twice.
+    assert_ne!(hash, hash_password("correct horse".to_string()).await);
+
+    assert!(verify_password("correct horse".to_string(), Some(hash.clone())).await);
+    assert!(!verify_password("correct horsE".to_string(), Some(hash)).await);
+    assert!(
+        !verify_password(
+            "correct horse".to_string(),
+            Some("correct horse".to_string())
+        )
+        .await
+    );
+}
+
+#[tokio::test]
+async fn unknown_users_never_verify_but_cost_a_full_verification() {
+    let hash = hash_password("some password".to_string()).await;
+    let started = Instant::now();
+    assert!(!verify_password("wrong password".to_string(), Some(hash)).await);
+    let known = started.elapsed();
+
+    // The dummy hash's own password does not log in as a missing user either.
+    assert!(!verify_password("not-a-real-password".to_string(), None).await);
+    let started = Instant::now();
+    assert!(!verify_password("wrong password".to_string(), None).await);
+    let unknown = started.elapsed();
+    // Both run one Argon2 verification; allow plenty of scheduling noise.
+    assert!(unknown * 4 > known, "unknown {unknown:?}, known {known:?}");
+}
+
+#[tokio::test]
+#[ignore = "needs Postgres in TEST_DATABASE_URL"]
+async fn refresh_tokens_rotate() {
+    let (_, username) = create_user("rotate-password").await;
+    let (_, first) = session(&username, "rotate-password").await;
+
+    let (status, tokens) = refresh(&first).await;
+    assert_eq!(status, StatusCode::OK, "{tokens}");
+    let second = tokens["refresh_token"].as_str().unwrap();
+    assert_ne!(second, first);
+    assert!(tokens["access_token"].is_string());
+
+    let (status, tokens) = refresh(second).await;
+    assert_eq!(status, StatusCode::OK, "{tokens}");
+    assert_eq!(
+        refresh("not-a-refresh-token").await.0,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Postgres in TEST_DATABASE_URL"]
+async fn reusing_a_refresh_token_revokes_its_family() {
+    let (_, username) = create_user("reuse-password").await;
+    let (_, stolen) = session(&username, "reuse-password").await;
+    let (_, other_session) = session(&username, "reuse-password").await;
+
+    let (_, tokens) = refresh(&stolen).await;
+    let latest = tokens["refresh_token"].as_str().unwrap().to_string();
+    let (status, problem) = refresh(&stolen).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(problem["detail"], "refresh token reused");
+
+    // The legitimate holder's newest token went with the family.
+    let (status, problem) = refresh(&latest).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(problem["detail"], "refresh token revoked");
+    // Other logins of the same user are separate families.
+    assert_eq!(refresh(&other_session).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs Postgres in TEST_DATABASE_URL"]
+async fn logged_out_access_tokens_are_refused_once_the_list_refreshes() {
+    let (_, username) = create_user("logout-password").await;
+    let (access, refresh_token) = session(&username, "logout-password").await;
+
+    let (status, _) = post(
+        "/api/auth/logout",
+        Some(&access),
+        json!({ "refresh_token": refresh_token }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(refresh(&refresh_token).await.0, StatusCode::UNAUTHORIZED);
+
+    tokio::time::sleep(Duration::from_secs(REVOCATION_CACHE_SECS) + Duration::from_millis(200))
+        .await;
+    let (status, problem) = post("/api/auth/logout", Some(&access), json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{problem}");
+    assert_eq!(problem["detail"], "token revoked");
+    // Only the logged-out token is refused.
+    let (access, _) = session(&username, "logout-password").await;
+    assert_eq!(
+        post("/api/auth/logout", Some(&access), json!({})).await.0,
+        StatusCode::NO_CONTENT
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Postgres in TEST_DATABASE_URL"]
+async fn disabled_accounts_cannot_log_in_or_refresh() {
+    let (user_id, username) = create_user("disabled-password").await;
+    let (_, refresh_token) = session(&username, "disabled-password").await;
+
+    set_disabled(&user_id, true).await;
+    let (status, problem) = login(&username, "disabled-password").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(problem["detail"], "account disabled");
+    // A wrong password gets the usual answer, so it does not reveal the account is disabled.
+    let (_, problem) = login(&username, "wrong-password").await;
+    assert_eq!(problem["detail"], "invalid username or password");
+    assert_eq!(refresh(&refresh_token).await.0, StatusCode::UNAUTHORIZED);
+
+    set_disabled(&user_id, false).await;
+    session(&username, "disabled-password").await;
+}
@@ -0,0 +1,3 @@
+ALTER TABLE admin.courses ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE admin.courses ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+ALTER TABLE admin.courses ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+-- Keyset pagination indexes on (created_at, id), scoped by the usual list filters.
+CREATE INDEX IF NOT EXISTS courses_created_at_id_idx ON admin.courses (created_at, id);
+CREATE INDEX IF NOT EXISTS assignments_course_created_at_id_idx ON teacher.assignments (course_id, created_at, id);
+CREATE INDEX IF NOT EXISTS submissions_student_created_at_id_idx ON student.submissions (student_id, created_at, id);
+CREATE INDEX IF NOT EXISTS submissions_assignment_created_at_id_idx ON student.submissions (assignment_id, created_at, id);
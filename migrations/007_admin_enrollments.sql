@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS admin.enrollments (
+    id UUID PRIMARY KEY,
+    course_id UUID NOT NULL REFERENCES admin.courses (id),
+    user_id TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('teacher', 'student')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (course_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS enrollments_course_created_at_id_idx ON admin.enrollments (course_id, created_at, id);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS teacher.grades (
+    submission_id UUID PRIMARY KEY,
+    assignment_id UUID NOT NULL REFERENCES teacher.assignments (id),
+    student_id TEXT NOT NULL,
+    score DOUBLE PRECISION NOT NULL CHECK (score >= 0),
+    max_points DOUBLE PRECISION NOT NULL CHECK (max_points > 0),
+    feedback TEXT,
+    status TEXT NOT NULL DEFAULT 'draft' CHECK (status IN ('draft', 'released')),
+    graded_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    released_at TIMESTAMPTZ,
+    CHECK (score <= max_points)
+);
+CREATE INDEX IF NOT EXISTS grades_assignment_idx ON teacher.grades (assignment_id);
@@ -0,0 +1,10 @@
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS opens_at TIMESTAMPTZ;
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS due_at TIMESTAMPTZ;
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS closes_at TIMESTAMPTZ;
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS late_policy TEXT NOT NULL DEFAULT 'accept_flag'
+    CHECK (late_policy IN ('reject', 'accept_flag', 'penalty'));
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS late_penalty_percent SMALLINT
+    CHECK (late_penalty_percent BETWEEN 0 AND 100);
+ALTER TABLE teacher.grades ADD COLUMN IF NOT EXISTS late_penalty_percent SMALLINT
+    CHECK (late_penalty_percent BETWEEN 0 AND 100);
+ALTER TABLE student.submissions ADD COLUMN IF NOT EXISTS is_late BOOLEAN NOT NULL DEFAULT FALSE;
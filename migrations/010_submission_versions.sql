@@ -0,0 +1,75 @@
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS max_attempts INTEGER CHECK (max_attempts > 0);
+ALTER TABLE teacher.grades ADD COLUMN IF NOT EXISTS submission_version INTEGER NOT NULL DEFAULT 1;
+
+-- One logical submission per (assignment, student); every POST appends an immutable version.
+ALTER TABLE student.submissions ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE student.submissions ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE TABLE IF NOT EXISTS student.submission_versions (
+    id UUID PRIMARY KEY,
+    submission_id UUID NOT NULL REFERENCES student.submissions (id),
+    version INTEGER NOT NULL CHECK (version > 0),
+    content TEXT,
+    is_late BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (submission_id, version)
+);
+
+-- Fold rows created before versioning: the oldest row of each (assignment, student) becomes the
+-- logical submission and every row becomes one of its versions, in creation order. Grades are
+-- keyed by submission id without a foreign key (008), so a grade on a folded row moves to the
+-- logical submission with the version it was given for. A logical submission holds one grade;
+-- when several rows of the same (assignment, student) are graded nothing is folded until all but
+-- one of those grades are removed by hand. One block, so the fold applies entirely or not at all.
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1
+        FROM student.submissions s
+        JOIN teacher.grades g ON g.submission_id = s.id
+        GROUP BY s.assignment_id, s.student_id
+        HAVING count(*) > 1
+    ) THEN
+        RAISE EXCEPTION 'several submissions of one assignment and student are graded; '
+            'keep one grade per student before folding submissions into versions';
+    END IF;
+
+    WITH ranked AS (
+        SELECT content, is_late, created_at,
+               first_value(id) OVER w AS submission_id,
+               row_number() OVER w AS version
+        FROM student.submissions
+        WINDOW w AS (PARTITION BY assignment_id, student_id ORDER BY created_at, id)
+    )
+    INSERT INTO student.submission_versions (id, submission_id, version, content, is_late, created_at)
+    SELECT gen_random_uuid(), submission_id, version, content, is_late, created_at FROM ranked
+    ON CONFLICT (submission_id, version) DO NOTHING;
+
+    -- Grades on the oldest rows stay where they are, at version 1.
+    WITH ranked AS (
+        SELECT id,
+               first_value(id) OVER w AS submission_id,
+               row_number() OVER w AS version
+        FROM student.submissions
+        WINDOW w AS (PARTITION BY assignment_id, student_id ORDER BY created_at, id)
+    )
+    UPDATE teacher.grades g
+    SET submission_id = r.submission_id, submission_version = r.version
+    FROM ranked r
+    WHERE g.submission_id = r.id AND r.id <> r.submission_id;
+
+    UPDATE student.submissions s
+    SET version = v.version, content = v.content, is_late = v.is_late, updated_at = v.created_at
+    FROM (
+        SELECT DISTINCT ON (submission_id) submission_id, version, content, is_late, created_at
+        FROM student.submission_versions
+        ORDER BY submission_id, version DESC
+    ) v
+    WHERE s.id = v.submission_id;
+
+    DELETE FROM student.submissions s
+    WHERE NOT EXISTS (SELECT 1 FROM student.submission_versions v WHERE v.submission_id = s.id);
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS submissions_assignment_student_key
+    ON student.submissions (assignment_id, student_id);
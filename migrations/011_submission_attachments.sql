@@ -0,0 +1,19 @@
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS max_attachment_bytes BIGINT CHECK (max_attachment_bytes > 0);
+ALTER TABLE teacher.assignments ADD COLUMN IF NOT EXISTS allowed_mime_types TEXT[];
+
+-- Files uploaded with a submission version; the bytes live in the blob store under storage_key.
+CREATE TABLE IF NOT EXISTS student.submission_attachments (
+    id UUID PRIMARY KEY,
+    submission_id UUID NOT NULL,
+    version INTEGER NOT NULL,
+    filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL CHECK (size_bytes >= 0),
+    sha256 TEXT NOT NULL,
+    storage_key TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (submission_id, version) REFERENCES student.submission_versions (submission_id, version)
+);
+
+CREATE INDEX IF NOT EXISTS submission_attachments_submission_version_idx
+    ON student.submission_attachments (submission_id, version);
@@ -0,0 +1,16 @@
+-- Users may hold several roles; access tokens carry them all in `roles`.
+ALTER TABLE auth.users ADD COLUMN IF NOT EXISTS roles TEXT[];
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_schema = 'auth' AND table_name = 'users' AND column_name = 'role'
+    ) THEN
+        UPDATE auth.users SET roles = ARRAY[role] WHERE roles IS NULL;
+        ALTER TABLE auth.users DROP COLUMN role;
+    END IF;
+END $$;
+ALTER TABLE auth.users ALTER COLUMN roles SET NOT NULL;
+ALTER TABLE auth.users DROP CONSTRAINT IF EXISTS users_roles_check;
+ALTER TABLE auth.users ADD CONSTRAINT users_roles_check
+    CHECK (cardinality(roles) > 0 AND roles <@ ARRAY['admin', 'teacher', 'student']);
@@ -0,0 +1,36 @@
+#!/usr/bin/env bash
+# Create a local CA and one certificate per service for TLS_CERT_FILE / TLS_KEY_FILE / TLS_CA_FILE.
+# Usage: ./scripts/gen-certs.sh [out-dir] [service ...]
+# Each certificate is valid as server and client certificate for <service>,
+# <service>.edu.svc.cluster.local, localhost and 127.0.0.1. An existing CA in out-dir is reused,
+# so rerunning the script rotates the service certificates only.
+
+set -e
+OUT="${1:-certs}"
+shift || true
+SERVICES=("$@")
+[[ ${#SERVICES[@]} -eq 0 ]] && SERVICES=(admin-service teacher-service student-service auth-service)
+DAYS="${CERT_DAYS:-365}"
+
+mkdir -p "$OUT"
+if [[ ! -f "$OUT/ca.pem" ]]; then
+  openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+    -keyout "$OUT/ca-key.pem" -out "$OUT/ca.pem" -days 3650 -subj "/CN=edu-platform local CA" 2>/dev/null
+  echo "created $OUT/ca.pem"
+fi
+
+for SVC in "${SERVICES[@]}"; do
+  EXT=$(mktemp)
+  cat > "$EXT" <<EOF
+basicConstraints = CA:FALSE
+keyUsage = digitalSignature, keyEncipherment
+extendedKeyUsage = serverAuth, clientAuth
+subjectAltName = DNS:$SVC, DNS:$SVC.edu.svc.cluster.local, DNS:localhost, IP:127.0.0.1
+EOF
+  openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+    -keyout "$OUT/$SVC-key.pem" -out "$OUT/$SVC.csr" -subj "/CN=$SVC" 2>/dev/null
+  openssl x509 -req -in "$OUT/$SVC.csr" -CA "$OUT/ca.pem" -CAkey "$OUT/ca-key.pem" -CAcreateserial \
+    -out "$OUT/$SVC.pem" -days "$DAYS" -extfile "$EXT" 2>/dev/null
+  rm -f "$EXT" "$OUT/$SVC.csr"
+  echo "created $OUT/$SVC.pem"
+done
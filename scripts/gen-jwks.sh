@@ -0,0 +1,29 @@
+#!/usr/bin/env bash
+# Print a JWKS document for an RSA key, for JWKS_FILE / JWKS_URL.
+# Usage: ./scripts/gen-jwks.sh <rsa-key.pem> [kid] [<rsa-key2.pem> [kid2] ...]
+# Pass several keys to publish them side by side while rotating.
+# Create a key with: openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out jwt-key.pem
+
+set -e
+base64url() { base64 | tr -d '\n' | tr '+/' '-_' | tr -d '='; }
+
+if [[ $# -lt 1 ]]; then
+  echo "Usage: $0 <rsa-key.pem> [kid] ..." >&2
+  exit 1
+fi
+
+KEYS=""
+N=1
+while [[ $# -gt 0 ]]; do
+  PEM="$1"
+  KID="${2:-local-$N}"
+  shift; [[ $# -gt 0 ]] && shift
+  PUB=$(openssl pkey -in "$PEM" -pubout 2>/dev/null || cat "$PEM")
+  MODULUS=$(echo "$PUB" | openssl rsa -pubin -noout -modulus | cut -d= -f2 | xxd -r -p | base64url)
+  EXPONENT_HEX=$(printf '%06x' "$(echo "$PUB" | openssl rsa -pubin -noout -text | sed -n 's/^Exponent: \([0-9]*\).*/\1/p')")
+  EXPONENT=$(echo -n "$EXPONENT_HEX" | xxd -r -p | base64url)
+  KEY=$(printf '{"kty":"RSA","use":"sig","alg":"RS256","kid":"%s","n":"%s","e":"%s"}' "$KID" "$MODULUS" "$EXPONENT")
+  KEYS="${KEYS:+$KEYS,}$KEY"
+  N=$((N + 1))
+done
+echo "{\"keys\":[$KEYS]}"
@@ -1,17 +1,38 @@
 #!/usr/bin/env bash
-# Generate a JWT for testing. Usage: ./scripts/gen-jwt.sh <role> [secret]
-# role: admin | teacher | student
+# Generate a JWT for testing. Usage: ./scripts/gen-jwt.sh <role> [secret] [sub]
+# role: admin | teacher | student, or several comma-separated (e.g. teacher,admin) for a "roles" array
+# sub: user id (default: test-user). Use distinct ids when enrolling users in a course.
+# With JWT_PRIVATE_KEY=<rsa-private.pem> the token is signed RS256 with kid JWT_KID (default: local-1)
+# instead of HS256; publish the matching key with ./scripts/gen-jwks.sh.
 
 set -e
 base64url() { base64 | tr -d '\n' | tr '+/' '-_' | tr -d '='; }
 
 ROLE="${1:-admin}"
 SECRET="${2:-your-jwt-secret-change-in-production}"
+SUB="${3:-test-user}"
 EXP=$(($(date +%s) + 86400))  # 24h
-HEADER='{"alg":"HS256","typ":"JWT"}'
-PAYLOAD=$(printf '{"sub":"test-user","role":"%s","exp":%d}' "$ROLE" "$EXP")
+if [[ -n "${JWT_PRIVATE_KEY:-}" ]]; then
+  HEADER=$(printf '{"alg":"RS256","typ":"JWT","kid":"%s"}' "${JWT_KID:-local-1}")
+else
+  HEADER='{"alg":"HS256","typ":"JWT"}'
+fi
+if [[ "$ROLE" == *,* ]]; then
+  ROLES_JSON="[\"${ROLE//,/\",\"}\"]"
+  PAYLOAD=$(printf '{"sub":"%s","roles":%s,"exp":%d' "$SUB" "$ROLES_JSON" "$EXP")
+else
+  PAYLOAD=$(printf '{"sub":"%s","role":"%s","exp":%d' "$SUB" "$ROLE" "$EXP")
+fi
+# Optional iss / aud matching the services' JWT_ISSUER / JWT_AUDIENCE.
+[[ -n "${JWT_ISSUER:-}" ]] && PAYLOAD="$PAYLOAD,\"iss\":\"$JWT_ISSUER\""
+[[ -n "${JWT_AUDIENCE:-}" ]] && PAYLOAD="$PAYLOAD,\"aud\":\"$JWT_AUDIENCE\""
+PAYLOAD="$PAYLOAD}"
 B64H=$(echo -n "$HEADER" | base64 | tr -d '\n' | tr '+/' '-_' | tr -d '=')
 B64P=$(echo -n "$PAYLOAD" | base64 | tr -d '\n' | tr '+/' '-_' | tr -d '=')
 UNSIGNED="${B64H}.${B64P}"
-SIG=$(echo -n "$UNSIGNED" | openssl dgst -sha256 -hmac "$SECRET" -binary | base64 | tr -d '\n' | tr '+/' '-_' | tr -d '=')
+if [[ -n "${JWT_PRIVATE_KEY:-}" ]]; then
+  SIG=$(echo -n "$UNSIGNED" | openssl dgst -sha256 -sign "$JWT_PRIVATE_KEY" -binary | base64url)
+else
+  SIG=$(echo -n "$UNSIGNED" | openssl dgst -sha256 -hmac "$SECRET" -binary | base64url)
+fi
 echo "${UNSIGNED}.${SIG}"
@@ -15,6 +15,10 @@ SECRET="${JWT_SECRET:-your-jwt-secret-change-in-production}"
 ADMIN_URL="${ADMIN_URL:-http://localhost:8080}"
 TEACHER_URL="${TEACHER_URL:-http://localhost:8081}"
 STUDENT_URL="${STUDENT_URL:-http://localhost:8082}"
+AUTH_URL="${AUTH_URL:-http://localhost:8083}"
+# auth-service の AUTH_BOOTSTRAP_ADMIN と同じ値
+AUTH_ADMIN_USER="${AUTH_ADMIN_USER:-admin}"
+AUTH_ADMIN_PASSWORD="${AUTH_ADMIN_PASSWORD:-change-me-admin-password}"
 
 echo "=========================================="
 echo "  インテグレーションテスト (垂直スライス)"
@@ -66,11 +70,47 @@ if [[ -z "$COURSE_ID" ]]; then
 fi
 echo -e "${GREEN}✓ コース作成成功: $COURSE_ID${NC}"
 echo "  コース名: $COURSE_NAME"
+
+BLANK_COURSE_RESP=$(curl -s -w "\n%{http_code}" -X POST "$ADMIN_URL/api/admin/courses" \
+  -H "Authorization: Bearer $TOKEN_ADMIN" \
+  -H "Content-Type: application/json" \
+  -d '{"name":"   "}')
+if [[ "$(echo "$BLANK_COURSE_RESP" | tail -1)" != "422" ]] \
+  || ! echo "$BLANK_COURSE_RESP" | grep -q '"field":"name"'; then
+  echo -e "${RED}✗ 空のコース名が拒否されませんでした${NC}"
+  echo "  レスポンス: $BLANK_COURSE_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ 空のコース名は 422 (validation_failed)${NC}"
+echo ""
+
+# Step 1.5: 教師と学生をコースに登録
+echo "Step 1.5: 教師と学生をコースに登録 (admin-service)"
+TEACHER_ID="it-teacher-$(date +%s)"
+STUDENT_ID="it-student-$(date +%s)"
+for MEMBER in "$TEACHER_ID:teacher" "$STUDENT_ID:student"; do
+  ENROLL_STATUS=$(curl -s -o /dev/null -w "%{http_code}" -X POST "$ADMIN_URL/api/admin/courses/$COURSE_ID/enrollments" \
+    -H "Authorization: Bearer $TOKEN_ADMIN" \
+    -H "Content-Type: application/json" \
+    -d "{\"user_id\":\"${MEMBER%%:*}\",\"role\":\"${MEMBER##*:}\"}")
+  if [[ "$ENROLL_STATUS" != "201" ]]; then
+    echo -e "${RED}✗ 登録失敗: $MEMBER (HTTP $ENROLL_STATUS)${NC}"
+    exit 1
+  fi
+done
+echo -e "${GREEN}✓ 登録成功: $TEACHER_ID (teacher), $STUDENT_ID (student)${NC}"
+UNKNOWN_COURSE_STATUS=$(curl -s -o /dev/null -w "%{http_code}" "$ADMIN_URL/api/admin/courses/00000000-0000-0000-0000-000000000000/enrollments" \
+  -H "Authorization: Bearer $TOKEN_ADMIN")
+if [[ "$UNKNOWN_COURSE_STATUS" != "404" ]]; then
+  echo -e "${RED}✗ 存在しないコースの登録一覧が 404 になりません (HTTP $UNKNOWN_COURSE_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ 存在しないコースの登録一覧は 404${NC}"
 echo ""
 
-# Step 2: 教師が課題作成（admin-service にコース存在確認）
+# Step 2: 教師が課題作成（admin-service にコース存在確認と所属確認）
 echo "Step 2: 教師が課題作成 (teacher-service → admin-service)"
-TOKEN_TEACHER=$(./scripts/gen-jwt.sh teacher "$SECRET")
+TOKEN_TEACHER=$(./scripts/gen-jwt.sh teacher "$SECRET" "$TEACHER_ID")
 ASSIGNMENT_TITLE="Integration Test Assignment $(date +%s)"
 ASSIGN_RESP=$(curl -s -X POST "$TEACHER_URL/api/teacher/courses/$COURSE_ID/assignments" \
   -H "Authorization: Bearer $TOKEN_TEACHER" \
@@ -87,9 +127,9 @@ echo -e "${GREEN}✓ 課題作成成功: $ASSIGNMENT_ID${NC}"
 echo "  課題名: $ASSIGNMENT_TITLE"
 echo ""
 
-# Step 3: 学生が提出（teacher-service に課題存在確認）
-echo "Step 3: 学生が提出 (student-service → teacher-service)"
-TOKEN_STUDENT=$(./scripts/gen-jwt.sh student "$SECRET")
+# Step 3: 学生が提出（teacher-service に課題存在確認、admin-service に所属確認）
+echo "Step 3: 学生が提出 (student-service → teacher-service / admin-service)"
+TOKEN_STUDENT=$(./scripts/gen-jwt.sh student "$SECRET" "$STUDENT_ID")
 SUBMISSION_CONTENT="My test submission at $(date)"
 SUBMIT_RESP=$(curl -s -X POST "$STUDENT_URL/api/student/assignments/$ASSIGNMENT_ID/submissions" \
   -H "Authorization: Bearer $TOKEN_STUDENT" \
@@ -104,6 +144,92 @@ if [[ -z "$SUBMISSION_ID" ]]; then
 fi
 echo -e "${GREEN}✓ 提出成功: $SUBMISSION_ID${NC}"
 echo "  提出内容: $SUBMISSION_CONTENT"
+
+OUTSIDER_TOKEN=$(./scripts/gen-jwt.sh student "$SECRET" "it-outsider-$(date +%s)")
+OUTSIDER_STATUS=$(curl -s -o /dev/null -w "%{http_code}" -X POST "$STUDENT_URL/api/student/assignments/$ASSIGNMENT_ID/submissions" \
+  -H "Authorization: Bearer $OUTSIDER_TOKEN" \
+  -H "Content-Type: application/json" \
+  -d '{"content":"not enrolled"}')
+if [[ "$OUTSIDER_STATUS" != "403" ]]; then
+  echo -e "${RED}✗ 未登録の学生の提出が拒否されませんでした (HTTP $OUTSIDER_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ 未登録の学生の提出は 403 で拒否${NC}"
+
+RESUBMIT_RESP=$(curl -s -X POST "$STUDENT_URL/api/student/assignments/$ASSIGNMENT_ID/submissions" \
+  -H "Authorization: Bearer $TOKEN_STUDENT" \
+  -H "Content-Type: application/json" \
+  -d "{\"content\":\"$SUBMISSION_CONTENT (revised)\"}")
+if ! echo "$RESUBMIT_RESP" | grep -q "\"id\":\"$SUBMISSION_ID\".*\"version\":2"; then
+  echo -e "${RED}✗ 再提出が同じ提出物のバージョン 2 になりませんでした${NC}"
+  echo "  レスポンス: $RESUBMIT_RESP"
+  exit 1
+fi
+DIFF_RESP=$(curl -s "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID/diff?from=1&to=2" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+if ! echo "$DIFF_RESP" | grep -q '(revised)'; then
+  echo -e "${RED}✗ バージョン差分の取得失敗${NC}"
+  echo "  レスポンス: $DIFF_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ 再提出でバージョン 2 が追加され、差分を取得${NC}"
+
+ATTACHMENT_FILE=$(mktemp)
+echo "attachment body $(date)" > "$ATTACHMENT_FILE"
+ATTACHMENT_SHA=$(sha256sum "$ATTACHMENT_FILE" | cut -d' ' -f1)
+UPLOAD_RESP=$(curl -s -X POST "$STUDENT_URL/api/student/assignments/$ASSIGNMENT_ID/submissions" \
+  -H "Authorization: Bearer $TOKEN_STUDENT" \
+  -F "content=$SUBMISSION_CONTENT (with file)" \
+  -F "file=@$ATTACHMENT_FILE;filename=report.txt;type=text/plain")
+if ! echo "$UPLOAD_RESP" | grep -q '"version":3'; then
+  echo -e "${RED}✗ 添付ファイル付きの提出失敗${NC}"
+  echo "  レスポンス: $UPLOAD_RESP"
+  exit 1
+fi
+ATTACHMENTS=$(curl -s "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID/attachments" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+DOWNLOAD_URL=$(echo "$ATTACHMENTS" | grep -o '"download_url":"[^"]*"' | head -1 | sed 's/"download_url":"\([^"]*\)"/\1/')
+if ! echo "$ATTACHMENTS" | grep -q "\"sha256\":\"$ATTACHMENT_SHA\"" || [[ -z "$DOWNLOAD_URL" ]]; then
+  echo -e "${RED}✗ 添付ファイル一覧の取得失敗${NC}"
+  echo "  レスポンス: $ATTACHMENTS"
+  exit 1
+fi
+DOWNLOADED_SHA=$(curl -s "$STUDENT_URL$DOWNLOAD_URL" | sha256sum | cut -d' ' -f1)
+rm -f "$ATTACHMENT_FILE"
+if [[ "$DOWNLOADED_SHA" != "$ATTACHMENT_SHA" ]]; then
+  echo -e "${RED}✗ 署名付き URL からのダウンロード内容が一致しません${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ 添付ファイルをアップロードし、署名付き URL からダウンロード${NC}"
+
+# 締切を過ぎた課題: accept_flag は遅延として受け付け、reject は 409
+for POLICY in accept_flag reject; do
+  LATE_ASSIGN_RESP=$(curl -s -X POST "$TEACHER_URL/api/teacher/courses/$COURSE_ID/assignments" \
+    -H "Authorization: Bearer $TOKEN_TEACHER" \
+    -H "Content-Type: application/json" \
+    -d "{\"title\":\"Past due ($POLICY)\",\"due_at\":\"2020-01-01T00:00:00Z\",\"late_policy\":\"$POLICY\"}")
+  LATE_ASSIGNMENT_ID=$(echo "$LATE_ASSIGN_RESP" | grep -o '"id":"[^"]*"' | head -1 | sed 's/"id":"\([^"]*\)"/\1/')
+  if [[ -z "$LATE_ASSIGNMENT_ID" ]]; then
+    echo -e "${RED}✗ 締切済みの課題の作成失敗 ($POLICY)${NC}"
+    echo "  レスポンス: $LATE_ASSIGN_RESP"
+    exit 1
+  fi
+  LATE_RESP=$(curl -s -w "\n%{http_code}" -X POST "$STUDENT_URL/api/student/assignments/$LATE_ASSIGNMENT_ID/submissions" \
+    -H "Authorization: Bearer $TOKEN_STUDENT" \
+    -H "Content-Type: application/json" \
+    -d '{"content":"late work"}')
+  LATE_STATUS=$(echo "$LATE_RESP" | tail -1)
+  if [[ "$POLICY" == "accept_flag" ]] && { [[ "$LATE_STATUS" != "201" ]] || ! echo "$LATE_RESP" | grep -q '"is_late":true'; }; then
+    echo -e "${RED}✗ 締切後の提出が遅延として記録されませんでした${NC}"
+    echo "  レスポンス: $LATE_RESP"
+    exit 1
+  fi
+  if [[ "$POLICY" == "reject" ]] && [[ "$LATE_STATUS" != "409" ]]; then
+    echo -e "${RED}✗ reject ポリシーで締切後の提出が拒否されませんでした (HTTP $LATE_STATUS)${NC}"
+    exit 1
+  fi
+done
+echo -e "${GREEN}✓ 締切後の提出は accept_flag で is_late、reject で 409${NC}"
 echo ""
 
 # Step 4: 各エンドポイントで取得確認
@@ -131,6 +257,247 @@ else
   exit 1
 fi
 
+OTHER_TEACHER_TOKEN=$(./scripts/gen-jwt.sh teacher "$SECRET" "it-other-teacher-$(date +%s)")
+OTHER_ASSIGN_STATUS=$(curl -s -o /dev/null -w "%{http_code}" "$TEACHER_URL/api/teacher/assignments/$ASSIGNMENT_ID" \
+  -H "Authorization: Bearer $OTHER_TEACHER_TOKEN")
+if [[ "$OTHER_ASSIGN_STATUS" != "403" ]]; then
+  echo -e "${RED}✗ コース外の教師が課題を取得できました (HTTP $OTHER_ASSIGN_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ コース外の教師の課題取得は 403 で拒否${NC}"
+
+INTERNAL_STATUS=$(curl -s -o /dev/null -w "%{http_code}" "$TEACHER_URL/internal/assignments/$ASSIGNMENT_ID" \
+  -H "Authorization: Bearer $TOKEN_TEACHER")
+if [[ "$INTERNAL_STATUS" != "401" ]]; then
+  echo -e "${RED}✗ ユーザーのトークンで内部エンドポイントにアクセスできました (HTTP $INTERNAL_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ 内部エンドポイントはユーザーのトークンを 401 で拒否${NC}"
+
+echo "  提出物取得 (student-service、提出者本人)..."
+SUBMISSION_GET=$(curl -s "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+if echo "$SUBMISSION_GET" | grep -q "\"id\":\"$SUBMISSION_ID\""; then
+  echo -e "${GREEN}✓ 提出物取得成功${NC}"
+else
+  echo -e "${RED}✗ 提出物取得失敗${NC}"
+  echo "  レスポンス: $SUBMISSION_GET"
+  exit 1
+fi
+
+OTHER_SUBMISSION_RESP=$(curl -s -w "\n%{http_code} %{content_type}" "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID" \
+  -H "Authorization: Bearer $OUTSIDER_TOKEN")
+OTHER_SUBMISSION_STATUS=$(echo "$OTHER_SUBMISSION_RESP" | tail -1)
+if [[ "$OTHER_SUBMISSION_STATUS" != "404 application/problem+json" ]]; then
+  echo -e "${RED}✗ 他の学生の提出物が取得できました (HTTP $OTHER_SUBMISSION_STATUS)${NC}"
+  exit 1
+fi
+if ! echo "$OTHER_SUBMISSION_RESP" | grep -q '"code":"not_found"'; then
+  echo -e "${RED}✗ エラーレスポンスに code がありません${NC}"
+  echo "  レスポンス: $OTHER_SUBMISSION_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ 他の学生の提出物は 404 (application/problem+json)${NC}"
+
+BAD_ID_STATUS=$(curl -s -o /dev/null -w "%{http_code} %{content_type}" "$STUDENT_URL/api/student/submissions/not-a-uuid" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+if [[ "$BAD_ID_STATUS" != "400 application/problem+json" ]]; then
+  echo -e "${RED}✗ 不正な ID が problem+json の 400 になりません (HTTP $BAD_ID_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ 不正な ID は 400 (application/problem+json)${NC}"
+
+echo "  課題への提出物一覧 (student-service → teacher-service / admin-service、担当教師)..."
+SUBMISSION_LIST=$(curl -s "$STUDENT_URL/api/student/assignments/$ASSIGNMENT_ID/submissions" \
+  -H "Authorization: Bearer $TOKEN_TEACHER")
+if echo "$SUBMISSION_LIST" | grep -q "\"id\":\"$SUBMISSION_ID\""; then
+  echo -e "${GREEN}✓ 提出物一覧取得成功${NC}"
+else
+  echo -e "${RED}✗ 提出物一覧取得失敗${NC}"
+  echo "  レスポンス: $SUBMISSION_LIST"
+  exit 1
+fi
+
+echo "  コース一覧 (admin-service)..."
+COURSE_LIST=$(curl -s "$ADMIN_URL/api/admin/courses?limit=100" \
+  -H "Authorization: Bearer $TOKEN_ADMIN")
+if echo "$COURSE_LIST" | grep -q "\"id\":\"$COURSE_ID\""; then
+  echo -e "${GREEN}✓ コース一覧取得成功${NC}"
+else
+  echo -e "${YELLOW}! コース一覧の先頭ページに作成したコースが含まれていません${NC}"
+fi
+echo ""
+
+# Step 4.5: 採点と公開（teacher-service → student-service、student-service → teacher-service）
+echo "Step 4.5: 採点と公開"
+OVER_MAX_RESP=$(curl -s -w "\n%{http_code}" -X PUT "$TEACHER_URL/api/teacher/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_TEACHER" \
+  -H "Content-Type: application/json" \
+  -d '{"score":11,"max_points":10}')
+if [[ "$(echo "$OVER_MAX_RESP" | tail -1)" != "422" ]] \
+  || ! echo "$OVER_MAX_RESP" | grep -q '"field":"score"'; then
+  echo -e "${RED}✗ max_points を超える点数が拒否されませんでした${NC}"
+  echo "  レスポンス: $OVER_MAX_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ max_points を超える点数は 422 (validation_failed)${NC}"
+
+GRADE_RESP=$(curl -s -X PUT "$TEACHER_URL/api/teacher/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_TEACHER" \
+  -H "Content-Type: application/json" \
+  -d '{"score":8,"max_points":10,"feedback":"Good work"}')
+if ! echo "$GRADE_RESP" | grep -q '"status":"draft"'; then
+  echo -e "${RED}✗ 採点失敗${NC}"
+  echo "  レスポンス: $GRADE_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ 採点（下書き）成功${NC}"
+
+DRAFT_STATUS=$(curl -s -o /dev/null -w "%{http_code}" "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+if [[ "$DRAFT_STATUS" != "404" ]]; then
+  echo -e "${RED}✗ 公開前の採点が学生に見えています (HTTP $DRAFT_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ 公開前の採点は学生から見えない${NC}"
+
+RELEASE_RESP=$(curl -s -X POST "$TEACHER_URL/api/teacher/submissions/$SUBMISSION_ID/grade/release" \
+  -H "Authorization: Bearer $TOKEN_TEACHER")
+if ! echo "$RELEASE_RESP" | grep -q '"status":"released"'; then
+  echo -e "${RED}✗ 採点公開失敗${NC}"
+  echo "  レスポンス: $RELEASE_RESP"
+  exit 1
+fi
+STUDENT_GRADE=$(curl -s "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+if ! echo "$STUDENT_GRADE" | grep -q '"score":8'; then
+  echo -e "${RED}✗ 公開後の採点取得失敗${NC}"
+  echo "  レスポンス: $STUDENT_GRADE"
+  exit 1
+fi
+echo -e "${GREEN}✓ 公開後の採点を学生が取得${NC}"
+
+REGRADE_RESP=$(curl -s -X PUT "$TEACHER_URL/api/teacher/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_TEACHER" \
+  -H "Content-Type: application/json" \
+  -d '{"score":6,"max_points":10}')
+REGRADE_STUDENT_STATUS=$(curl -s -o /dev/null -w "%{http_code}" "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+if ! echo "$REGRADE_RESP" | grep -q '"status":"draft"' || [[ "$REGRADE_STUDENT_STATUS" != "404" ]]; then
+  echo -e "${RED}✗ 公開済みの採点の編集が再公開前に学生に見えています (HTTP $REGRADE_STUDENT_STATUS)${NC}"
+  echo "  レスポンス: $REGRADE_RESP"
+  exit 1
+fi
+curl -s -o /dev/null -X POST "$TEACHER_URL/api/teacher/submissions/$SUBMISSION_ID/grade/release" \
+  -H "Authorization: Bearer $TOKEN_TEACHER"
+echo -e "${GREEN}✓ 公開済みの採点を編集すると下書きに戻る${NC}"
+
+if ! echo "$STUDENT_GRADE" | grep -q '"stale":false'; then
+  echo -e "${RED}✗ 最新バージョンの採点が古いと表示されています${NC}"
+  echo "  レスポンス: $STUDENT_GRADE"
+  exit 1
+fi
+curl -s -o /dev/null -X POST "$STUDENT_URL/api/student/assignments/$ASSIGNMENT_ID/submissions" \
+  -H "Authorization: Bearer $TOKEN_STUDENT" \
+  -H "Content-Type: application/json" \
+  -d '{"content":"revised after grading"}'
+STALE_STUDENT_GRADE=$(curl -s "$STUDENT_URL/api/student/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_STUDENT")
+STALE_TEACHER_GRADE=$(curl -s "$TEACHER_URL/api/teacher/submissions/$SUBMISSION_ID/grade" \
+  -H "Authorization: Bearer $TOKEN_TEACHER")
+if ! echo "$STALE_STUDENT_GRADE" | grep -q '"stale":true' || ! echo "$STALE_TEACHER_GRADE" | grep -q '"stale":true'; then
+  echo -e "${RED}✗ 採点後の再提出で採点が古いと表示されません${NC}"
+  echo "  レスポンス: $STALE_STUDENT_GRADE"
+  echo "  レスポンス: $STALE_TEACHER_GRADE"
+  exit 1
+fi
+echo -e "${GREEN}✓ 採点後に再提出すると採点に stale が付く${NC}"
+echo ""
+
+# Step 5: コースをアーカイブすると課題を作成できなくなる
+echo "Step 5: コースのアーカイブ (admin-service → teacher-service)"
+ARCHIVE_RESP=$(curl -s -X PATCH "$ADMIN_URL/api/admin/courses/$COURSE_ID" \
+  -H "Authorization: Bearer $TOKEN_ADMIN" \
+  -H "Content-Type: application/json" \
+  -d '{"archived":true}')
+if ! echo "$ARCHIVE_RESP" | grep -q '"archived_at":"'; then
+  echo -e "${RED}✗ コースのアーカイブ失敗${NC}"
+  echo "  レスポンス: $ARCHIVE_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ コースをアーカイブしました${NC}"
+
+ARCHIVED_ASSIGN_STATUS=$(curl -s -o /dev/null -w "%{http_code}" -X POST "$TEACHER_URL/api/teacher/courses/$COURSE_ID/assignments" \
+  -H "Authorization: Bearer $TOKEN_TEACHER" \
+  -H "Content-Type: application/json" \
+  -d '{"title":"Should be rejected"}')
+if [[ "$ARCHIVED_ASSIGN_STATUS" != "409" ]]; then
+  echo -e "${RED}✗ アーカイブ済みコースへの課題作成が拒否されませんでした (HTTP $ARCHIVED_ASSIGN_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ アーカイブ済みコースへの課題作成は 409 で拒否${NC}"
+
+echo ""
+
+# Step 6: auth-service でログイン、リフレッシュ、ログアウト
+echo "Step 6: ログインとトークンのローテーション (auth-service)"
+LOGIN_RESP=$(curl -s -X POST "$AUTH_URL/api/auth/login" \
+  -H "Content-Type: application/json" \
+  -d "{\"username\":\"$AUTH_ADMIN_USER\",\"password\":\"$AUTH_ADMIN_PASSWORD\"}")
+ACCESS_TOKEN=$(echo "$LOGIN_RESP" | grep -o '"access_token":"[^"]*"' | sed 's/"access_token":"\([^"]*\)"/\1/')
+REFRESH_TOKEN=$(echo "$LOGIN_RESP" | grep -o '"refresh_token":"[^"]*"' | sed 's/"refresh_token":"\([^"]*\)"/\1/')
+if [[ -z "$ACCESS_TOKEN" || -z "$REFRESH_TOKEN" ]]; then
+  echo -e "${RED}✗ ログイン失敗${NC}"
+  echo "  レスポンス: $LOGIN_RESP"
+  exit 1
+fi
+LOGIN_COURSES_STATUS=$(curl -s -o /dev/null -w "%{http_code}" "$ADMIN_URL/api/admin/courses" \
+  -H "Authorization: Bearer $ACCESS_TOKEN")
+if [[ "$LOGIN_COURSES_STATUS" != "200" ]]; then
+  echo -e "${RED}✗ 発行されたトークンが admin-service で使えません (HTTP $LOGIN_COURSES_STATUS)${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ ログインしたトークンで admin-service を呼び出し${NC}"
+
+REFRESH_RESP=$(curl -s -X POST "$AUTH_URL/api/auth/refresh" \
+  -H "Content-Type: application/json" \
+  -d "{\"refresh_token\":\"$REFRESH_TOKEN\"}")
+NEW_ACCESS_TOKEN=$(echo "$REFRESH_RESP" | grep -o '"access_token":"[^"]*"' | sed 's/"access_token":"\([^"]*\)"/\1/')
+NEW_REFRESH_TOKEN=$(echo "$REFRESH_RESP" | grep -o '"refresh_token":"[^"]*"' | sed 's/"refresh_token":"\([^"]*\)"/\1/')
+if [[ -z "$NEW_REFRESH_TOKEN" || "$NEW_REFRESH_TOKEN" == "$REFRESH_TOKEN" ]]; then
+  echo -e "${RED}✗ リフレッシュ失敗${NC}"
+  echo "  レスポンス: $REFRESH_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ リフレッシュトークンをローテーション${NC}"
+
+LOGOUT_STATUS=$(curl -s -o /dev/null -w "%{http_code}" -X POST "$AUTH_URL/api/auth/logout" \
+  -H "Authorization: Bearer $NEW_ACCESS_TOKEN" \
+  -H "Content-Type: application/json" \
+  -d "{\"refresh_token\":\"$NEW_REFRESH_TOKEN\"}")
+REVOKED_RESP=$(curl -s "$AUTH_URL/api/auth/revoked")
+REUSE_STATUS=$(curl -s -o /dev/null -w "%{http_code}" -X POST "$AUTH_URL/api/auth/refresh" \
+  -H "Content-Type: application/json" \
+  -d "{\"refresh_token\":\"$NEW_REFRESH_TOKEN\"}")
+if [[ "$LOGOUT_STATUS" != "204" || "$REUSE_STATUS" != "401" ]] || ! echo "$REVOKED_RESP" | grep -q '"revoked":\["'; then
+  echo -e "${RED}✗ ログアウト後もトークンが有効です (logout HTTP $LOGOUT_STATUS, refresh HTTP $REUSE_STATUS)${NC}"
+  echo "  失効一覧: $REVOKED_RESP"
+  exit 1
+fi
+echo -e "${GREEN}✓ ログアウトでアクセストークンとリフレッシュトークンを失効${NC}"
+echo ""
+
+# Step 7: メトリクス
+echo "Step 7: メトリクス (/metrics)"
+METRICS=$(curl -s "$STUDENT_URL/metrics")
+if ! echo "$METRICS" | grep -q '^http_requests_total{method="POST",route="/api/student/assignments/:assignment_id/submissions",status="201"}' \
+  || ! echo "$METRICS" | grep -q '^upstream_attempts_total{status="200",upstream="teacher-service"}' \
+  || ! echo "$METRICS" | grep -q '^db_pool_max_connections'; then
+  echo -e "${RED}✗ student-service のメトリクスに提出や teacher-service 呼び出しが記録されていません${NC}"
+  exit 1
+fi
+echo -e "${GREEN}✓ リクエスト数、呼び出し先ごとの試行数、DB プールを記録${NC}"
+
 echo ""
 echo "=========================================="
 echo -e "${GREEN}✓ すべてのテストが成功しました！${NC}"
@@ -139,8 +506,12 @@ echo ""
 echo "検証内容:"
 echo "  1. admin-service でコース作成"
 echo "  2. teacher-service が admin-service を呼んで課題作成（サービス間連携 1）"
-echo "  3. student-service が teacher-service を呼んで提出（サービス間連携 2）"
-echo "  4. 各リソースの取得確認"
+echo "  3. student-service が teacher-service / admin-service を呼んで提出（サービス間連携 2）"
+echo "     未登録ユーザーの提出は拒否される"
+echo "  4. 各リソースの取得確認、採点と公開"
+echo "  5. アーカイブ済みコースへの課題作成が拒否されること"
+echo "  6. auth-service のログイン、リフレッシュトークンのローテーション、ログアウトによる失効"
+echo "  7. /metrics にリクエストと外部呼び出しのメトリクスが出ること"
 echo ""
 echo "作成されたリソース:"
 echo "  - コース ID: $COURSE_ID"
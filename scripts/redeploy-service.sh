@@ -6,7 +6,7 @@ cd "$(dirname "$0")/.."
 
 SERVICE="${1:-}"
 if [[ -z "$SERVICE" ]]; then
-  echo "Usage: $0 <admin-service|teacher-service|student-service|all>"
+  echo "Usage: $0 <admin-service|teacher-service|student-service|auth-service|all>"
   exit 1
 fi
 
@@ -42,14 +42,18 @@ case "$SERVICE" in
   student-service)
     build_one student-service Dockerfile.student-service 8082
     ;;
+  auth-service)
+    build_one auth-service Dockerfile.auth-service 8083
+    ;;
   all)
     build_one admin-service Dockerfile.admin-service 8080
     build_one teacher-service Dockerfile.teacher-service 8081
     build_one student-service Dockerfile.student-service 8082
+    build_one auth-service Dockerfile.auth-service 8083
     ;;
   *)
     echo "Unknown service: $SERVICE"
-    echo "Usage: $0 <admin-service|teacher-service|student-service|all>"
+    echo "Usage: $0 <admin-service|teacher-service|student-service|auth-service|all>"
     exit 1
     ;;
 esac
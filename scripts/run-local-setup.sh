@@ -21,6 +21,8 @@ echo "teacher-service をビルド中..."
 docker build -f Dockerfile.teacher-service -t teacher-service:latest . --progress=plain
 echo "student-service をビルド中..."
 docker build -f Dockerfile.student-service -t student-service:latest . --progress=plain
+echo "auth-service をビルド中..."
+docker build -f Dockerfile.auth-service -t auth-service:latest . --progress=plain
 
 echo ""
 echo "=== K8s マニフェストを適用 ==="
@@ -32,6 +34,7 @@ kubectl wait --for=condition=complete job/run-migrations -n edu --timeout=120s
 kubectl apply -f k8s/admin-service-configmap.yaml -f k8s/admin-service-secret.yaml -f k8s/admin-service-deployment.yaml
 kubectl apply -f k8s/teacher-service-configmap.yaml -f k8s/teacher-service-secret.yaml -f k8s/teacher-service-deployment.yaml
 kubectl apply -f k8s/student-service-configmap.yaml -f k8s/student-service-secret.yaml -f k8s/student-service-deployment.yaml
+kubectl apply -f k8s/auth-service-configmap.yaml -f k8s/auth-service-secret.yaml -f k8s/auth-service-deployment.yaml
 kubectl apply -f k8s/ingress.yaml
 
 echo ""
@@ -47,5 +50,6 @@ echo ""
 echo "  kubectl port-forward -n edu svc/admin-service 8080:8080 &"
 echo "  kubectl port-forward -n edu svc/teacher-service 8081:8080 &"
 echo "  kubectl port-forward -n edu svc/student-service 8082:8080 &"
+echo "  kubectl port-forward -n edu svc/auth-service 8083:8080 &"
 echo ""
 echo "その後、docs/QUICKSTART-ja.md の「動作確認（垂直スライス）」の curl を実行してください。"
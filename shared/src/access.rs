@@ -0,0 +1,248 @@
+//! Resource-level authorization on top of role permissions: a caller may act on a resource they
+//! own, or on one in a course where their enrollment role grants the permission. Every decision
+//! is logged under the `access` target.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::{Claims, Role};
+use crate::error::ApiError;
+use crate::http_client::{path_segment, ServiceClient};
+use crate::policy::{policy, Permission};
+
+/// A user's enrollment in a course, as reported by admin-service.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Membership {
+    pub role: Role,
+    pub course_archived: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("membership lookup failed: {0}")]
+pub struct MembershipError(pub String);
+
+/// Where course memberships are looked up.
+#[async_trait]
+pub trait Memberships: Send + Sync {
+    /// `None` when `user_id` is not enrolled in `course_id` (or the course does not exist).
+    async fn membership(
+        &self,
+        course_id: Uuid,
+        user_id: &str,
+    ) -> Result<Option<Membership>, MembershipError>;
+}
+
+/// Memberships from admin-service's internal membership route, asked as this service.
+pub struct AdminMemberships<'a> {
+    client: &'a ServiceClient,
+}
+
+impl<'a> AdminMemberships<'a> {
+    pub fn new(client: &'a ServiceClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Memberships for AdminMemberships<'_> {
+    async fn membership(
+        &self,
+        course_id: Uuid,
+        user_id: &str,
+    ) -> Result<Option<Membership>, MembershipError> {
+        let path = format!(
+            "/internal/courses/{}/members/{}",
+            course_id,
+            path_segment(user_id)
+        );
+        match self.client.get_json("admin-service", &path, None).await {
+            Ok(membership) => Ok(Some(membership)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(MembershipError(e.to_string())),
+        }
+    }
+}
+
+/// For resources without a course; every lookup answers "not enrolled".
+pub struct NoMemberships;
+
+#[async_trait]
+impl Memberships for NoMemberships {
+    async fn membership(&self, _: Uuid, _: &str) -> Result<Option<Membership>, MembershipError> {
+        Ok(None)
+    }
+}
+
+/// Attributes of the resource a handler is about to act on. Without an owner, course or
+/// overriding permission the role check made by `Authorized` is all there is.
+pub struct Resource<'a> {
+    kind: &'static str,
+    id: String,
+    owner: Option<&'a str>,
+    course: Option<(Uuid, Permission)>,
+    override_permission: Option<Permission>,
+    denied: Option<ApiError>,
+}
+
+impl<'a> Resource<'a> {
+    /// `kind` and `id` only identify the resource in the decision log.
+    pub fn new(kind: &'static str, id: impl ToString) -> Self {
+        Self {
+            kind,
+            id: id.to_string(),
+            owner: None,
+            course: None,
+            override_permission: None,
+            denied: None,
+        }
+    }
+
+    /// The user (`sub`) the resource belongs to; they may always access it.
+    pub fn owned_by(mut self, owner: &'a str) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    /// The course the resource belongs to. Members may access it if both their token roles and
+    /// their enrollment role grant `permission`.
+    pub fn in_course(mut self, course_id: Uuid, permission: Permission) -> Self {
+        self.course = Some((course_id, permission));
+        self
+    }
+
+    /// Callers holding `permission` may access the resource whoever owns it.
+    pub fn or_permission(mut self, permission: Permission) -> Self {
+        self.override_permission = Some(permission);
+        self
+    }
+
+    /// Response on denial, e.g. a 404 so others' resources look missing. Defaults to 403.
+    pub fn deny_as(mut self, error: ApiError) -> Self {
+        self.denied = Some(error);
+        self
+    }
+
+    pub fn is_owned_by(&self, sub: &str) -> bool {
+        self.owner == Some(sub)
+    }
+
+    fn denial(&self) -> ApiError {
+        self.denied.clone().unwrap_or(match self.course {
+            Some(_) => ApiError::Forbidden("not enrolled in this course"),
+            None => ApiError::Forbidden("access denied"),
+        })
+    }
+}
+
+/// Which attribute granted access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grant {
+    Owner,
+    Permission,
+    CourseMember,
+    /// The resource has no owner or course; the role check alone applies.
+    Unscoped,
+}
+
+impl Grant {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Grant::Owner => "owner",
+            Grant::Permission => "permission",
+            Grant::CourseMember => "course_member",
+            Grant::Unscoped => "unscoped",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Decision {
+    pub grant: Grant,
+    /// The caller's enrollment, when access came from course membership.
+    pub membership: Option<Membership>,
+}
+
+impl Claims {
+    /// Decide whether the caller may access `resource` and log the decision. A failed membership
+    /// lookup is a 502, never an allow.
+    pub async fn authorize(
+        &self,
+        resource: &Resource<'_>,
+        memberships: &dyn Memberships,
+    ) -> Result<Decision, ApiError> {
+        let decision = match self.decide(resource, memberships).await {
+            Ok(decision) => decision,
+            Err(e) => {
+                tracing::warn!(
+                    target: "access",
+                    sub = %self.sub,
+                    resource = resource.kind,
+                    id = %resource.id,
+                    "{}",
+                    e
+                );
+                return Err(ApiError::BadGateway("course service unavailable"));
+            }
+        };
+        match decision {
+            Some(decision) => {
+                tracing::info!(
+                    target: "access",
+                    sub = %self.sub,
+                    resource = resource.kind,
+                    id = %resource.id,
+                    grant = decision.grant.as_str(),
+                    "allow"
+                );
+                Ok(decision)
+            }
+            None => {
+                tracing::info!(
+                    target: "access",
+                    sub = %self.sub,
+                    resource = resource.kind,
+                    id = %resource.id,
+                    "deny"
+                );
+                Err(resource.denial())
+            }
+        }
+    }
+
+    async fn decide(
+        &self,
+        resource: &Resource<'_>,
+        memberships: &dyn Memberships,
+    ) -> Result<Option<Decision>, MembershipError> {
+        let granted = |grant| {
+            Some(Decision {
+                grant,
+                membership: None,
+            })
+        };
+        if resource.is_owned_by(&self.sub) {
+            return Ok(granted(Grant::Owner));
+        }
+        if resource.override_permission.is_some_and(|p| self.can(p)) {
+            return Ok(granted(Grant::Permission));
+        }
+        if let Some((course_id, permission)) = resource.course {
+            if !self.can(permission) {
+                return Ok(None);
+            }
+            return Ok(memberships
+                .membership(course_id, &self.sub)
+                .await?
+                .filter(|m| policy().allows(&[m.role], permission))
+                .map(|m| Decision {
+                    grant: Grant::CourseMember,
+                    membership: Some(m),
+                }));
+        }
+        if resource.owner.is_none() && resource.override_permission.is_none() {
+            return Ok(granted(Grant::Unscoped));
+        }
+        Ok(None)
+    }
+}
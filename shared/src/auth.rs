@@ -3,11 +3,19 @@ use axum::{
     extract::FromRequestParts,
     http::{request::Parts, StatusCode},
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use serde::Deserialize;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+use crate::config::AuthConfig;
+use crate::error::ApiError;
+use crate::jwks::{JwksCache, JwksError, JwksSource};
+use crate::revocation::{RevocationError, RevocationList};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     Admin,
@@ -15,30 +23,271 @@ pub enum Role {
     Student,
 }
 
-impl fmt::Display for Role {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Role {
+    pub fn as_str(&self) -> &'static str {
         match self {
-            Role::Admin => write!(f, "admin"),
-            Role::Teacher => write!(f, "teacher"),
-            Role::Student => write!(f, "student"),
+            Role::Admin => "admin",
+            Role::Teacher => "teacher",
+            Role::Student => "student",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(Role::Admin),
+            "teacher" => Ok(Role::Teacher),
+            "student" => Ok(Role::Student),
+            _ => Err(()),
         }
     }
 }
 
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Claims {
     pub sub: String,
-    pub role: Role,
+    /// Every recognised role the token carries; never empty.
+    pub roles: Vec<Role>,
     pub exp: i64,
     pub iss: Option<String>,
+    /// Token id; tokens without one cannot be revoked before they expire.
+    pub jti: Option<String>,
+}
+
+/// Value at a dotted claim path such as `realm_access.roles`.
+fn claim_at<'a>(claims: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(claims, |value, key| value.get(key))
+}
+
+/// Recognised roles found at `paths`, in order and without duplicates. A path may hold a
+/// single string or an array of strings (as identity providers list realm or client roles);
+/// unknown names such as `offline_access` are skipped.
+fn roles_from_claims(claims: &serde_json::Value, paths: &[String]) -> Vec<Role> {
+    let mut roles = Vec::new();
+    let names = paths
+        .iter()
+        .filter_map(|path| claim_at(claims, path))
+        .flat_map(|value| match value {
+            serde_json::Value::String(s) => vec![s.as_str()],
+            serde_json::Value::Array(items) => {
+                items.iter().filter_map(serde_json::Value::as_str).collect()
+            }
+            _ => Vec::new(),
+        });
+    for role in names.filter_map(|name| name.parse::<Role>().ok()) {
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+    roles
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    #[error("invalid token: {0}")]
+    Invalid(#[from] jsonwebtoken::errors::Error),
+    #[error("token has no kid")]
+    MissingKid,
+    #[error("token algorithm {0:?} does not match its key")]
+    AlgorithmMismatch(Algorithm),
+    #[error("invalid claims: {0}")]
+    Claims(serde_json::Error),
+    #[error("token has no recognised role")]
+    MissingRole,
+    #[error("{0}")]
+    Keys(#[from] JwksError),
+    #[error("token has been revoked")]
+    Revoked,
+    #[error("{0}")]
+    Revocation(#[from] RevocationError),
 }
 
-pub fn verify_jwt(token: &str, secret: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let key = DecodingKey::from_secret(secret.as_ref());
-    let mut validation = Validation::default();
-    validation.validate_exp = true;
-    let data = decode::<Claims>(token, &key, &validation)?;
-    Ok(data.claims)
+impl VerifyError {
+    /// Response body for the failure; each reason gets its own message.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            VerifyError::Invalid(e) => match e.kind() {
+                ErrorKind::ExpiredSignature => "token expired",
+                ErrorKind::ImmatureSignature => "token not yet valid",
+                ErrorKind::InvalidIssuer => "invalid token issuer",
+                ErrorKind::InvalidAudience => "invalid token audience",
+                ErrorKind::MissingRequiredClaim(c) if c == "iss" => "token has no issuer",
+                ErrorKind::MissingRequiredClaim(c) if c == "aud" => "token has no audience",
+                ErrorKind::MissingRequiredClaim(_) => "token is missing a required claim",
+                ErrorKind::InvalidSignature => "invalid token signature",
+                ErrorKind::InvalidAlgorithm => "unexpected token algorithm",
+                ErrorKind::Json(_) => "invalid token claims",
+                _ => "malformed token",
+            },
+            VerifyError::MissingKid => "token has no kid",
+            VerifyError::AlgorithmMismatch(_) => "unexpected token algorithm",
+            VerifyError::Claims(_) => "invalid token claims",
+            VerifyError::MissingRole => "token has no recognised role",
+            VerifyError::Keys(JwksError::UnknownKid(_)) => "unknown token signing key",
+            VerifyError::Keys(_) => "token keys unavailable",
+            VerifyError::Revoked => "token revoked",
+            VerifyError::Revocation(_) => "token revocation list unavailable",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            VerifyError::Keys(e) if !matches!(e, JwksError::UnknownKid(_)) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            VerifyError::Revocation(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+impl From<VerifyError> for ApiError {
+    fn from(e: VerifyError) -> Self {
+        if e.status() == StatusCode::SERVICE_UNAVAILABLE {
+            ApiError::Unavailable(e.reason())
+        } else {
+            ApiError::Unauthorized(e.reason())
+        }
+    }
+}
+
+/// Where verification keys come from: a shared HS256 secret, or asymmetric keys
+/// (RS256/ES256/EdDSA, ...) published in a JWKS document and selected by the token's `kid`.
+pub enum VerificationKeys {
+    Secret(String),
+    Jwks(JwksCache),
+}
+
+/// Verifies bearer tokens: signature, `exp`/`nbf` (with leeway), and the configured issuer and
+/// audience, then maps the role claim and checks the `jti` against the revocation list.
+pub struct JwtVerifier {
+    keys: VerificationKeys,
+    issuer: Option<String>,
+    audience: Vec<String>,
+    leeway_secs: u64,
+    role_claims: Vec<String>,
+    revocations: Option<RevocationList>,
+}
+
+impl JwtVerifier {
+    /// Keys come from `jwks_url`, then `jwks_file`, then the OIDC issuer's discovery document,
+    /// then `jwt_secret`. Once asymmetric keys are configured, HS256 tokens are no longer
+    /// accepted, so services cannot mint tokens for each other. With an OIDC issuer, `iss` must
+    /// match it unless `issuer` says otherwise.
+    pub fn new(config: &AuthConfig) -> Option<Self> {
+        let keys = if let Some(url) = &config.jwks_url {
+            VerificationKeys::Jwks(JwksCache::new(JwksSource::Url(url.clone())))
+        } else if let Some(path) = &config.jwks_file {
+            VerificationKeys::Jwks(JwksCache::new(JwksSource::File(path.into())))
+        } else if let Some(issuer) = &config.oidc_issuer {
+            VerificationKeys::Jwks(JwksCache::new(JwksSource::Discovery(issuer.clone())))
+        } else {
+            VerificationKeys::Secret(config.jwt_secret.clone()?)
+        };
+        Some(Self {
+            keys,
+            issuer: config.issuer.clone().or_else(|| config.oidc_issuer.clone()),
+            audience: config.audience.clone(),
+            leeway_secs: config.leeway_secs,
+            role_claims: if config.role_claims.is_empty() {
+                vec!["role".to_string(), "roles".to_string()]
+            } else {
+                config.role_claims.clone()
+            },
+            revocations: config.revocation_url.clone().map(|url| {
+                RevocationList::new(url, Duration::from_secs(config.revocation_cache_secs))
+            }),
+        })
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.validate_exp = true;
+        validation.validate_nbf = true;
+        validation.leeway = self.leeway_secs;
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            validation.required_spec_claims.insert("iss".to_string());
+        }
+        if self.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.audience);
+            validation.required_spec_claims.insert("aud".to_string());
+        }
+        validation
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<Claims, VerifyError> {
+        let raw = match &self.keys {
+            VerificationKeys::Secret(secret) => {
+                let key = DecodingKey::from_secret(secret.as_ref());
+                decode::<serde_json::Value>(token, &key, &self.validation(Algorithm::HS256))?
+                    .claims
+            }
+            VerificationKeys::Jwks(jwks) => {
+                let header = decode_header(token)?;
+                let kid = header.kid.ok_or(VerifyError::MissingKid)?;
+                let key = jwks.key(&kid).await?;
+                if header.alg != key.algorithm {
+                    return Err(VerifyError::AlgorithmMismatch(header.alg));
+                }
+                decode::<serde_json::Value>(token, &key.key, &self.validation(key.algorithm))?
+                    .claims
+            }
+        };
+        #[derive(Deserialize)]
+        struct Registered {
+            sub: String,
+            exp: i64,
+            iss: Option<String>,
+            jti: Option<String>,
+        }
+        let roles = roles_from_claims(&raw, &self.role_claims);
+        if roles.is_empty() {
+            return Err(VerifyError::MissingRole);
+        }
+        let Registered { sub, exp, iss, jti } =
+            serde_json::from_value(raw).map_err(VerifyError::Claims)?;
+        if let (Some(revocations), Some(jti)) = (&self.revocations, &jti) {
+            if revocations.is_revoked(jti).await? {
+                return Err(VerifyError::Revoked);
+            }
+        }
+        Ok(Claims {
+            sub,
+            roles,
+            exp,
+            iss,
+            jti,
+        })
+    }
+}
+
+static VERIFIER: OnceLock<Option<JwtVerifier>> = OnceLock::new();
+
+/// Install the verifier `AuthUser` uses from the service's config; call once at startup.
+pub fn init_verifier(config: &AuthConfig) {
+    if VERIFIER.set(JwtVerifier::new(config)).is_err() {
+        tracing::warn!("JWT verifier already initialised; ignoring new config");
+    }
+}
+
+/// Process-wide verifier used by `AuthUser`; built from the environment if `init_verifier`
+/// was not called.
+pub fn verifier() -> Option<&'static JwtVerifier> {
+    VERIFIER
+        .get_or_init(|| JwtVerifier::new(&AuthConfig::from_env()))
+        .as_ref()
 }
 
 /// Extract Bearer token from Authorization header and verify; yields Claims.
@@ -49,22 +298,36 @@ impl<S> FromRequestParts<S> for AuthUser
 where
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         let auth = parts
             .headers
             .get("Authorization")
             .and_then(|v| v.to_str().ok())
-            .ok_or((StatusCode::UNAUTHORIZED, "Missing Authorization header"))?;
+            .ok_or(ApiError::Unauthorized("Missing Authorization header"))?;
         let token = auth
             .strip_prefix("Bearer ")
-            .ok_or((StatusCode::UNAUTHORIZED, "Invalid Authorization format"))?;
-        let secret = std::env::var("JWT_SECRET").map_err(|_| {
-            (StatusCode::INTERNAL_SERVER_ERROR, "JWT_SECRET not set")
+            .ok_or(ApiError::Unauthorized("Invalid Authorization format"))?;
+        let verifier = verifier().ok_or_else(|| {
+            tracing::error!(
+                "no token verifier: set JWT_SECRET, JWKS_URL, JWKS_FILE or OIDC_ISSUER_URL"
+            );
+            ApiError::Internal("authentication is not configured")
+        })?;
+        let claims = verifier.verify(token).await.map_err(|e| {
+            if e.status().is_server_error() {
+                tracing::error!("token verification failed: {}", e);
+            } else {
+                tracing::debug!("rejected token: {}", e);
+            }
+            ApiError::from(e)
         })?;
-        let claims = verify_jwt(token, &secret)
-            .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired token"))?;
+        // Fields declared by `trace_context::make_span`; the token itself is never recorded.
+        let span = tracing::Span::current();
+        span.record("sub", claims.sub.as_str());
+        let roles: Vec<&str> = claims.roles.iter().map(Role::as_str).collect();
+        span.record("role", roles.join(",").as_str());
         Ok(AuthUser(claims))
     }
 }
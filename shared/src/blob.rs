@@ -0,0 +1,219 @@
+//! Storage for uploaded files behind the `BlobStore` trait: files under a local directory or
+//! objects in an S3-compatible bucket, picked by `BLOB_STORE`. `UrlSigner` issues the
+//! time-limited links files are downloaded with.
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use object_store::{aws::AmazonS3Builder, path::Path as ObjectPath, ObjectStore};
+use sha2::Sha256;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlobError {
+    #[error("blob not found: {0}")]
+    NotFound(String),
+    #[error("invalid blob key: {0}")]
+    InvalidKey(String),
+    #[error("blob store misconfigured: {0}")]
+    Config(String),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("object store: {0}")]
+    ObjectStore(#[from] object_store::Error),
+}
+
+/// Storage for uploaded files. Keys are `/`-separated relative paths chosen by the caller.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, data: Bytes, content_type: &str) -> Result<(), BlobError>;
+    async fn get(&self, key: &str) -> Result<Bytes, BlobError>;
+    async fn delete(&self, key: &str) -> Result<(), BlobError>;
+}
+
+fn check_key(key: &str) -> Result<(), BlobError> {
+    if key.is_empty()
+        || key.starts_with('/')
+        || key
+            .split('/')
+            .any(|s| s.is_empty() || s == "." || s == "..")
+    {
+        return Err(BlobError::InvalidKey(key.to_string()));
+    }
+    Ok(())
+}
+
+/// Stores blobs as files under a root directory. Content types are not persisted.
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, BlobError> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, data: Bytes, _content_type: &str) -> Result<(), BlobError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Write then rename so readers never see a partial file.
+        let tmp = path.with_extension("part");
+        tokio::fs::write(&tmp, &data).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes, BlobError> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Bytes::from(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(BlobError::NotFound(key.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), BlobError> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Stores blobs in an S3-compatible bucket (AWS S3, MinIO, ...).
+pub struct S3BlobStore {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl S3BlobStore {
+    /// Over any `object_store` backend, e.g. `InMemory` as a stand-in for a bucket.
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store }
+    }
+
+    /// Reads the standard `AWS_*` variables (`AWS_ENDPOINT`, `AWS_REGION`, `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY`, `AWS_ALLOW_HTTP`, ...) for everything but the bucket.
+    pub fn from_env(bucket: &str) -> Result<Self, BlobError> {
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()?;
+        Ok(Self::new(Arc::new(store)))
+    }
+
+    fn path(key: &str) -> Result<ObjectPath, BlobError> {
+        check_key(key)?;
+        ObjectPath::parse(key).map_err(|_| BlobError::InvalidKey(key.to_string()))
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(&self, key: &str, data: Bytes, content_type: &str) -> Result<(), BlobError> {
+        let mut attributes = object_store::Attributes::new();
+        attributes.insert(
+            object_store::Attribute::ContentType,
+            content_type.to_string().into(),
+        );
+        let opts = object_store::PutOptions {
+            attributes,
+            ..Default::default()
+        };
+        self.store
+            .put_opts(&Self::path(key)?, data.into(), opts)
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes, BlobError> {
+        match self.store.get(&Self::path(key)?).await {
+            Ok(res) => Ok(res.bytes().await?),
+            Err(object_store::Error::NotFound { .. }) => Err(BlobError::NotFound(key.to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), BlobError> {
+        match self.store.delete(&Self::path(key)?).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Build the blob store selected by `BLOB_STORE`: `local` (default, rooted at `BLOB_LOCAL_ROOT`)
+/// or `s3` (bucket `BLOB_S3_BUCKET`).
+pub fn blob_store_from_env() -> Result<Arc<dyn BlobStore>, BlobError> {
+    match std::env::var("BLOB_STORE").as_deref().unwrap_or("local") {
+        "local" => {
+            let root = std::env::var("BLOB_LOCAL_ROOT").unwrap_or_else(|_| "./blobs".to_string());
+            Ok(Arc::new(LocalBlobStore::new(root)))
+        }
+        "s3" => {
+            let bucket = std::env::var("BLOB_S3_BUCKET")
+                .map_err(|_| BlobError::Config("BLOB_S3_BUCKET is required".to_string()))?;
+            Ok(Arc::new(S3BlobStore::from_env(&bucket)?))
+        }
+        other => Err(BlobError::Config(format!("unknown BLOB_STORE {:?}", other))),
+    }
+}
+
+/// Issues and checks time-limited download links: `path?expires=<unix>&signature=<hmac>`,
+/// where the HMAC-SHA256 covers the path and expiry.
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: Arc<[u8]>,
+}
+
+impl UrlSigner {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: Arc::from(secret.as_ref()),
+        }
+    }
+
+    fn mac(&self, path: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(path.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+
+    /// Returns the signed URL and its expiry.
+    pub fn sign(&self, path: &str, ttl: Duration) -> (String, chrono::DateTime<chrono::Utc>) {
+        let expires_at = chrono::Utc::now()
+            + chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::hours(1));
+        let expires = expires_at.timestamp();
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(path, expires).finalize().into_bytes());
+        (
+            format!("{}?expires={}&signature={}", path, expires, signature),
+            expires_at,
+        )
+    }
+
+    /// True if `signature` was issued for `path` and `expires` has not passed.
+    pub fn verify(&self, path: &str, expires: i64, signature: &str) -> bool {
+        if expires < chrono::Utc::now().timestamp() {
+            return false;
+        }
+        let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+        self.mac(path, expires).verify_slice(&signature).is_ok()
+    }
+}
@@ -0,0 +1,317 @@
+//! Circuit breaker for one upstream. Closed, it counts outcomes in a sliding window and opens
+//! once the failure rate crosses the threshold; open, it rejects calls until `open_duration`
+//! has passed; half-open, it admits up to `half_open_probes` calls at a time and closes after
+//! that many succeed, or opens again on the first failure. Transitions are logged under the
+//! `circuit` target, broadcast as `CircuitEvent`s and reported in `upstream_circuit_state`.
+
+use std::collections::VecDeque;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::metrics;
+
+/// Outcomes are counted in buckets of this length, so the window's memory is bounded.
+const BUCKET: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct CircuitConfig {
+    /// Failure rate (0.0–1.0) over the window at which the circuit opens.
+    pub failure_rate: f64,
+    /// Calls the window must hold before the rate is judged.
+    pub min_calls: u32,
+    pub window: Duration,
+    pub open_duration: Duration,
+    /// Calls admitted at once while half-open; as many must succeed to close.
+    pub half_open_probes: u32,
+}
+
+impl Default for CircuitConfig {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.5,
+            min_calls: 5,
+            window: Duration::from_secs(30),
+            open_duration: Duration::from_secs(30),
+            half_open_probes: 1,
+        }
+    }
+}
+
+impl CircuitConfig {
+    /// `<prefix>FAILURE_RATE`, `<prefix>MIN_CALLS`, `<prefix>WINDOW_SECS`, `<prefix>OPEN_SECS`
+    /// and `<prefix>HALF_OPEN_PROBES`; unset values are taken from `base`.
+    pub fn from_env(prefix: &str, base: &CircuitConfig) -> Self {
+        fn var<T: std::str::FromStr>(prefix: &str, name: &str) -> Option<T> {
+            env::var(format!("{}{}", prefix, name))
+                .ok()
+                .and_then(|s| s.parse().ok())
+        }
+        Self {
+            failure_rate: var(prefix, "FAILURE_RATE").unwrap_or(base.failure_rate),
+            min_calls: var(prefix, "MIN_CALLS").unwrap_or(base.min_calls),
+            window: var(prefix, "WINDOW_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(base.window),
+            open_duration: var(prefix, "OPEN_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(base.open_duration),
+            half_open_probes: var(prefix, "HALF_OPEN_PROBES")
+                .unwrap_or(base.half_open_probes)
+                .max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitStatus {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitStatus::Closed => "closed",
+            CircuitStatus::Open => "open",
+            CircuitStatus::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// A state change of the circuit for `upstream`.
+#[derive(Debug, Clone)]
+pub struct CircuitEvent {
+    pub upstream: String,
+    pub from: CircuitStatus,
+    pub to: CircuitStatus,
+}
+
+enum State {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { in_flight: u32, successes: u32 },
+}
+
+impl State {
+    fn status(&self) -> CircuitStatus {
+        match self {
+            State::Closed => CircuitStatus::Closed,
+            State::Open { .. } => CircuitStatus::Open,
+            State::HalfOpen { .. } => CircuitStatus::HalfOpen,
+        }
+    }
+}
+
+struct Bucket {
+    start: Instant,
+    calls: u32,
+    failures: u32,
+}
+
+struct Inner {
+    state: State,
+    /// Bumped on every transition; permits carry the generation they were admitted in.
+    generation: u64,
+    window: VecDeque<Bucket>,
+}
+
+pub struct CircuitBreaker {
+    upstream: String,
+    config: CircuitConfig,
+    inner: Mutex<Inner>,
+    events: broadcast::Sender<CircuitEvent>,
+}
+
+impl CircuitBreaker {
+    pub fn new(
+        upstream: impl Into<String>,
+        config: CircuitConfig,
+        events: broadcast::Sender<CircuitEvent>,
+    ) -> Self {
+        let upstream = upstream.into();
+        metrics::record_circuit_state(&upstream, CircuitStatus::Closed);
+        Self {
+            upstream,
+            config,
+            inner: Mutex::new(Inner {
+                state: State::Closed,
+                generation: 0,
+                window: VecDeque::new(),
+            }),
+            events,
+        }
+    }
+
+    pub fn upstream(&self) -> &str {
+        &self.upstream
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        self.inner.lock().unwrap().state.status()
+    }
+
+    /// Admit a call, or `None` while the circuit is open or its half-open probes are taken.
+    /// The permit must be resolved with `success` or `failure`; dropping it frees its slot
+    /// without recording an outcome.
+    pub fn try_acquire(&self) -> Option<CircuitPermit<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            State::Closed => {}
+            State::Open { until } if Instant::now() >= until => {
+                self.transition(
+                    &mut inner,
+                    State::HalfOpen {
+                        in_flight: 1,
+                        successes: 0,
+                    },
+                );
+            }
+            State::Open { .. } => return None,
+            State::HalfOpen {
+                ref mut in_flight, ..
+            } => {
+                if *in_flight >= self.config.half_open_probes {
+                    return None;
+                }
+                *in_flight += 1;
+            }
+        }
+        Some(CircuitPermit {
+            breaker: self,
+            generation: inner.generation,
+            resolved: false,
+        })
+    }
+
+    fn record(&self, generation: u64, failed: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        // Outcomes of calls admitted before the last transition say nothing about the current
+        // state; a slow probe from an earlier half-open episode must not count in this one.
+        if generation != inner.generation {
+            return;
+        }
+        match inner.state {
+            State::Closed => {
+                let now = Instant::now();
+                self.count(&mut inner.window, now, failed);
+                let (calls, failures) = inner
+                    .window
+                    .iter()
+                    .fold((0, 0), |(c, f), b| (c + b.calls, f + b.failures));
+                if calls >= self.config.min_calls
+                    && f64::from(failures) >= self.config.failure_rate * f64::from(calls)
+                {
+                    self.open(&mut inner, now);
+                }
+            }
+            State::HalfOpen {
+                ref mut in_flight,
+                ref mut successes,
+            } => {
+                *in_flight = in_flight.saturating_sub(1);
+                if failed {
+                    self.open(&mut inner, Instant::now());
+                } else {
+                    *successes += 1;
+                    if *successes >= self.config.half_open_probes {
+                        inner.window.clear();
+                        self.transition(&mut inner, State::Closed);
+                    }
+                }
+            }
+            // No permits are admitted while open.
+            State::Open { .. } => {}
+        }
+    }
+
+    fn release(&self, generation: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if generation != inner.generation {
+            return;
+        }
+        if let State::HalfOpen {
+            ref mut in_flight, ..
+        } = inner.state
+        {
+            *in_flight = in_flight.saturating_sub(1);
+        }
+    }
+
+    /// Add an outcome to the current bucket and drop buckets that left the window.
+    fn count(&self, window: &mut VecDeque<Bucket>, now: Instant, failed: bool) {
+        while window
+            .front()
+            .is_some_and(|b| now.duration_since(b.start) >= self.config.window)
+        {
+            window.pop_front();
+        }
+        if window
+            .back()
+            .is_none_or(|b| now.duration_since(b.start) >= BUCKET)
+        {
+            window.push_back(Bucket {
+                start: now,
+                calls: 0,
+                failures: 0,
+            });
+        }
+        let bucket = window.back_mut().expect("bucket was just pushed");
+        bucket.calls += 1;
+        bucket.failures += u32::from(failed);
+    }
+
+    fn open(&self, inner: &mut Inner, now: Instant) {
+        inner.window.clear();
+        let until = now + self.config.open_duration;
+        self.transition(inner, State::Open { until });
+    }
+
+    fn transition(&self, inner: &mut Inner, to: State) {
+        let from = inner.state.status();
+        inner.state = to;
+        inner.generation += 1;
+        let to = inner.state.status();
+        metrics::record_circuit_state(&self.upstream, to);
+        if to == CircuitStatus::Open {
+            tracing::warn!(target: "circuit", upstream = %self.upstream, from = from.as_str(), "circuit open");
+        } else {
+            tracing::info!(target: "circuit", upstream = %self.upstream, from = from.as_str(), "circuit {}", to.as_str());
+        }
+        // Nobody listening is fine.
+        let _ = self.events.send(CircuitEvent {
+            upstream: self.upstream.clone(),
+            from,
+            to,
+        });
+    }
+}
+
+/// A call admitted by `CircuitBreaker::try_acquire`.
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    generation: u64,
+    resolved: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn success(mut self) {
+        self.resolved = true;
+        self.breaker.record(self.generation, false);
+    }
+
+    pub fn failure(mut self) {
+        self.resolved = true;
+        self.breaker.record(self.generation, true);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if !self.resolved {
+            self.breaker.release(self.generation);
+        }
+    }
+}
@@ -1,16 +1,206 @@
+use std::collections::BTreeMap;
 use std::env;
 
+use crate::circuit::CircuitConfig;
+use crate::retry::RetryPolicy;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub service_name: String,
     pub http_port: u16,
     pub database_url: String,
-    pub jwt_secret: String,
+    pub auth: AuthConfig,
+    /// HS256 secret shared by the services for `/internal/...` calls; see `service_auth`.
+    pub service_token_secret: Option<String>,
+    /// Base URLs of the services `ServiceClient` can call, by `SERVICE_NAME`.
+    pub upstreams: BTreeMap<String, String>,
+    /// How `ServiceClient` retries failed calls; see `RetryPolicy::from_env`.
+    pub retry: RetryPolicy,
+    /// Circuit breaker thresholds per upstream: `CIRCUIT_*` for all, overridden by
+    /// `<UPSTREAM>_CIRCUIT_*` (e.g. `ADMIN_SERVICE_CIRCUIT_OPEN_SECS`); see
+    /// `CircuitConfig::from_env`.
+    pub circuits: BTreeMap<String, CircuitConfig>,
+    /// `ServiceClient` calls in flight per upstream (`HTTP_MAX_CONCURRENCY`); calls beyond it
+    /// are shed.
+    pub max_concurrency: usize,
+    /// HTTPS listener and mutual TLS for `ServiceClient`; plain HTTP when unset.
+    pub tls: Option<TlsConfig>,
+    /// OTLP/HTTP endpoint spans are exported to: `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`, or
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` + `/v1/traces`. Spans are not exported when unset.
+    pub otlp_endpoint: Option<String>,
+    pub log_format: LogFormat,
     pub rust_log: String,
 }
 
+/// Log line format (`LOG_FORMAT`): human-readable `text` (the default) or one JSON object per
+/// line, carrying the fields of the current span such as `request_id` and `sub`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// How `AuthUser` verifies bearer tokens. Each service sets its own `JWT_AUDIENCE`.
+#[derive(Clone, Debug, Default)]
+pub struct AuthConfig {
+    /// HS256 secret; optional when tokens are verified against a JWKS (`JWKS_URL`/`JWKS_FILE`).
+    pub jwt_secret: Option<String>,
+    pub jwks_url: Option<String>,
+    pub jwks_file: Option<String>,
+    /// OIDC issuer URL; keys are found via its `.well-known/openid-configuration`.
+    pub oidc_issuer: Option<String>,
+    /// Dotted claim paths searched for roles, e.g. `realm_access.roles`; roles from every
+    /// path are combined.
+    pub role_claims: Vec<String>,
+    /// Required `iss`; any issuer is accepted when unset.
+    pub issuer: Option<String>,
+    /// Accepted `aud` values (comma-separated `JWT_AUDIENCE`); not checked when empty.
+    pub audience: Vec<String>,
+    /// Clock skew tolerated when checking `exp` and `nbf`.
+    pub leeway_secs: u64,
+    /// Published `jti` denylist (the auth-service's `/api/auth/revoked`); not checked when unset.
+    pub revocation_url: Option<String>,
+    /// How long a fetched denylist is used before it is refetched.
+    pub revocation_cache_secs: u64,
+    /// JSON role-to-permission mapping; the built-in `Policy` applies when unset.
+    pub permissions_file: Option<String>,
+}
+
+/// The platform's services with their `*_SERVICE_URL` overrides, plus any extra or replaced
+/// entries from `UPSTREAM_URLS` (comma-separated `name=url`).
+fn upstreams_from_env() -> BTreeMap<String, String> {
+    let mut upstreams = BTreeMap::new();
+    for (name, var) in [
+        ("admin-service", "ADMIN_SERVICE_URL"),
+        ("teacher-service", "TEACHER_SERVICE_URL"),
+        ("student-service", "STUDENT_SERVICE_URL"),
+        ("auth-service", "AUTH_SERVICE_URL"),
+    ] {
+        let url = env::var(var).unwrap_or_else(|_| format!("http://{}:8080", name));
+        upstreams.insert(name.to_string(), url);
+    }
+    for entry in list_var("UPSTREAM_URLS").unwrap_or_default() {
+        match entry.split_once('=') {
+            Some((name, url)) => {
+                upstreams.insert(name.trim().to_string(), url.trim().to_string());
+            }
+            None => tracing::warn!(
+                "ignoring UPSTREAM_URLS entry {:?}: expected name=url",
+                entry
+            ),
+        }
+    }
+    upstreams
+}
+
+/// Certificates for serving HTTPS and calling other services; see `tls`.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// PEM certificate chain, presented as server and as client certificate.
+    pub cert_file: String,
+    pub key_file: String,
+    /// PEM CA bundle that peers' certificates must chain to. Without it clients are not asked
+    /// for certificates and upstreams are checked against the built-in web roots.
+    pub ca_file: Option<String>,
+    pub client_auth: ClientAuth,
+    /// How often the files are checked for changes; 0 disables reloading.
+    pub reload_secs: u64,
+}
+
+/// Whether clients must present a certificate when `TLS_CA_FILE` is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientAuth {
+    Required,
+    /// Certificates are verified when presented, e.g. so probes without one still connect.
+    Optional,
+}
+
+impl TlsConfig {
+    /// `None` unless `TLS_CERT_FILE` is set.
+    pub fn from_env() -> Result<Option<Self>, env::VarError> {
+        let Some(cert_file) = env::var("TLS_CERT_FILE").ok().filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            cert_file,
+            key_file: env::var("TLS_KEY_FILE")?,
+            ca_file: env::var("TLS_CA_FILE").ok().filter(|s| !s.is_empty()),
+            client_auth: match env::var("TLS_CLIENT_AUTH").as_deref() {
+                Ok("optional") => ClientAuth::Optional,
+                _ => ClientAuth::Required,
+            },
+            reload_secs: env::var("TLS_RELOAD_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
+        }))
+    }
+}
+
+/// Comma-separated list variable; `None` when unset or empty.
+fn list_var(name: &str) -> Option<Vec<String>> {
+    let items: Vec<String> = env::var(name)
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+    (!items.is_empty()).then_some(items)
+}
+
+impl AuthConfig {
+    pub fn from_env() -> Self {
+        Self {
+            jwt_secret: env::var("JWT_SECRET").ok(),
+            jwks_url: env::var("JWKS_URL").ok(),
+            jwks_file: env::var("JWKS_FILE").ok(),
+            oidc_issuer: env::var("OIDC_ISSUER_URL").ok().filter(|s| !s.is_empty()),
+            role_claims: list_var("JWT_ROLE_CLAIMS")
+                .unwrap_or_else(|| vec!["role".to_string(), "roles".to_string()]),
+            issuer: env::var("JWT_ISSUER").ok().filter(|s| !s.is_empty()),
+            audience: list_var("JWT_AUDIENCE").unwrap_or_default(),
+            leeway_secs: env::var("JWT_LEEWAY_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(60),
+            revocation_url: env::var("REVOCATION_LIST_URL")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            revocation_cache_secs: env::var("REVOCATION_CACHE_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
+            permissions_file: env::var("ROLE_PERMISSIONS_FILE")
+                .ok()
+                .filter(|s| !s.is_empty()),
+        }
+    }
+}
+
+/// Circuit settings for each upstream, layered over the shared `CIRCUIT_*` ones.
+fn circuits_from_env(upstreams: &BTreeMap<String, String>) -> BTreeMap<String, CircuitConfig> {
+    let shared = CircuitConfig::from_env("CIRCUIT_", &CircuitConfig::default());
+    upstreams
+        .keys()
+        .map(|name| {
+            let prefix = format!("{}_CIRCUIT_", name.to_uppercase().replace('-', "_"));
+            (name.clone(), CircuitConfig::from_env(&prefix, &shared))
+        })
+        .collect()
+}
+
+fn otlp_endpoint_from_env() -> Option<String> {
+    let non_empty = |name| env::var(name).ok().filter(|s: &String| !s.is_empty());
+    non_empty("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").or_else(|| {
+        non_empty("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .map(|base| format!("{}/v1/traces", base.trim_end_matches('/')))
+    })
+}
+
 impl Config {
     pub fn from_env() -> Result<Self, env::VarError> {
+        let upstreams = upstreams_from_env();
         Ok(Self {
             service_name: env::var("SERVICE_NAME").unwrap_or_else(|_| "local".to_string()),
             http_port: env::var("HTTP_PORT")
@@ -18,7 +208,24 @@ impl Config {
                 .parse()
                 .unwrap_or(8080),
             database_url: env::var("DATABASE_URL")?,
-            jwt_secret: env::var("JWT_SECRET")?,
+            auth: AuthConfig::from_env(),
+            service_token_secret: env::var("SERVICE_TOKEN_SECRET")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            circuits: circuits_from_env(&upstreams),
+            upstreams,
+            retry: RetryPolicy::from_env(),
+            max_concurrency: env::var("HTTP_MAX_CONCURRENCY")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(64),
+            tls: TlsConfig::from_env()?,
+            otlp_endpoint: otlp_endpoint_from_env(),
+            log_format: match env::var("LOG_FORMAT").as_deref() {
+                Ok("json") => LogFormat::Json,
+                _ => LogFormat::Text,
+            },
             rust_log: env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()),
         })
     }
@@ -0,0 +1,247 @@
+//! Error responses for every service: `ApiError` renders as an RFC 7807
+//! `application/problem+json` body with a stable `code`, the `x-request-id` of the request (see
+//! `request_id`) and, for validation failures, the invalid fields.
+
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+use crate::auth::VerifyError;
+use crate::http_client::ServiceError;
+use crate::request_id;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// One invalid field of a request, e.g. `title` with "must not be empty".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Error returned by handlers and extractors; the message is the problem's `detail`. Server
+/// errors are logged where they are converted, so the message never carries internals.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(&'static str),
+    #[error("{0}")]
+    Unauthorized(&'static str),
+    #[error("{0}")]
+    Forbidden(&'static str),
+    #[error("{0}")]
+    NotFound(&'static str),
+    #[error("{0}")]
+    Conflict(&'static str),
+    #[error("{0}")]
+    PayloadTooLarge(&'static str),
+    #[error("{0}")]
+    UnsupportedMediaType(&'static str),
+    /// A well-formed body that does not match the expected shape.
+    #[error("{0}")]
+    Unprocessable(&'static str),
+    /// Fields that failed validation, all of them rather than the first.
+    #[error("one or more fields are invalid")]
+    Validation(Vec<FieldError>),
+    #[error("{0}")]
+    Internal(&'static str),
+    /// An upstream service failed or answered unexpectedly.
+    #[error("{0}")]
+    BadGateway(&'static str),
+    #[error("{0}")]
+    Unavailable(&'static str),
+    #[error("{0}")]
+    GatewayTimeout(&'static str),
+}
+
+impl ApiError {
+    /// Client error for an extractor rejection with `status`; statuses without a variant of
+    /// their own are a 400.
+    pub fn for_status(status: StatusCode, detail: &'static str) -> Self {
+        match status {
+            StatusCode::PAYLOAD_TOO_LARGE => ApiError::PayloadTooLarge(detail),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ApiError::UnsupportedMediaType(detail),
+            StatusCode::UNPROCESSABLE_ENTITY => ApiError::Unprocessable(detail),
+            _ => ApiError::BadRequest(detail),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Unprocessable(_) | ApiError::Validation(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::GatewayTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    /// Machine-readable kind of the error; clients match on this rather than on `detail`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::Unprocessable(_) => "unprocessable_entity",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Internal(_) => "internal_error",
+            ApiError::BadGateway(_) => "bad_gateway",
+            ApiError::Unavailable(_) => "service_unavailable",
+            ApiError::GatewayTimeout(_) => "gateway_timeout",
+        }
+    }
+
+    /// Invalid fields; empty unless this is `Validation`.
+    pub fn field_errors(&self) -> &[FieldError] {
+        match self {
+            ApiError::Validation(errors) => errors,
+            _ => &[],
+        }
+    }
+}
+
+/// Problem details as in RFC 7807, with `code`, `request_id` and `errors` as extension members.
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[FieldError]>::is_empty")]
+    errors: &'a [FieldError],
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let request_id = request_id::current();
+        let problem = Problem {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail: self.to_string(),
+            code: self.code(),
+            request_id: request_id.as_ref().and_then(|id| id.to_str().ok()),
+            errors: self.field_errors(),
+        };
+        let body = serde_json::to_vec(&problem).expect("problem details serialize");
+        (
+            status,
+            [(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON))],
+            body,
+        )
+            .into_response()
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        tracing::error!("database error: {}", e);
+        match e {
+            sqlx::Error::PoolTimedOut => ApiError::Unavailable("database unavailable"),
+            _ => ApiError::Internal("database error"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        tracing::warn!("upstream call failed: {}", e);
+        if e.is_timeout() {
+            ApiError::GatewayTimeout("upstream timed out")
+        } else {
+            ApiError::BadGateway("upstream unavailable")
+        }
+    }
+}
+
+impl From<ServiceError> for ApiError {
+    fn from(e: ServiceError) -> Self {
+        match e {
+            ServiceError::Transport { source, .. } => source.into(),
+            ServiceError::Timeout(_) => {
+                tracing::warn!("upstream call failed: {}", e);
+                ApiError::GatewayTimeout("upstream timed out")
+            }
+            ServiceError::UnknownUpstream(_)
+            | ServiceError::InvalidHeader(_)
+            | ServiceError::Encode(_)
+            | ServiceError::Auth(_) => {
+                tracing::error!("upstream call failed: {}", e);
+                ApiError::Internal("upstream call failed")
+            }
+            _ => {
+                tracing::warn!("upstream call failed: {}", e);
+                ApiError::BadGateway("upstream unavailable")
+            }
+        }
+    }
+}
+
+/// A token that failed to decode or validate; 401 with the reason `VerifyError` gives.
+impl From<jsonwebtoken::errors::Error> for ApiError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        VerifyError::Invalid(e).into()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(e: JsonRejection) -> Self {
+        tracing::debug!("rejected JSON body: {}", e.body_text());
+        let detail = match e {
+            JsonRejection::JsonDataError(_) => "request body does not match the expected shape",
+            JsonRejection::MissingJsonContentType(_) => "expected a JSON body",
+            _ => "invalid JSON body",
+        };
+        ApiError::for_status(e.status(), detail)
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(e: PathRejection) -> Self {
+        match e {
+            PathRejection::FailedToDeserializePathParams(e) => {
+                tracing::debug!("rejected path: {}", e.body_text());
+                ApiError::BadRequest("invalid path parameter")
+            }
+            _ => {
+                tracing::error!("path parameters unavailable: {}", e.body_text());
+                ApiError::Internal("path parameters unavailable")
+            }
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(e: QueryRejection) -> Self {
+        tracing::debug!("rejected query string: {}", e.body_text());
+        ApiError::BadRequest("invalid query parameters")
+    }
+}
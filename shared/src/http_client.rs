@@ -1,172 +1,451 @@
-//! HTTP client with timeout, retry (exponential backoff), and circuit breaker for outbound calls.
+//! HTTP client for outbound calls to named upstreams, sent through the tower layers in
+//! `resilience`: timeout, retry (see `retry`), a circuit breaker (see `circuit`) and a
+//! concurrency limit with load-shedding per upstream.
 
-use reqwest::Client;
-use std::error::Error;
-use std::sync::atomic::{AtomicU32, Ordering};
+use bytes::Bytes;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, Method, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
-use std::time::Instant;
-use tokio::time::sleep;
+use tokio::sync::{broadcast, Semaphore};
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower::load_shed::error::Overloaded;
+use tower::timeout::error::Elapsed;
+use tower::{BoxError, ServiceBuilder, ServiceExt};
+use tracing::Instrument;
 
-/// Error type for outbound HTTP calls (reqwest or circuit/open/retry).
-pub type HttpClientError = Box<dyn Error + Send + Sync>;
+use crate::auth::Claims;
+use crate::circuit::{CircuitBreaker, CircuitConfig, CircuitEvent, CircuitStatus};
+use crate::config::{Config, TlsConfig};
+use crate::metrics::UpstreamMetricsLayer;
+use crate::request_id::{self, X_REQUEST_ID};
+use crate::resilience::{CircuitBreakerLayer, DeadlineExceeded, HttpService, RetryLayer};
+use crate::retry::{RetryPolicy, IDEMPOTENCY_KEY};
+use crate::service_auth::{service_tokens, ServiceAuthError};
+use crate::tls::{self, TlsError};
+use crate::trace_context;
+
+/// Failure of an outbound call. `Status` means the upstream answered; everything else means no
+/// usable answer was received.
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("unknown upstream {0:?}")]
+    UnknownUpstream(String),
+    #[error("circuit open ({0})")]
+    CircuitOpen(String),
+    /// Rejected without being sent: the upstream's concurrency limit was reached.
+    #[error("too many calls in flight ({0})")]
+    Overloaded(String),
+    #[error("{0} timed out")]
+    Timeout(String),
+    #[error("{upstream} answered {status}: {body}")]
+    Status {
+        upstream: String,
+        status: StatusCode,
+        body: String,
+    },
+    #[error("{upstream}: {source}")]
+    Transport {
+        upstream: String,
+        source: reqwest::Error,
+    },
+    #[error("invalid {0} header value")]
+    InvalidHeader(String),
+    #[error("encoding request body: {0}")]
+    Encode(serde_json::Error),
+    #[error("{upstream} returned an unreadable body: {source}")]
+    Decode {
+        upstream: String,
+        source: serde_json::Error,
+    },
+    #[error("service token: {0}")]
+    Auth(#[from] ServiceAuthError),
+    /// Any other error from a layer of the outbound stack.
+    #[error("{upstream}: {source}")]
+    Layer { upstream: String, source: BoxError },
+}
+
+impl ServiceError {
+    /// HTTP status when the upstream did answer (e.g. 404), `None` for transport errors and an
+    /// open circuit.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ServiceError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    /// Classify an error from `upstream`'s layer stack.
+    pub fn from_layers(upstream: &str, err: BoxError) -> Self {
+        let err = match err.downcast::<ServiceError>() {
+            Ok(err) => return *err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<reqwest::Error>() {
+            Ok(source) => {
+                return ServiceError::Transport {
+                    upstream: upstream.to_string(),
+                    source: *source,
+                }
+            }
+            Err(err) => err,
+        };
+        if err.is::<Elapsed>() || err.is::<DeadlineExceeded>() {
+            ServiceError::Timeout(upstream.to_string())
+        } else if err.is::<Overloaded>() {
+            ServiceError::Overloaded(upstream.to_string())
+        } else {
+            ServiceError::Layer {
+                upstream: upstream.to_string(),
+                source: err,
+            }
+        }
+    }
+}
 
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
-const RETRY_COUNT: u32 = 3;
-const CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
-const CIRCUIT_OPEN_DURATION: Duration = Duration::from_secs(30);
+/// Default for `ServiceClient::with_concurrency_limit`.
+const MAX_CONCURRENCY: usize = 64;
 
-/// Shared HTTP client with timeout. Retry and circuit breaker are applied per-call in ServiceClient.
-pub fn default_client() -> Client {
+/// Characters that must be escaped inside a single URL path segment.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Percent-encode a value (e.g. a JWT `sub`) for use as one segment of a ServiceClient path.
+pub fn path_segment(value: &str) -> String {
+    utf8_percent_encode(value, PATH_SEGMENT).to_string()
+}
+
+/// Client builder with the shared timeouts.
+pub fn client_builder() -> reqwest::ClientBuilder {
     Client::builder()
         .connect_timeout(CONNECT_TIMEOUT)
         .timeout(REQUEST_TIMEOUT)
-        .build()
-        .expect("reqwest client")
 }
 
-/// Circuit breaker state for one target (e.g. admin-service).
-#[derive(Debug)]
-struct CircuitState {
-    failures: AtomicU32,
-    last_failure: std::sync::Mutex<Option<Instant>>,
+/// Shared HTTP client with timeout. Retry and circuit breaker are layered on in ServiceClient.
+pub fn default_client() -> Client {
+    client_builder().build().expect("reqwest client")
+}
+
+/// A service the client can call, registered by name (its `SERVICE_NAME`).
+struct Upstream {
+    name: String,
+    base_url: String,
+    circuit: Arc<CircuitBreaker>,
+    /// Bulkhead: permits for calls in flight, so one slow upstream cannot take every task.
+    limit: Arc<Semaphore>,
+}
+
+/// Client for calling other services through the `resilience` layers. Upstreams are looked up by
+/// name, so any service in the registry can be called the same way.
+pub struct ServiceClient {
+    /// Replaced when TLS certificates are reloaded.
+    client: Arc<RwLock<Client>>,
+    upstreams: HashMap<String, Upstream>,
+    retry: RetryPolicy,
+    circuit_events: broadcast::Sender<CircuitEvent>,
 }
 
-impl CircuitState {
-    fn new() -> Self {
+impl ServiceClient {
+    /// `upstreams` maps names to base URLs, e.g. `admin-service` => `http://admin-service:8080`.
+    /// Every upstream starts with the default `CircuitConfig`.
+    pub fn new(upstreams: impl IntoIterator<Item = (String, String)>) -> Self {
+        let (circuit_events, _) = broadcast::channel(64);
         Self {
-            failures: AtomicU32::new(0),
-            last_failure: std::sync::Mutex::new(None),
+            client: Arc::new(RwLock::new(default_client())),
+            upstreams: upstreams
+                .into_iter()
+                .map(|(name, base_url)| {
+                    let upstream = Upstream {
+                        name: name.clone(),
+                        base_url: base_url.trim_end_matches('/').to_string(),
+                        circuit: Arc::new(CircuitBreaker::new(
+                            name.clone(),
+                            CircuitConfig::default(),
+                            circuit_events.clone(),
+                        )),
+                        limit: Arc::new(Semaphore::new(MAX_CONCURRENCY)),
+                    };
+                    (name, upstream)
+                })
+                .collect(),
+            retry: RetryPolicy::default(),
+            circuit_events,
         }
     }
 
-    fn record_success(&self) {
-        self.failures.store(0, Ordering::SeqCst);
-        *self.last_failure.lock().unwrap() = None;
+    /// Client for the upstreams in `config.upstreams` with `config.retry`, `config.circuits`
+    /// and `config.max_concurrency`, and mutual TLS when `config.tls` is set.
+    pub fn from_config(config: &Config) -> Result<Self, TlsError> {
+        let mut client = Self::new(config.upstreams.clone())
+            .with_retry_policy(config.retry.clone())
+            .with_concurrency_limit(config.max_concurrency);
+        for (name, circuit) in &config.circuits {
+            client = client.with_circuit_config(name, circuit.clone());
+        }
+        match &config.tls {
+            Some(tls) => client.with_tls(tls),
+            None => Ok(client),
+        }
     }
 
-    fn record_failure(&self) {
-        let n = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
-        *self.last_failure.lock().unwrap() = Some(Instant::now());
-        if n >= CIRCUIT_FAILURE_THRESHOLD {
-            tracing::warn!("circuit open after {} failures", n);
-        }
+    /// Retry policy for calls that do not set their own.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
     }
 
-    fn is_open(&self) -> bool {
-        let failures = self.failures.load(Ordering::SeqCst);
-        if failures < CIRCUIT_FAILURE_THRESHOLD {
-            return false;
+    /// Circuit breaker thresholds for `upstream`; resets its circuit to closed.
+    pub fn with_circuit_config(mut self, upstream: &str, config: CircuitConfig) -> Self {
+        if let Some(entry) = self.upstreams.get_mut(upstream) {
+            entry.circuit = Arc::new(CircuitBreaker::new(
+                upstream,
+                config,
+                self.circuit_events.clone(),
+            ));
         }
-        let last = self.last_failure.lock().unwrap();
-        match *last {
-            Some(t) if t.elapsed() >= CIRCUIT_OPEN_DURATION => {
-                // half-open: allow one request
-                false
-            }
-            Some(_) => true,
-            None => true,
+        self
+    }
+
+    /// Calls in flight allowed per upstream (default 64); calls beyond it fail at once with
+    /// `ServiceError::Overloaded`.
+    pub fn with_concurrency_limit(mut self, max: usize) -> Self {
+        for upstream in self.upstreams.values_mut() {
+            upstream.limit = Arc::new(Semaphore::new(max));
         }
+        self
     }
-}
 
-/// Client for calling other services with retry and circuit breaker.
-pub struct ServiceClient {
-    client: Client,
-    admin_base: String,
-    teacher_base: String,
-    admin_circuit: std::sync::Arc<CircuitState>,
-    teacher_circuit: std::sync::Arc<CircuitState>,
-}
+    /// Circuit state changes of every upstream, from now on.
+    pub fn circuit_events(&self) -> broadcast::Receiver<CircuitEvent> {
+        self.circuit_events.subscribe()
+    }
 
-impl ServiceClient {
-    /// base_url e.g. http://admin-service:8080 (without trailing slash)
-    pub fn new(admin_base: String, teacher_base: String) -> Self {
-        Self {
-            client: default_client(),
-            admin_base,
-            teacher_base,
-            admin_circuit: std::sync::Arc::new(CircuitState::new()),
-            teacher_circuit: std::sync::Arc::new(CircuitState::new()),
-        }
+    pub fn circuit_status(&self, upstream: &str) -> Option<CircuitStatus> {
+        self.upstreams.get(upstream).map(|u| u.circuit.status())
     }
 
-    /// GET admin-service e.g. /api/admin/courses/{id}
-    /// bearer_token: optional "Bearer <jwt>" for forwarding auth to admin-service
-    pub async fn get_admin(
-        &self,
-        path: &str,
-        bearer_token: Option<&str>,
-    ) -> Result<reqwest::Response, HttpClientError> {
-        if self.admin_circuit.is_open() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::ConnectionRefused,
-                "circuit open (admin-service)",
-            )));
-        }
-        let url = format!("{}{}", self.admin_base, path);
-        let res = self.request_with_retry(&url, bearer_token).await;
-        if res.is_ok() {
-            self.admin_circuit.record_success();
-        } else {
-            self.admin_circuit.record_failure();
+    /// Call upstreams with mutual TLS: present `tls`'s certificate and trust its CA bundle.
+    /// The client is rebuilt when the certificate files change.
+    pub fn with_tls(self, tls: &TlsConfig) -> Result<Self, TlsError> {
+        *self.client.write().unwrap() = tls::client(tls)?;
+        let client = self.client.clone();
+        tls::watch(tls, "client", move |tls| {
+            *client.write().unwrap() = tls::client(tls)?;
+            Ok(())
+        });
+        Ok(self)
+    }
+
+    /// Request `method path` on the upstream named `upstream`; nothing is sent until `send`.
+    pub fn request(&self, method: Method, upstream: &str, path: &str) -> ServiceRequest<'_> {
+        ServiceRequest {
+            client: self,
+            method,
+            upstream: upstream.to_string(),
+            path: path.to_string(),
+            headers: HeaderMap::new(),
+            body: None,
+            service_auth: None,
+            retry: None,
         }
-        res
     }
 
-    /// GET teacher-service e.g. /api/teacher/assignments/{id}
-    /// bearer_token: optional "Bearer <jwt>" for forwarding auth
-    pub async fn get_teacher(
-        &self,
+    pub fn get(&self, upstream: &str, path: &str) -> ServiceRequest<'_> {
+        self.request(Method::GET, upstream, path)
+    }
+
+    pub fn post(&self, upstream: &str, path: &str) -> ServiceRequest<'_> {
+        self.request(Method::POST, upstream, path)
+    }
+
+    pub fn put(&self, upstream: &str, path: &str) -> ServiceRequest<'_> {
+        self.request(Method::PUT, upstream, path)
+    }
+
+    pub fn patch(&self, upstream: &str, path: &str) -> ServiceRequest<'_> {
+        self.request(Method::PATCH, upstream, path)
+    }
+
+    pub fn delete(&self, upstream: &str, path: &str) -> ServiceRequest<'_> {
+        self.request(Method::DELETE, upstream, path)
+    }
+
+    /// GET an `/internal/...` route as this service, acting for `user` if given, and parse the
+    /// JSON answer.
+    pub async fn get_json<'a, T: DeserializeOwned>(
+        &'a self,
+        upstream: &str,
         path: &str,
-        bearer_token: Option<&str>,
-    ) -> Result<reqwest::Response, HttpClientError> {
-        if self.teacher_circuit.is_open() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::ConnectionRefused,
-                "circuit open (teacher-service)",
-            )));
+        user: Option<&'a Claims>,
+    ) -> Result<T, ServiceError> {
+        self.get(upstream, path).as_service(user).send_json().await
+    }
+
+    /// Send the request through the upstream's stack (see `resilience`): load-shed over its
+    /// concurrency limit, retry by the call's policy, its circuit breaker, metrics and a
+    /// timeout per attempt. The call gets a client span whose context is sent as `traceparent`.
+    async fn execute(&self, req: ServiceRequest<'_>) -> Result<reqwest::Response, ServiceError> {
+        let upstream = self
+            .upstreams
+            .get(&req.upstream)
+            .ok_or_else(|| ServiceError::UnknownUpstream(req.upstream.clone()))?;
+        let mut headers = req.headers;
+        if let Some(user) = req.service_auth {
+            let token = service_tokens()?.mint(&req.upstream, user)?;
+            headers.insert(AUTHORIZATION, bearer(&token)?);
         }
-        let url = format!("{}{}", self.teacher_base, path);
-        let res = self.request_with_retry(&url, bearer_token).await;
-        if res.is_ok() {
-            self.teacher_circuit.record_success();
-        } else {
-            self.teacher_circuit.record_failure();
+        let url = format!("{}{}", upstream.base_url, req.path);
+        let span = tracing::info_span!(
+            "outbound",
+            otel.name = %format!("{} {}", req.method, upstream.name),
+            otel.kind = "client",
+            upstream = %upstream.name,
+            method = %req.method,
+            url = %url,
+        );
+        trace_context::inject(&span, &mut headers);
+        if let Some(id) = request_id::current() {
+            headers.entry(X_REQUEST_ID).or_insert(id);
         }
-        res
-    }
-
-    async fn request_with_retry(
-        &self,
-        url: &str,
-        bearer_token: Option<&str>,
-    ) -> Result<reqwest::Response, HttpClientError> {
-        let mut last_err: Option<HttpClientError> = None;
-        for attempt in 0..=RETRY_COUNT {
-            if attempt > 0 {
-                let backoff = Duration::from_millis(100 * 2u64.pow(attempt - 1));
-                sleep(backoff).await;
-            }
-            let mut req = self.client.get(url);
-            if let Some(t) = bearer_token {
-                req = req.header("Authorization", t);
-            }
-            match req.send().await {
-                Ok(res) => {
-                    if res.status().is_success() {
-                        return Ok(res);
-                    }
-                    last_err = Some(Box::new(res.error_for_status().unwrap_err()));
-                }
-                Err(e) => last_err = Some(Box::new(e)),
-            }
+        let client = self.client.read().unwrap().clone();
+        let mut builder = client.request(req.method, &url).headers(headers);
+        if let Some(body) = req.body {
+            builder = builder.body(body);
         }
-        Err(last_err.unwrap_or_else(|| {
-            Box::new(std::io::Error::new(
-                std::io::ErrorKind::ConnectionRefused,
-                "no response after retries",
+        let request = builder.build().map_err(|source| ServiceError::Transport {
+            upstream: upstream.name.clone(),
+            source,
+        })?;
+        let retry = req.retry.unwrap_or_else(|| self.retry.clone());
+        let res = ServiceBuilder::new()
+            .load_shed()
+            .layer(GlobalConcurrencyLimitLayer::with_semaphore(
+                upstream.limit.clone(),
             ))
-        }))
+            .layer(RetryLayer::new(retry).upstream(&upstream.name))
+            .layer(CircuitBreakerLayer::new(upstream.circuit.clone()))
+            .layer(UpstreamMetricsLayer::new(&upstream.name))
+            .timeout(REQUEST_TIMEOUT)
+            .service(HttpService::shared(self.client.clone()))
+            .oneshot(request)
+            .instrument(span)
+            .await
+            .map_err(|e| ServiceError::from_layers(&upstream.name, e))?;
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        Err(ServiceError::Status {
+            upstream: upstream.name.clone(),
+            status,
+            body: res.text().await.unwrap_or_default(),
+        })
+    }
+}
+
+fn bearer(token: &str) -> Result<HeaderValue, ServiceError> {
+    let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+        .map_err(|_| ServiceError::InvalidHeader(AUTHORIZATION.to_string()))?;
+    // Keeps the token out of `Debug` output of the request.
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// An outbound request being built; see `ServiceClient::request`.
+pub struct ServiceRequest<'a> {
+    client: &'a ServiceClient,
+    method: Method,
+    upstream: String,
+    path: String,
+    headers: HeaderMap,
+    body: Option<Bytes>,
+    /// Set when the request carries a service token; the inner value is the delegating user.
+    service_auth: Option<Option<&'a Claims>>,
+    retry: Option<RetryPolicy>,
+}
+
+impl<'a> ServiceRequest<'a> {
+    /// Authenticate as this service with a token addressed to the upstream, acting for `user`
+    /// if given. Required by `/internal/...` routes.
+    pub fn as_service(mut self, user: Option<&'a Claims>) -> Self {
+        self.service_auth = Some(user);
+        self
     }
-}
\ No newline at end of file
+
+    /// Send `token` as the bearer token instead of a service token.
+    pub fn bearer(mut self, token: &str) -> Result<Self, ServiceError> {
+        self.headers.insert(AUTHORIZATION, bearer(token)?);
+        self.service_auth = None;
+        Ok(self)
+    }
+
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Allow retrying a non-idempotent request: the upstream must apply it at most once per
+    /// `key`.
+    pub fn idempotency_key(self, key: &str) -> Result<Self, ServiceError> {
+        let value = HeaderValue::from_str(key)
+            .map_err(|_| ServiceError::InvalidHeader(IDEMPOTENCY_KEY.to_string()))?;
+        Ok(self.header(IDEMPOTENCY_KEY, value))
+    }
+
+    /// Retry this call by `retry` instead of the client's policy.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Send `body` as JSON.
+    pub fn json<B: Serialize + ?Sized>(mut self, body: &B) -> Result<Self, ServiceError> {
+        self.body = Some(
+            serde_json::to_vec(body)
+                .map_err(ServiceError::Encode)?
+                .into(),
+        );
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(self)
+    }
+
+    /// Send the request; any non-2xx answer is a `ServiceError::Status`.
+    pub async fn send(self) -> Result<reqwest::Response, ServiceError> {
+        self.client.execute(self).await
+    }
+
+    /// Send the request and parse the JSON answer.
+    pub async fn send_json<T: DeserializeOwned>(self) -> Result<T, ServiceError> {
+        let upstream = self.upstream.clone();
+        let res = self.send().await?;
+        let body = res
+            .bytes()
+            .await
+            .map_err(|source| ServiceError::Transport {
+                upstream: upstream.clone(),
+                source,
+            })?;
+        serde_json::from_slice(&body).map_err(|source| ServiceError::Decode { upstream, source })
+    }
+}
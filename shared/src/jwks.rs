@@ -0,0 +1,239 @@
+//! Verification keys for asymmetric JWTs, read from a JWKS document (a file, a URL, or the
+//! `jwks_uri` of an OIDC issuer) and cached by `kid`. Unknown kids trigger a throttled refetch,
+//! which is how rotated keys are picked up.
+
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, KeyAlgorithm, PublicKeyUse};
+use jsonwebtoken::{Algorithm, DecodingKey};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// An unknown `kid` triggers at most one refetch per interval (by default), so forged kids
+/// cannot hammer the JWKS endpoint.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// Keys are refetched at least this often so retired keys stop verifying.
+const MAX_KEY_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwksError {
+    #[error("reading JWKS file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("fetching JWKS: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("parsing JWKS: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("OIDC discovery: {0}")]
+    Discovery(String),
+    #[error("JWKS has no usable signing keys")]
+    Empty,
+    #[error("no key with kid {0:?}")]
+    UnknownKid(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    File(PathBuf),
+    Url(String),
+    /// OIDC issuer URL; the JWKS location is read from its discovery document on each fetch.
+    Discovery(String),
+}
+
+/// The parts of an OpenID Provider configuration we use.
+#[derive(serde::Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    jwks_uri: String,
+}
+
+#[derive(Clone)]
+pub struct VerificationKey {
+    pub key: DecodingKey,
+    pub algorithm: Algorithm,
+}
+
+/// Signing algorithm for a JWK: its `alg` if given, else the usual one for its key type.
+/// Only asymmetric algorithms are accepted.
+fn key_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    if let Some(alg) = jwk.common.key_algorithm {
+        return match alg {
+            KeyAlgorithm::RS256 => Some(Algorithm::RS256),
+            KeyAlgorithm::RS384 => Some(Algorithm::RS384),
+            KeyAlgorithm::RS512 => Some(Algorithm::RS512),
+            KeyAlgorithm::PS256 => Some(Algorithm::PS256),
+            KeyAlgorithm::PS384 => Some(Algorithm::PS384),
+            KeyAlgorithm::PS512 => Some(Algorithm::PS512),
+            KeyAlgorithm::ES256 => Some(Algorithm::ES256),
+            KeyAlgorithm::ES384 => Some(Algorithm::ES384),
+            KeyAlgorithm::EdDSA => Some(Algorithm::EdDSA),
+            _ => None,
+        };
+    }
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Some(Algorithm::RS256),
+        AlgorithmParameters::EllipticCurve(p) if p.curve == EllipticCurve::P256 => {
+            Some(Algorithm::ES256)
+        }
+        AlgorithmParameters::EllipticCurve(p) if p.curve == EllipticCurve::P384 => {
+            Some(Algorithm::ES384)
+        }
+        AlgorithmParameters::OctetKeyPair(p) if p.curve == EllipticCurve::Ed25519 => {
+            Some(Algorithm::EdDSA)
+        }
+        _ => None,
+    }
+}
+
+/// Parse a JWKS document into verification keys by `kid`. Keys that are not signing keys, have
+/// no `kid`, or use an unsupported type are skipped rather than failing the whole set.
+fn parse_jwks(body: &[u8]) -> Result<HashMap<String, VerificationKey>, JwksError> {
+    #[derive(serde::Deserialize)]
+    struct RawSet {
+        keys: Vec<serde_json::Value>,
+    }
+    let raw: RawSet = serde_json::from_slice(body)?;
+    let mut keys = HashMap::new();
+    for value in raw.keys {
+        let Ok(jwk) = serde_json::from_value::<Jwk>(value) else {
+            continue;
+        };
+        if matches!(jwk.common.public_key_use, Some(ref u) if *u != PublicKeyUse::Signature) {
+            continue;
+        }
+        let (Some(kid), Some(algorithm)) = (jwk.common.key_id.clone(), key_algorithm(&jwk)) else {
+            continue;
+        };
+        match DecodingKey::from_jwk(&jwk) {
+            Ok(key) => {
+                keys.insert(kid, VerificationKey { key, algorithm });
+            }
+            Err(e) => tracing::warn!("skipping JWK {:?}: {}", kid, e),
+        }
+    }
+    if keys.is_empty() {
+        return Err(JwksError::Empty);
+    }
+    Ok(keys)
+}
+
+#[derive(Default)]
+struct CachedKeys {
+    keys: HashMap<String, VerificationKey>,
+    fetched_at: Option<Instant>,
+}
+
+/// Cached view of a JWKS document. Every key in the document is active, so a new key can be
+/// published before tokens are signed with it and an old one removed after they expire.
+pub struct JwksCache {
+    source: JwksSource,
+    http: reqwest::Client,
+    cache: RwLock<CachedKeys>,
+    /// Time of the last fetch attempt; the lock also makes concurrent refreshes single-flight.
+    last_attempt: tokio::sync::Mutex<Option<Instant>>,
+    min_refresh_interval: Duration,
+}
+
+impl JwksCache {
+    pub fn new(source: JwksSource) -> Self {
+        Self {
+            source,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("reqwest client"),
+            cache: RwLock::new(CachedKeys::default()),
+            last_attempt: tokio::sync::Mutex::new(None),
+            min_refresh_interval: MIN_REFRESH_INTERVAL,
+        }
+    }
+
+    /// Shortest time between two fetches (30s by default).
+    pub fn with_min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+
+    async fn get(&self, url: &str) -> Result<Vec<u8>, JwksError> {
+        Ok(self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec())
+    }
+
+    /// `jwks_uri` from the issuer's discovery document, which must name the same issuer.
+    async fn discover(&self, issuer: &str) -> Result<String, JwksError> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = serde_json::from_slice(&self.get(&url).await?)?;
+        if metadata.issuer != issuer {
+            return Err(JwksError::Discovery(format!(
+                "document is for issuer {:?}, expected {:?}",
+                metadata.issuer, issuer
+            )));
+        }
+        Ok(metadata.jwks_uri)
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, VerificationKey>, JwksError> {
+        let body = match &self.source {
+            JwksSource::File(path) => tokio::fs::read(path).await?,
+            JwksSource::Url(url) => self.get(url).await?,
+            JwksSource::Discovery(issuer) => {
+                let jwks_uri = self.discover(issuer).await?;
+                self.get(&jwks_uri).await?
+            }
+        };
+        parse_jwks(&body)
+    }
+
+    /// Refetch unless an attempt was made within the minimum refresh interval. A failed
+    /// refetch keeps the previously cached keys.
+    async fn refresh(&self) -> Result<(), JwksError> {
+        let mut last_attempt = self.last_attempt.lock().await;
+        if last_attempt.is_some_and(|t| t.elapsed() < self.min_refresh_interval) {
+            return Ok(());
+        }
+        *last_attempt = Some(Instant::now());
+        match self.fetch().await {
+            Ok(keys) => {
+                *self.cache.write().expect("jwks lock") = CachedKeys {
+                    keys,
+                    fetched_at: Some(Instant::now()),
+                };
+                Ok(())
+            }
+            Err(e) if !self.cache.read().expect("jwks lock").keys.is_empty() => {
+                tracing::warn!("JWKS refresh failed, keeping cached keys: {}", e);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Key for `kid`, refetching the document when it is stale or the key is unknown.
+    pub async fn key(&self, kid: &str) -> Result<VerificationKey, JwksError> {
+        {
+            let cache = self.cache.read().expect("jwks lock");
+            if cache.fetched_at.is_some_and(|t| t.elapsed() < MAX_KEY_AGE) {
+                if let Some(key) = cache.keys.get(kid) {
+                    return Ok(key.clone());
+                }
+            }
+        }
+        self.refresh().await?;
+        self.cache
+            .read()
+            .expect("jwks lock")
+            .keys
+            .get(kid)
+            .cloned()
+            .ok_or_else(|| JwksError::UnknownKid(kid.to_string()))
+    }
+}
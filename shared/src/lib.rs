@@ -1,9 +1,34 @@
+pub mod access;
 pub mod auth;
+pub mod blob;
+pub mod circuit;
 pub mod config;
+pub mod error;
+pub mod extract;
 pub mod http_client;
+pub mod jwks;
+pub mod metrics;
+pub mod pagination;
+pub mod policy;
+pub mod request_id;
+pub mod resilience;
+pub mod retry;
+pub mod revocation;
+pub mod service_auth;
+pub mod tls;
+pub mod trace_context;
 pub mod tracing_init;
+pub mod validation;
 
+pub use access::{AdminMemberships, Resource};
 pub use auth::{AuthUser, Claims, Role};
-pub use config::Config;
-pub use http_client::{HttpClientError, ServiceClient};
+pub use blob::{BlobStore, UrlSigner};
+pub use config::{AuthConfig, Config, TlsConfig};
+pub use error::{ApiError, FieldError};
+pub use http_client::{ServiceClient, ServiceError};
+pub use pagination::{Page, PageParams};
+pub use policy::{perm, Authorized, Permission};
+pub use retry::RetryPolicy;
+pub use service_auth::ServiceCaller;
 pub use tracing_init::init_tracing;
+pub use validation::ValidJson;
@@ -0,0 +1,262 @@
+//! Prometheus metrics, served on `/metrics` by `with_metrics`: request rate, errors and latency
+//! per route and status, the database pool, and `ServiceClient` calls per upstream (attempts,
+//! retries, latency and circuit state).
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{BoxError, Layer, Service};
+
+use crate::circuit::CircuitStatus;
+
+struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    db_connections: IntGaugeVec,
+    db_max_connections: IntGauge,
+    upstream_attempts: IntCounterVec,
+    upstream_retries: IntCounterVec,
+    upstream_duration: HistogramVec,
+    circuit_state: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        fn register<M: prometheus::core::Collector + Clone + 'static>(
+            registry: &Registry,
+            metric: prometheus::Result<M>,
+        ) -> M {
+            let metric = metric.expect("valid metric");
+            registry
+                .register(Box::new(metric.clone()))
+                .expect("metric names are unique");
+            metric
+        }
+        Self {
+            http_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("http_requests_total", "HTTP requests served"),
+                    &["method", "route", "status"],
+                ),
+            ),
+            http_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "http_request_duration_seconds",
+                        "Time to answer HTTP requests",
+                    ),
+                    &["method", "route", "status"],
+                ),
+            ),
+            db_connections: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new("db_pool_connections", "Database pool connections by state"),
+                    &["state"],
+                ),
+            ),
+            db_max_connections: register(
+                &registry,
+                IntGauge::new("db_pool_max_connections", "Database pool size limit"),
+            ),
+            upstream_attempts: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "upstream_attempts_total",
+                        "Requests sent to upstreams, retries included, by answer status \
+                         (error or timeout when there was none)",
+                    ),
+                    &["upstream", "status"],
+                ),
+            ),
+            upstream_retries: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("upstream_retries_total", "Retried requests to upstreams"),
+                    &["upstream"],
+                ),
+            ),
+            upstream_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "upstream_request_duration_seconds",
+                        "Time for upstreams to answer one attempt",
+                    ),
+                    &["upstream", "status"],
+                ),
+            ),
+            circuit_state: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new(
+                        "upstream_circuit_state",
+                        "1 for the current state of the upstream's circuit breaker",
+                    ),
+                    &["upstream", "state"],
+                ),
+            ),
+            registry,
+        }
+    }
+}
+
+fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Serve `/metrics` on `router` and record every request it answers. `pool` is reported as
+/// `db_pool_*` at each scrape.
+pub fn with_metrics(router: Router, pool: PgPool) -> Router {
+    router
+        .route("/metrics", get(move || render(pool.clone())))
+        .layer(middleware::from_fn(track))
+}
+
+async fn track(req: Request, next: Next) -> Response {
+    // Label by route template, not path, to keep the number of series bounded.
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let method = req.method().clone();
+    let started = Instant::now();
+    let res = next.run(req).await;
+    let status = res.status();
+    let labels = [method.as_str(), &route, status.as_str()];
+    let m = metrics();
+    m.http_requests.with_label_values(&labels).inc();
+    m.http_duration
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    res
+}
+
+async fn render(pool: PgPool) -> Response {
+    let m = metrics();
+    let idle = pool.num_idle() as i64;
+    m.db_connections.with_label_values(&["idle"]).set(idle);
+    m.db_connections
+        .with_label_values(&["in_use"])
+        .set(i64::from(pool.size()) - idle);
+    m.db_max_connections
+        .set(i64::from(pool.options().get_max_connections()));
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&m.registry.gather(), &mut body) {
+        tracing::error!("encoding metrics: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "metrics error").into_response();
+    }
+    ([(CONTENT_TYPE, encoder.format_type().to_string())], body).into_response()
+}
+
+pub(crate) fn record_retry(upstream: &str) {
+    metrics()
+        .upstream_retries
+        .with_label_values(&[upstream])
+        .inc();
+}
+
+pub(crate) fn record_circuit_state(upstream: &str, current: CircuitStatus) {
+    for state in [
+        CircuitStatus::Closed,
+        CircuitStatus::Open,
+        CircuitStatus::HalfOpen,
+    ] {
+        metrics()
+            .circuit_state
+            .with_label_values(&[upstream, state.as_str()])
+            .set(i64::from(state == current));
+    }
+}
+
+/// Records each attempt that passes it in `upstream_attempts_total` and
+/// `upstream_request_duration_seconds`; `ServiceClient` puts it under the circuit breaker, so
+/// only requests actually sent are counted.
+#[derive(Clone)]
+pub struct UpstreamMetricsLayer {
+    upstream: Arc<str>,
+}
+
+impl UpstreamMetricsLayer {
+    pub fn new(upstream: &str) -> Self {
+        Self {
+            upstream: upstream.into(),
+        }
+    }
+}
+
+impl<S> Layer<S> for UpstreamMetricsLayer {
+    type Service = UpstreamMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        UpstreamMetrics {
+            inner,
+            upstream: self.upstream.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct UpstreamMetrics<S> {
+    inner: S,
+    upstream: Arc<str>,
+}
+
+impl<S> Service<reqwest::Request> for UpstreamMetrics<S>
+where
+    S: Service<reqwest::Request, Response = reqwest::Response>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = reqwest::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<reqwest::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: reqwest::Request) -> Self::Future {
+        let upstream = self.upstream.clone();
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = future.await.map_err(Into::into);
+            let status = match &result {
+                Ok(res) => res.status().as_str().to_string(),
+                Err(e) if e.is::<tower::timeout::error::Elapsed>() => "timeout".to_string(),
+                Err(_) => "error".to_string(),
+            };
+            let labels = [&*upstream, status.as_str()];
+            let m = metrics();
+            m.upstream_attempts.with_label_values(&labels).inc();
+            m.upstream_duration
+                .with_label_values(&labels)
+                .observe(started.elapsed().as_secs_f64());
+            result
+        })
+    }
+}
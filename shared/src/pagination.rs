@@ -0,0 +1,184 @@
+//! Keyset (cursor) pagination over `(created_at, id)` shared by every list endpoint.
+//!
+//! Handlers take [`PageParams`] as an extractor, splice [`PageParams::keyset_predicate`] and
+//! [`PageParams::order_by`] into their SQL, fetch [`PageParams::fetch_limit`] rows and wrap the
+//! result with [`Page::from_rows`].
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+
+/// Sort direction on `(created_at, id)`. `?sort=created_at` (default) or `?sort=-created_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "created_at" => Some(SortOrder::Asc),
+            "-created_at" => Some(SortOrder::Desc),
+            _ => None,
+        }
+    }
+}
+
+/// Position of the last row of a page. Opaque to clients (base64url of `created_at|id`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: Uuid) -> Self {
+        Self { created_at, id }
+    }
+
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}", self.created_at.to_rfc3339(), self.id);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(s: &str) -> Option<Self> {
+        let raw = URL_SAFE_NO_PAD.decode(s).ok()?;
+        let raw = String::from_utf8(raw).ok()?;
+        let (ts, id) = raw.split_once('|')?;
+        Some(Self {
+            created_at: DateTime::parse_from_rfc3339(ts).ok()?.with_timezone(&Utc),
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RawPageParams {
+    limit: Option<i64>,
+    cursor: Option<String>,
+    sort: Option<String>,
+}
+
+/// `limit` / `cursor` / `sort` query parameters. Other query parameters (filters) are ignored
+/// here and can be extracted separately with `Query<...>`.
+#[derive(Debug, Clone)]
+pub struct PageParams {
+    pub limit: i64,
+    pub cursor: Option<Cursor>,
+    pub sort: SortOrder,
+}
+
+impl PageParams {
+    /// Rows to fetch: one more than the page size so we know whether a next page exists.
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// SQL predicate selecting rows after the cursor. Binds two parameters starting at `$n`:
+    /// [`PageParams::cursor_created_at`] then [`PageParams::cursor_id`].
+    pub fn keyset_predicate(&self, n: usize) -> String {
+        let op = match self.sort {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+        format!(
+            "(${n}::TIMESTAMPTZ IS NULL OR (created_at, id) {op} (${n}, ${m}::UUID))",
+            m = n + 1
+        )
+    }
+
+    pub fn order_by(&self) -> &'static str {
+        match self.sort {
+            SortOrder::Asc => "ORDER BY created_at ASC, id ASC",
+            SortOrder::Desc => "ORDER BY created_at DESC, id DESC",
+        }
+    }
+
+    pub fn cursor_created_at(&self) -> Option<DateTime<Utc>> {
+        self.cursor.map(|c| c.created_at)
+    }
+
+    pub fn cursor_id(&self) -> Option<Uuid> {
+        self.cursor.map(|c| c.id)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for PageParams
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(raw) = Query::<RawPageParams>::try_from_uri(&parts.uri)
+            .map_err(|_| ApiError::BadRequest("invalid pagination parameters"))?;
+        let limit = raw.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(ApiError::BadRequest("limit must be between 1 and 100"));
+        }
+        let cursor = raw
+            .cursor
+            .as_deref()
+            .map(|c| Cursor::decode(c).ok_or(ApiError::BadRequest("invalid cursor")))
+            .transpose()?;
+        let sort = raw
+            .sort
+            .as_deref()
+            .map(|s| SortOrder::parse(s).ok_or(ApiError::BadRequest("invalid sort")))
+            .transpose()?
+            .unwrap_or_default();
+        Ok(PageParams {
+            limit,
+            cursor,
+            sort,
+        })
+    }
+}
+
+/// `q` with the `LIKE` wildcards `%` and `_` (and the escape character `\`) escaped, for
+/// filters written as `ILIKE '%' || $n || '%' ESCAPE '\'`.
+pub fn escape_like(q: &str) -> String {
+    let mut escaped = String::with_capacity(q.len());
+    for c in q.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Response envelope for list endpoints. `next_cursor` is absent on the last page.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from up to [`PageParams::fetch_limit`] rows; `key` yields a row's cursor.
+    pub fn from_rows(mut items: Vec<T>, params: &PageParams, key: impl Fn(&T) -> Cursor) -> Self {
+        let has_more = items.len() as i64 > params.limit;
+        items.truncate(params.limit as usize);
+        let next_cursor = if has_more {
+            items.last().map(|item| key(item).encode())
+        } else {
+            None
+        };
+        Self { items, next_cursor }
+    }
+}
@@ -0,0 +1,278 @@
+//! Role-based permissions: each role grants a set of `resource:action` permissions, either the
+//! built-in defaults or those read from `ROLE_PERMISSIONS_FILE`. Handlers state what they need
+//! with `Authorized<perm::...>` or `Claims::require`.
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use serde::{Deserialize, Deserializer};
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use crate::auth::{AuthUser, Claims, Role};
+use crate::config::AuthConfig;
+use crate::error::ApiError;
+
+/// Declares `Permission`, its `resource:action` names, and a marker type per permission in
+/// `perm` for use with `Authorized`.
+macro_rules! permissions {
+    ($($(#[$doc:meta])* $variant:ident => $name:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Permission {
+            $($(#[$doc])* $variant,)*
+        }
+
+        impl Permission {
+            pub const ALL: &'static [Permission] = &[$(Permission::$variant,)*];
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Permission::$variant => $name,)*
+                }
+            }
+
+            /// 403 body when the caller lacks this permission.
+            fn denied(&self) -> &'static str {
+                match self {
+                    $(Permission::$variant => concat!($name, " permission required"),)*
+                }
+            }
+        }
+
+        /// Marker types naming a permission at the type level, e.g. `Authorized<perm::CourseWrite>`.
+        pub mod perm {
+            $(
+                pub struct $variant;
+
+                impl super::RequiredPermission for $variant {
+                    const PERMISSION: super::Permission = super::Permission::$variant;
+                }
+            )*
+        }
+    };
+}
+
+permissions! {
+    /// Read a single course.
+    CourseRead => "course:read",
+    /// List and search all courses.
+    CourseList => "course:list",
+    /// Create, rename, archive and delete courses.
+    CourseWrite => "course:write",
+    /// List a course's enrollments and look up anyone's membership.
+    EnrollmentRead => "enrollment:read",
+    /// Enroll and unenroll users.
+    EnrollmentWrite => "enrollment:write",
+    /// Read a single assignment.
+    AssignmentRead => "assignment:read",
+    /// List a course's assignments.
+    AssignmentList => "assignment:list",
+    /// Create assignments.
+    AssignmentWrite => "assignment:write",
+    /// Submit work to an assignment.
+    SubmissionCreate => "submission:create",
+    /// Read one's own submissions.
+    SubmissionRead => "submission:read",
+    /// Read other users' submissions in courses one teaches.
+    SubmissionReview => "submission:review",
+    /// Grade submissions and release grades.
+    SubmissionGrade => "submission:grade",
+    /// Read grades: one's own once released, or any in courses one teaches.
+    GradeRead => "grade:read",
+    /// Create, disable and re-enable user accounts.
+    UserManage => "user:manage",
+}
+
+impl std::str::FromStr for Permission {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permission::ALL
+            .iter()
+            .find(|p| p.as_str() == s)
+            .copied()
+            .ok_or(())
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Permission {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| serde::de::Error::custom(format!("unknown permission {:?}", name)))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PolicyError {
+    #[error("reading {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("parsing {0}: {1}")]
+    Parse(String, serde_json::Error),
+}
+
+/// Which permissions each role grants. A caller holds the union of its roles' permissions.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    grants: HashMap<Role, HashSet<Permission>>,
+}
+
+impl Default for Policy {
+    /// The platform's built-in grants, used unless `ROLE_PERMISSIONS_FILE` is set.
+    fn default() -> Self {
+        use Permission::*;
+        let grants = [
+            (
+                Role::Admin,
+                vec![
+                    CourseRead,
+                    CourseList,
+                    CourseWrite,
+                    EnrollmentRead,
+                    EnrollmentWrite,
+                    UserManage,
+                ],
+            ),
+            (
+                Role::Teacher,
+                vec![
+                    AssignmentRead,
+                    AssignmentList,
+                    AssignmentWrite,
+                    SubmissionRead,
+                    SubmissionReview,
+                    SubmissionGrade,
+                    GradeRead,
+                ],
+            ),
+            (
+                Role::Student,
+                // Enrolled students read their course's assignments.
+                vec![AssignmentRead, SubmissionCreate, SubmissionRead, GradeRead],
+            ),
+        ];
+        Self {
+            grants: grants
+                .into_iter()
+                .map(|(role, permissions)| (role, permissions.into_iter().collect()))
+                .collect(),
+        }
+    }
+}
+
+impl Policy {
+    /// Grants from a JSON object mapping role to permission names, e.g.
+    /// `{"teacher": ["assignment:write", "submission:grade"]}`. Roles left out grant nothing;
+    /// unknown roles or permissions are rejected.
+    pub fn from_file(path: &str) -> Result<Self, PolicyError> {
+        let body = std::fs::read(path).map_err(|e| PolicyError::Io(path.to_string(), e))?;
+        let grants: HashMap<Role, HashSet<Permission>> =
+            serde_json::from_slice(&body).map_err(|e| PolicyError::Parse(path.to_string(), e))?;
+        Ok(Self { grants })
+    }
+
+    pub fn from_config(config: &AuthConfig) -> Result<Self, PolicyError> {
+        match &config.permissions_file {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn allows(&self, roles: &[Role], permission: Permission) -> bool {
+        roles.iter().any(|role| {
+            self.grants
+                .get(role)
+                .is_some_and(|granted| granted.contains(&permission))
+        })
+    }
+}
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Install the role-to-permission mapping from the service's config; call once at startup so
+/// a broken `ROLE_PERMISSIONS_FILE` stops the service instead of denying every request.
+pub fn init_policy(config: &AuthConfig) -> Result<(), PolicyError> {
+    let policy = Policy::from_config(config)?;
+    if POLICY.set(policy).is_err() {
+        tracing::warn!("permission policy already initialised; ignoring new config");
+    }
+    Ok(())
+}
+
+/// Process-wide policy; built from the environment if `init_policy` was not called. A policy
+/// file that fails to load then grants nothing.
+pub fn policy() -> &'static Policy {
+    POLICY.get_or_init(|| {
+        Policy::from_config(&AuthConfig::from_env()).unwrap_or_else(|e| {
+            tracing::error!("permission policy: {}; denying all permissions", e);
+            Policy {
+                grants: HashMap::new(),
+            }
+        })
+    })
+}
+
+impl Claims {
+    /// Whether any of the caller's roles grants `permission`.
+    pub fn can(&self, permission: Permission) -> bool {
+        policy().allows(&self.roles, permission)
+    }
+
+    /// `can`, as a 403 for handlers whose required permission depends on the request.
+    pub fn require(&self, permission: Permission) -> Result<(), ApiError> {
+        if self.can(permission) {
+            Ok(())
+        } else {
+            tracing::info!(
+                target: "access",
+                sub = %self.sub,
+                permission = permission.as_str(),
+                "deny"
+            );
+            Err(ApiError::Forbidden(permission.denied()))
+        }
+    }
+}
+
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+/// Authenticated caller holding permission `P`; otherwise the request is rejected with 403.
+/// Derefs to the caller's `Claims`.
+pub struct Authorized<P> {
+    pub claims: Claims,
+    permission: PhantomData<P>,
+}
+
+impl<P> Deref for Authorized<P> {
+    type Target = Claims;
+
+    fn deref(&self) -> &Claims {
+        &self.claims
+    }
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for Authorized<P>
+where
+    S: Send + Sync,
+    P: RequiredPermission,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthUser(claims) = AuthUser::from_request_parts(parts, state).await?;
+        claims.require(P::PERMISSION)?;
+        Ok(Authorized {
+            claims,
+            permission: PhantomData,
+        })
+    }
+}
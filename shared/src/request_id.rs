@@ -0,0 +1,47 @@
+//! `x-request-id` for every request: the caller's when it sends a usable one, a new UUID
+//! otherwise. `propagate` echoes it in the response and makes it available to the request span
+//! (see `trace_context::make_span`) and, through `current`, to `ServiceClient`, which forwards
+//! it to upstreams.
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use uuid::Uuid;
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest caller-supplied id that is kept.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: HeaderValue;
+}
+
+/// Id of the request being handled; `None` outside `propagate`.
+pub fn current() -> Option<HeaderValue> {
+    REQUEST_ID.try_with(HeaderValue::clone).ok()
+}
+
+/// Middleware assigning the request id; add it outside `TraceLayer` so the request span sees
+/// the id.
+pub async fn propagate(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(X_REQUEST_ID)
+        .filter(|id| usable(id))
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&Uuid::new_v4().to_string()).expect("a UUID is a valid header")
+        });
+    req.headers_mut().insert(X_REQUEST_ID, id.clone());
+    let mut res = REQUEST_ID.scope(id.clone(), next.run(req)).await;
+    res.headers_mut().insert(X_REQUEST_ID, id);
+    res
+}
+
+/// Ids end up in logs, so only short printable ASCII ones are accepted.
+fn usable(id: &HeaderValue) -> bool {
+    let id = id.as_bytes();
+    !id.is_empty() && id.len() <= MAX_LEN && id.iter().all(u8::is_ascii_graphic)
+}
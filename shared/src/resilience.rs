@@ -0,0 +1,276 @@
+//! Outbound resilience as tower layers over `HttpService`, a reqwest-backed `Service`. Retry and
+//! circuit breaking are implemented here; timeout, concurrency limit (bulkhead) and load-shed
+//! are tower's own. `ServiceClient` stacks them per upstream as
+//!
+//! ```text
+//! LoadShed → GlobalConcurrencyLimit → Retry → CircuitBreaker → UpstreamMetrics → Timeout
+//!   → HttpService
+//! ```
+//!
+//! so a call over the limit is rejected at once, a call holds its concurrency slot across
+//! retries, and every attempt passes the breaker, is measured (see `metrics`) and has its own
+//! timeout. Services can compose the layers differently for their own clients.
+//!
+//! Every layer answers with the upstream's response whatever its status; only failures to get
+//! one are errors (`tower::BoxError`, holding e.g. a `reqwest::Error`, tower's `Elapsed` or a
+//! `ServiceError`).
+
+use reqwest::{Client, Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tower::{BoxError, Layer, Service, ServiceExt};
+
+use crate::circuit::CircuitBreaker;
+use crate::http_client::ServiceError;
+use crate::metrics;
+use crate::retry::{retry_after, RetryPolicy};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// The call's retry deadline passed before an attempt answered.
+#[derive(Debug, thiserror::Error)]
+#[error("call deadline exceeded")]
+pub struct DeadlineExceeded;
+
+/// Sends requests with a reqwest `Client`; always ready.
+#[derive(Clone)]
+pub struct HttpService {
+    /// Shared with `ServiceClient`, which swaps the client when TLS certificates are reloaded.
+    client: Arc<RwLock<Client>>,
+}
+
+impl HttpService {
+    pub fn new(client: Client) -> Self {
+        Self::shared(Arc::new(RwLock::new(client)))
+    }
+
+    pub(crate) fn shared(client: Arc<RwLock<Client>>) -> Self {
+        Self { client }
+    }
+}
+
+impl Service<Request> for HttpService {
+    type Response = Response;
+    type Error = BoxError;
+    type Future = BoxFuture<Result<Response, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let client = self.client.read().unwrap().clone();
+        Box::pin(async move { Ok(client.execute(req).await?) })
+    }
+}
+
+/// Retries attempts as a `RetryPolicy` allows; see `RetryPolicy::max_retries_for` for which
+/// requests may be repeated. Requests whose body cannot be cloned are sent once.
+#[derive(Clone)]
+pub struct RetryLayer {
+    policy: Arc<RetryPolicy>,
+    upstream: Option<Arc<str>>,
+}
+
+impl RetryLayer {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+            upstream: None,
+        }
+    }
+
+    /// Count retries in `upstream_retries_total` under `upstream`.
+    pub fn upstream(mut self, upstream: &str) -> Self {
+        self.upstream = Some(upstream.into());
+        self
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            inner,
+            policy: self.policy.clone(),
+            upstream: self.upstream.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Retry<S> {
+    inner: S,
+    policy: Arc<RetryPolicy>,
+    upstream: Option<Arc<str>>,
+}
+
+impl<S> Service<Request> for Retry<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = BoxError;
+    type Future = BoxFuture<Result<Response, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // The ready service handles the first attempt; later ones wait for readiness again.
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(retry(
+            inner,
+            self.policy.clone(),
+            self.upstream.clone(),
+            req,
+        ))
+    }
+}
+
+/// Stands in for deadlines too far away to represent as an `Instant`.
+const FAR_FUTURE: Duration = Duration::from_secs(30 * 365 * 24 * 60 * 60);
+
+async fn retry<S>(
+    mut inner: S,
+    policy: Arc<RetryPolicy>,
+    upstream: Option<Arc<str>>,
+    req: Request,
+) -> Result<Response, BoxError>
+where
+    S: Service<Request, Response = Response>,
+    S::Error: Into<BoxError>,
+{
+    let retries = policy.max_retries_for(&req);
+    let (method, url) = (req.method().clone(), req.url().clone());
+    let started = Instant::now();
+    let deadline = started
+        .checked_add(policy.deadline)
+        .unwrap_or(started + FAR_FUTURE);
+    let mut next = Some(req);
+    let mut retry = 0;
+    loop {
+        let req = next.take().expect("a request for every attempt");
+        if retry < retries {
+            next = req.try_clone();
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let attempt = async {
+            let ready = inner.ready().await.map_err(Into::into)?;
+            ready.call(req).await.map_err(Into::into)
+        };
+        let result = tokio::time::timeout(remaining, attempt)
+            .await
+            .unwrap_or_else(|_| Err(DeadlineExceeded.into()));
+        let (retryable, wait) = match &result {
+            Ok(res) => (
+                policy.retries_status(res.status()),
+                retry_after(res.headers()),
+            ),
+            Err(e) => (policy.retries_error(e.as_ref()), None),
+        };
+        if !retryable || next.is_none() {
+            return result;
+        }
+        retry += 1;
+        let delay = wait.unwrap_or_else(|| policy.backoff(retry));
+        let outcome = match &result {
+            Ok(res) => res.status().to_string(),
+            Err(e) => e.to_string(),
+        };
+        if delay >= deadline.saturating_duration_since(Instant::now()) {
+            tracing::warn!(
+                "{} {}: not retrying past the deadline: {}",
+                method,
+                url,
+                outcome
+            );
+            return result;
+        }
+        tracing::warn!(
+            "{} {}: retry {} of {} in {:?}: {}",
+            method,
+            url,
+            retry,
+            retries,
+            delay,
+            outcome
+        );
+        if let Some(upstream) = &upstream {
+            metrics::record_retry(upstream);
+        }
+        sleep(delay).await;
+    }
+}
+
+/// Passes attempts through a `CircuitBreaker`: rejected with `ServiceError::CircuitOpen` while
+/// it is open, counted as failures on errors and 5xx answers.
+#[derive(Clone)]
+pub struct CircuitBreakerLayer {
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl CircuitBreakerLayer {
+    pub fn new(breaker: Arc<CircuitBreaker>) -> Self {
+        Self { breaker }
+    }
+}
+
+impl<S> Layer<S> for CircuitBreakerLayer {
+    type Service = CircuitBreakerService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreakerService {
+            inner,
+            breaker: self.breaker.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CircuitBreakerService<S> {
+    inner: S,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl<S> Service<Request> for CircuitBreakerService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = BoxError;
+    type Future = BoxFuture<Result<Response, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let breaker = self.breaker.clone();
+        Box::pin(async move {
+            let Some(permit) = breaker.try_acquire() else {
+                return Err(ServiceError::CircuitOpen(breaker.upstream().to_string()).into());
+            };
+            let result = inner.call(req).await.map_err(Into::into);
+            match &result {
+                // A 4xx (e.g. a 404 membership lookup) is an answer, not an unhealthy upstream.
+                Ok(res) if !res.status().is_server_error() => permit.success(),
+                _ => permit.failure(),
+            }
+            result
+        })
+    }
+}
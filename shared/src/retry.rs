@@ -0,0 +1,164 @@
+//! When and how `ServiceClient` retries a failed call: only for listed status codes and error
+//! kinds, with full-jitter exponential backoff (or the upstream's `Retry-After`), and never past
+//! the call's deadline.
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+/// Marks a request that is safe to repeat; the upstream applies it at most once per key.
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Transport failures that may be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryableError {
+    /// The connection could not be established, so the request was never sent.
+    Connect,
+    /// No answer within the attempt's timeout; the upstream may still have acted on it.
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts after the first one.
+    pub max_retries: u32,
+    /// Backoff before retry `n` is random in `0..=min(max_delay, base_delay * 2^(n-1))`.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub statuses: Vec<StatusCode>,
+    pub errors: Vec<RetryableError>,
+    /// Budget for the whole call: attempts, backoff and `Retry-After` waits.
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+            statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            errors: vec![RetryableError::Connect, RetryableError::Timeout],
+            deadline: Duration::from_secs(30),
+        }
+    }
+}
+
+fn millis_var(name: &str) -> Option<Duration> {
+    env::var(name)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .map(Duration::from_millis)
+}
+
+impl RetryPolicy {
+    /// A single attempt.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// `HTTP_RETRY_MAX`, `HTTP_RETRY_BASE_DELAY_MS`, `HTTP_RETRY_MAX_DELAY_MS`,
+    /// `HTTP_RETRY_STATUSES` (comma-separated codes), `HTTP_RETRY_ERRORS` (`connect`,
+    /// `timeout`) and `HTTP_RETRY_DEADLINE_MS`; unset values keep the defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_retries: env::var("HTTP_RETRY_MAX")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.max_retries),
+            base_delay: millis_var("HTTP_RETRY_BASE_DELAY_MS").unwrap_or(default.base_delay),
+            max_delay: millis_var("HTTP_RETRY_MAX_DELAY_MS").unwrap_or(default.max_delay),
+            statuses: env::var("HTTP_RETRY_STATUSES")
+                .map(|s| {
+                    s.split(',')
+                        .filter_map(|code| code.trim().parse::<u16>().ok())
+                        .filter_map(|code| StatusCode::from_u16(code).ok())
+                        .collect()
+                })
+                .unwrap_or(default.statuses),
+            errors: env::var("HTTP_RETRY_ERRORS")
+                .map(|s| {
+                    s.split(',')
+                        .filter_map(|kind| match kind.trim() {
+                            "connect" => Some(RetryableError::Connect),
+                            "timeout" => Some(RetryableError::Timeout),
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .unwrap_or(default.errors),
+            deadline: millis_var("HTTP_RETRY_DEADLINE_MS").unwrap_or(default.deadline),
+        }
+    }
+
+    pub fn retries_status(&self, status: StatusCode) -> bool {
+        self.statuses.contains(&status)
+    }
+
+    /// Retries allowed for `req`. Requests with a non-idempotent method get none unless they
+    /// carry an `Idempotency-Key`, since a failed POST or PATCH may already have taken effect.
+    pub fn max_retries_for(&self, req: &reqwest::Request) -> u32 {
+        let idempotent = matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+        );
+        if idempotent || req.headers().contains_key(IDEMPOTENCY_KEY) {
+            self.max_retries
+        } else {
+            0
+        }
+    }
+
+    /// Whether a failed attempt may be retried: connection failures, and reqwest or tower
+    /// timeouts, if listed.
+    pub fn retries_error(&self, err: &(dyn Error + 'static)) -> bool {
+        let kind = if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            if err.is_connect() {
+                RetryableError::Connect
+            } else if err.is_timeout() {
+                RetryableError::Timeout
+            } else {
+                return false;
+            }
+        } else if err.is::<tower::timeout::error::Elapsed>() {
+            RetryableError::Timeout
+        } else {
+            return false;
+        };
+        self.errors.contains(&kind)
+    }
+
+    /// Full-jitter backoff before retry `retry` (1 for the first retry).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let cap = exp.min(self.max_delay).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=cap))
+    }
+}
+
+/// Wait requested by a `Retry-After` header, given in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+        .or(Some(Duration::ZERO))
+}
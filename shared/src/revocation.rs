@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// After a failed fetch, wait this long before trying again.
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RevocationError {
+    #[error("fetching revocation list: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("revocation list not loaded yet")]
+    Unavailable,
+}
+
+/// Body of the auth-service's `GET /api/auth/revoked`: `jti`s of access tokens that were
+/// revoked before they expire.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct RevokedTokens {
+    pub revoked: Vec<String>,
+}
+
+#[derive(Default)]
+struct CachedList {
+    jtis: HashSet<String>,
+    fetched_at: Option<Instant>,
+}
+
+/// Cached copy of the published `jti` denylist. The list is refetched once it is older than
+/// `max_age`, so a revoked token may still be accepted for up to that long.
+pub struct RevocationList {
+    url: String,
+    max_age: Duration,
+    http: reqwest::Client,
+    cache: RwLock<CachedList>,
+    /// Time of the last fetch attempt; the lock also makes concurrent refreshes single-flight.
+    last_attempt: tokio::sync::Mutex<Option<Instant>>,
+}
+
+impl RevocationList {
+    pub fn new(url: String, max_age: Duration) -> Self {
+        Self {
+            url,
+            max_age,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .expect("reqwest client"),
+            cache: RwLock::new(CachedList::default()),
+            last_attempt: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    async fn fetch(&self) -> Result<HashSet<String>, RevocationError> {
+        let list: RevokedTokens = self
+            .http
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(list.revoked.into_iter().collect())
+    }
+
+    /// Refetch a stale list, at most once per `MIN_RETRY_INTERVAL` while fetches are failing.
+    /// A failed refetch keeps the previous list, so lookups only fail while no list has ever
+    /// been loaded.
+    async fn refresh(&self) -> Result<(), RevocationError> {
+        let mut last_attempt = self.last_attempt.lock().await;
+        let loaded = self.cache.read().expect("revocation lock").fetched_at;
+        if loaded.is_some_and(|t| t.elapsed() < self.max_age) {
+            return Ok(());
+        }
+        // An attempt newer than the loaded list failed; don't retry straight away.
+        let failed_recently = last_attempt.is_some_and(|t| {
+            t.elapsed() < MIN_RETRY_INTERVAL && loaded.is_none_or(|loaded| loaded < t)
+        });
+        if failed_recently {
+            return match loaded {
+                Some(_) => Ok(()),
+                None => Err(RevocationError::Unavailable),
+            };
+        }
+        *last_attempt = Some(Instant::now());
+        match self.fetch().await {
+            Ok(jtis) => {
+                *self.cache.write().expect("revocation lock") = CachedList {
+                    jtis,
+                    fetched_at: Some(Instant::now()),
+                };
+                Ok(())
+            }
+            Err(e) if loaded.is_some() => {
+                tracing::warn!("revocation list refresh failed, keeping cached list: {}", e);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn is_revoked(&self, jti: &str) -> Result<bool, RevocationError> {
+        let stale = self
+            .cache
+            .read()
+            .expect("revocation lock")
+            .fetched_at
+            .is_none_or(|t| t.elapsed() >= self.max_age);
+        if stale {
+            self.refresh().await?;
+        }
+        Ok(self
+            .cache
+            .read()
+            .expect("revocation lock")
+            .jtis
+            .contains(jti))
+    }
+}
@@ -0,0 +1,200 @@
+//! Service identity for internal calls. Services sign short-lived HS256 tokens with the shared
+//! `SERVICE_TOKEN_SECRET`, naming the calling service (`iss`), the target service (`aud`) and,
+//! when acting for a user, that user's `sub` and roles. `/internal/...` routes accept only these
+//! tokens, so user tokens never reach them and they never have to accept user tokens. Each route
+//! also names the services expected to call it (`ServiceCaller::allow`).
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::auth::{Claims, Role};
+use crate::config::Config;
+use crate::error::ApiError;
+
+/// Service tokens are minted per call, so they only need to outlive retries.
+const SERVICE_TOKEN_TTL_SECS: i64 = 60;
+/// `typ` of service tokens; user tokens never carry it.
+const SERVICE_TOKEN_TYPE: &str = "service";
+
+/// The end user a service acts for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelegatedUser {
+    pub sub: String,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ServiceTokenClaims {
+    typ: String,
+    iss: String,
+    aud: String,
+    iat: i64,
+    exp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    act_for: Option<DelegatedUser>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceAuthError {
+    #[error("SERVICE_TOKEN_SECRET is not set")]
+    NotConfigured,
+    #[error("missing service token")]
+    Missing,
+    #[error("invalid service token: {0}")]
+    Invalid(jsonwebtoken::errors::Error),
+    #[error("not a service token")]
+    NotServiceToken,
+    #[error("signing service token: {0}")]
+    Sign(jsonwebtoken::errors::Error),
+}
+
+impl From<ServiceAuthError> for ApiError {
+    fn from(e: ServiceAuthError) -> Self {
+        match e {
+            ServiceAuthError::NotConfigured => {
+                ApiError::Internal("service authentication is not configured")
+            }
+            ServiceAuthError::Missing => ApiError::Unauthorized("missing service token"),
+            ServiceAuthError::Invalid(_) | ServiceAuthError::NotServiceToken => {
+                ApiError::Unauthorized("invalid service token")
+            }
+            ServiceAuthError::Sign(_) => ApiError::Internal("service token signing failed"),
+        }
+    }
+}
+
+/// Mints this service's tokens and verifies tokens addressed to it.
+pub struct ServiceTokens {
+    name: String,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl ServiceTokens {
+    /// `name` is this service's `SERVICE_NAME`: the `iss` of its tokens and the `aud` it accepts.
+    pub fn new(name: impl Into<String>, secret: &str) -> Self {
+        Self {
+            name: name.into(),
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self, ServiceAuthError> {
+        let secret = config
+            .service_token_secret
+            .as_deref()
+            .ok_or(ServiceAuthError::NotConfigured)?;
+        Ok(Self::new(config.service_name.clone(), secret))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Token for calling `audience` (the target's `SERVICE_NAME`), acting for `user` if given.
+    pub fn mint(&self, audience: &str, user: Option<&Claims>) -> Result<String, ServiceAuthError> {
+        let now = chrono::Utc::now().timestamp();
+        let claims = ServiceTokenClaims {
+            typ: SERVICE_TOKEN_TYPE.to_string(),
+            iss: self.name.clone(),
+            aud: audience.to_string(),
+            iat: now,
+            exp: now + SERVICE_TOKEN_TTL_SECS,
+            act_for: user.map(|u| DelegatedUser {
+                sub: u.sub.clone(),
+                roles: u.roles.clone(),
+            }),
+        };
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+            .map_err(ServiceAuthError::Sign)
+    }
+
+    /// Check a token addressed to this service and return who sent it.
+    pub fn verify(&self, token: &str) -> Result<ServiceCaller, ServiceAuthError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&[&self.name]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        let claims = decode::<ServiceTokenClaims>(token, &self.decoding, &validation)
+            .map_err(ServiceAuthError::Invalid)?
+            .claims;
+        if claims.typ != SERVICE_TOKEN_TYPE {
+            return Err(ServiceAuthError::NotServiceToken);
+        }
+        Ok(ServiceCaller {
+            service: claims.iss,
+            user: claims.act_for.map(|u| Claims {
+                sub: u.sub,
+                roles: u.roles,
+                exp: claims.exp,
+                iss: None,
+                jti: None,
+            }),
+        })
+    }
+}
+
+static SERVICE_TOKENS: OnceLock<ServiceTokens> = OnceLock::new();
+
+/// Install this service's identity from its config; call once at startup.
+pub fn init_service_tokens(config: &Config) -> Result<(), ServiceAuthError> {
+    install_service_tokens(ServiceTokens::from_config(config)?);
+    Ok(())
+}
+
+/// Install `tokens` as this process's service identity; later calls are ignored.
+pub fn install_service_tokens(tokens: ServiceTokens) {
+    if SERVICE_TOKENS.set(tokens).is_err() {
+        tracing::warn!("service tokens already initialised; ignoring new config");
+    }
+}
+
+/// Process-wide service identity used by `ServiceClient` and `ServiceCaller`.
+pub fn service_tokens() -> Result<&'static ServiceTokens, ServiceAuthError> {
+    SERVICE_TOKENS.get().ok_or(ServiceAuthError::NotConfigured)
+}
+
+/// Caller of an internal route, authenticated by its service token; `user` is the end user it
+/// acts for, if any. User tokens are rejected with 401.
+pub struct ServiceCaller {
+    pub service: String,
+    pub user: Option<Claims>,
+}
+
+impl ServiceCaller {
+    /// 403 unless the caller is one of `services`, the callers a route expects.
+    pub fn allow(&self, services: &[&str]) -> Result<(), ApiError> {
+        if services.contains(&self.service.as_str()) {
+            return Ok(());
+        }
+        tracing::info!(target: "access", service = %self.service, "deny: service not allowed");
+        Err(ApiError::Forbidden("service not allowed"))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ServiceCaller
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let caller = parts
+            .headers
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(ServiceAuthError::Missing)
+            .and_then(|token| service_tokens()?.verify(token));
+        match caller {
+            Ok(caller) => Ok(caller),
+            Err(e) => {
+                tracing::info!(target: "access", path = %parts.uri.path(), "deny: {}", e);
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! HTTPS listener and mutual TLS for `ServiceClient`, configured by `TlsConfig`. Certificate
+//! files are polled for changes and reloaded in place, so rotated certificates are picked up
+//! without a restart; a reload that fails keeps the previous certificates.
+
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::config::{ClientAuth, Config, TlsConfig};
+use crate::http_client::client_builder;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("reading {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("{0} contains no certificates")]
+    NoCertificates(String),
+    #[error("{0} contains no private key")]
+    NoPrivateKey(String),
+    #[error("TLS config: {0}")]
+    Rustls(#[from] rustls::Error),
+    #[error("client certificate verifier: {0}")]
+    Verifier(#[from] rustls::server::VerifierBuilderError),
+    #[error("HTTP client: {0}")]
+    Client(#[from] reqwest::Error),
+}
+
+fn read(path: &str) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|e| TlsError::Io(path.to_string(), e))
+}
+
+fn certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let pem = read(path)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::Io(path.to_string(), e))?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_string()));
+    }
+    Ok(certs)
+}
+
+fn private_key(path: &str) -> Result<PrivateKeyDer<'static>, TlsError> {
+    let pem = read(path)?;
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .map_err(|e| TlsError::Io(path.to_string(), e))?
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_string()))
+}
+
+/// Server-side rustls config. With a CA bundle, client certificates are verified against it
+/// (and required unless `client_auth` is optional).
+pub fn server_config(tls: &TlsConfig) -> Result<ServerConfig, TlsError> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match &tls.ca_file {
+        Some(ca_file) => {
+            let mut roots = RootCertStore::empty();
+            for cert in certificates(ca_file)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = match tls.client_auth {
+                ClientAuth::Required => verifier,
+                ClientAuth::Optional => verifier.allow_unauthenticated(),
+            };
+            builder.with_client_cert_verifier(verifier.build()?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config =
+        builder.with_single_cert(certificates(&tls.cert_file)?, private_key(&tls.key_file)?)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Outbound client presenting this service's certificate. With a CA bundle only upstreams whose
+/// certificates chain to it are trusted.
+pub fn client(tls: &TlsConfig) -> Result<reqwest::Client, TlsError> {
+    let mut identity = read(&tls.cert_file)?;
+    identity.push(b'\n');
+    identity.extend(read(&tls.key_file)?);
+    let mut builder = client_builder()
+        .use_rustls_tls()
+        .identity(reqwest::Identity::from_pem(&identity)?);
+    if let Some(ca_file) = &tls.ca_file {
+        builder = builder.tls_built_in_root_certs(false);
+        for cert in reqwest::Certificate::from_pem_bundle(&read(ca_file)?)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// Modification times of the certificate files; a missing file reads as `None`.
+fn modified(tls: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [
+        Some(&tls.cert_file),
+        Some(&tls.key_file),
+        tls.ca_file.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+    .collect()
+}
+
+/// Run `reload` whenever one of the certificate files changes, checking every `reload_secs`.
+/// A failed reload (e.g. the key is not written yet) is retried on the next check.
+pub fn watch(
+    tls: &TlsConfig,
+    what: &'static str,
+    reload: impl Fn(&TlsConfig) -> Result<(), TlsError> + Send + 'static,
+) {
+    if tls.reload_secs == 0 {
+        return;
+    }
+    let tls = tls.clone();
+    tokio::spawn(async move {
+        let mut loaded = modified(&tls);
+        let mut interval = tokio::time::interval(Duration::from_secs(tls.reload_secs));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let current = modified(&tls);
+            if current == loaded {
+                continue;
+            }
+            match reload(&tls) {
+                Ok(()) => {
+                    tracing::info!("reloaded {} certificates from {}", what, tls.cert_file);
+                    loaded = current;
+                }
+                Err(e) => tracing::warn!("reloading {} certificates: {}", what, e),
+            }
+        }
+    });
+}
+
+/// Serve `app` on `HTTP_PORT`: over HTTPS when TLS is configured, else plain HTTP.
+pub async fn serve(
+    app: Router,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.http_port));
+    let Some(tls) = &config.tls else {
+        axum::serve(
+            tokio::net::TcpListener::bind(addr).await?,
+            app.into_make_service(),
+        )
+        .await?;
+        return Ok(());
+    };
+    let rustls = RustlsConfig::from_config(Arc::new(server_config(tls)?));
+    let reloadable = rustls.clone();
+    watch(tls, "server", move |tls| {
+        reloadable.reload_from_config(Arc::new(server_config(tls)?));
+        Ok(())
+    });
+    tracing::info!(
+        "serving HTTPS (client certificates {})",
+        match (&tls.ca_file, tls.client_auth) {
+            (None, _) => "not requested",
+            (Some(_), ClientAuth::Required) => "required",
+            (Some(_), ClientAuth::Optional) => "optional",
+        }
+    );
+    axum_server::bind_rustls(addr, rustls)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
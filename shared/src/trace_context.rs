@@ -0,0 +1,97 @@
+//! W3C trace context (`traceparent`, `tracestate`) across service calls: `ServiceClient` injects
+//! the context of its call span into outbound requests, and `make_span` continues the caller's
+//! trace in the request span of the service that receives them.
+
+use axum::extract::MatchedPath;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Request, Uri};
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use tracing::field::Empty;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::request_id::X_REQUEST_ID;
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+/// Add `span`'s trace context to outbound `headers`; nothing is added when tracing is not set
+/// up (see `tracing_init`).
+pub fn inject(span: &Span, headers: &mut HeaderMap) {
+    TraceContextPropagator::new().inject_context(&span.context(), &mut HeaderInjector(headers));
+}
+
+/// Trace context sent by the caller, empty when there is none.
+pub fn extract(headers: &HeaderMap) -> opentelemetry::Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Span for an incoming request, for `TraceLayer::make_span_with`: named after the matched
+/// route, and a child of the caller's span when the request carries a `traceparent`. It
+/// carries the `x-request-id` set by `request_id::propagate`, and `AuthUser` records the
+/// caller's `sub` and `role` on it.
+pub fn make_span<B>(req: &Request<B>) -> Span {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(req.uri().path(), MatchedPath::as_str);
+    let request_id = req
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %redacted(req.uri()),
+        version = ?req.version(),
+        request_id,
+        sub = Empty,
+        role = Empty,
+        otel.name = %format!("{} {}", req.method(), route),
+        otel.kind = "server",
+    );
+    // Fails only when tracing is not set up, and then there is no trace to continue.
+    let _ = span.set_parent(extract(req.headers()));
+    span
+}
+
+/// Query parameters whose values are credentials, such as the signature of a download link.
+const SECRET_PARAMS: &[&str] = &["signature", "token", "access_token", "refresh_token"];
+
+/// `uri` with the values of `SECRET_PARAMS` replaced, for logs and spans.
+pub fn redacted(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if SECRET_PARAMS.contains(&name) => format!("{}=[redacted]", name),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{}?{}", uri.path(), query.join("&"))
+}
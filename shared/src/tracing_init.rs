@@ -1,9 +1,85 @@
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+//! Logging as text or JSON (`Config.log_format`), plus OpenTelemetry spans exported over
+//! OTLP/HTTP when `Config.otlp_endpoint` is set. The OpenTelemetry layer is installed either
+//! way, so trace context received from callers is passed on to upstreams (see
+//! `trace_context`) even by services that do not export.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider, TracerProviderBuilder};
+use opentelemetry_sdk::Resource;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{Config, LogFormat};
+
+/// Flushes buffered spans when dropped; keep it alive until the service exits. The subscriber
+/// is global, so a failed flush is still logged in the configured format.
+pub struct TracingGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            tracing::warn!("flushing spans: {}", e);
+        }
+    }
+}
+
+pub fn init_tracing(config: &Config) -> Result<TracingGuard, ExporterBuildError> {
+    let mut builder = tracer_provider_builder(&config.service_name);
+    if let Some(endpoint) = &config.otlp_endpoint {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()?;
+        builder = builder.with_batch_exporter(exporter);
+    }
+    let provider = builder.build();
 
-pub fn init_tracing() {
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let json = config.log_format == LogFormat::Json;
     tracing_subscriber::registry()
         .with(filter)
-        .with(tracing_subscriber::fmt::layer())
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(json.then(|| json_layer(std::io::stdout)))
+        .with(otel_layer(&provider))
         .init();
+    Ok(TracingGuard { provider })
+}
+
+/// One JSON object per line to `writer`, with the current span's fields (e.g. `request_id`,
+/// `sub`) under `span` next to the event's.
+pub fn json_layer<S, W>(writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .with_span_list(false)
+}
+
+/// Tracer provider for `service_name`; add an exporter and build it. Sampling follows
+/// `OTEL_TRACES_SAMPLER` / `OTEL_TRACES_SAMPLER_ARG` (default: always, or as the caller decided).
+pub fn tracer_provider_builder(service_name: &str) -> TracerProviderBuilder {
+    SdkTracerProvider::builder().with_resource(
+        Resource::builder()
+            .with_service_name(service_name.to_string())
+            .build(),
+    )
+}
+
+/// Layer turning `tracing` spans into OpenTelemetry spans of `provider`.
+pub fn otel_layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("shared"))
 }
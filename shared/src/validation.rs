@@ -0,0 +1,116 @@
+//! Request body validation: `ValidJson<T>` deserializes like `Json<T>` and then checks the
+//! `validator` rules declared on `T` (`#[validate(length(..))]`, `range`, `regex`, ...). A body
+//! breaking any rule is a 422 `ApiError::Validation` listing every invalid field. Rules that
+//! relate several fields are `#[validate(schema(function = ..))]` functions returning
+//! `schema_error`, so they are reported against a field too.
+//!
+//! String fields marked `#[serde(deserialize_with = "trim")]` (or `trim_opt`) are trimmed
+//! before the rules run, so `length(min = 1)` also rejects blank values.
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    Json,
+};
+use serde::de::{Deserialize, DeserializeOwned, Deserializer};
+use serde_json::Value;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::{ApiError, FieldError};
+
+/// JSON body that passed its validation rules.
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(body) = Json::<T>::from_request(req, state).await?;
+        body.validate()?;
+        Ok(ValidJson(body))
+    }
+}
+
+/// `deserialize_with` helper trimming surrounding whitespace.
+pub fn trim<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    String::deserialize(deserializer).map(|s| s.trim().to_string())
+}
+
+/// `trim` for optional fields; pair it with `#[serde(default)]` so the field may be omitted.
+pub fn trim_opt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(|s| s.map(|s| s.trim().to_string()))
+}
+
+/// Error for a schema rule, listed under `field` instead of validator's `__all__`.
+pub fn schema_error(field: &'static str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new("schema").with_message(message.into());
+    error.add_param("field".into(), &field);
+    error
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = Vec::new();
+        collect("", &errors, &mut fields);
+        // `ValidationErrors` is a map; sort so responses are stable.
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+        ApiError::Validation(fields)
+    }
+}
+
+/// Flatten nested errors into `parent.child` and `list[0].child` field paths.
+fn collect(prefix: &str, errors: &ValidationErrors, out: &mut Vec<FieldError>) {
+    let join = |field: &str| {
+        if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        }
+    };
+    for (field, kind) in errors.errors() {
+        let path = join(field);
+        match kind {
+            ValidationErrorsKind::Field(errors) => out.extend(errors.iter().map(|error| {
+                let path = match (field.as_ref(), error.params.get("field")) {
+                    ("__all__", Some(Value::String(name))) => join(name),
+                    _ => path.clone(),
+                };
+                FieldError::new(path, message(error))
+            })),
+            ValidationErrorsKind::Struct(nested) => collect(&path, nested, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect(&format!("{}[{}]", path, index), nested, out);
+                }
+            }
+        }
+    }
+}
+
+/// The rule's `message`, or one built from its code and bounds.
+fn message(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    let param = |name: &str| error.params.get(name).map(|value| value.to_string());
+    match (error.code.as_ref(), param("min"), param("max")) {
+        ("length", Some(min), Some(max)) => format!("length must be between {} and {}", min, max),
+        ("length", Some(min), None) => format!("length must be at least {}", min),
+        ("length", None, Some(max)) => format!("length must be at most {}", max),
+        ("range", Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+        ("range", Some(min), None) => format!("must be at least {}", min),
+        ("range", None, Some(max)) => format!("must be at most {}", max),
+        ("range", None, None) => match (param("exclusive_min"), param("exclusive_max")) {
+            (Some(min), _) => format!("must be greater than {}", min),
+            (_, Some(max)) => format!("must be less than {}", max),
+            _ => "is out of range".to_string(),
+        },
+        ("regex", ..) => "has an invalid format".to_string(),
+        (code, ..) => format!("is invalid ({})", code),
+    }
+}
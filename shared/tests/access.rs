@@ -0,0 +1,252 @@
+//! Access decisions against a stub `Memberships`, and course membership lookups through
+//! `AdminMemberships` against an in-process admin-service stand-in that authenticates callers
+//! by service token.
+
+use async_trait::async_trait;
+use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+use serde_json::json;
+use shared::access::{
+    AdminMemberships, Grant, Membership, MembershipError, Memberships, NoMemberships,
+};
+use shared::circuit::{CircuitConfig, CircuitStatus};
+use shared::service_auth::{install_service_tokens, ServiceTokens};
+use shared::{ApiError, Claims, Permission, Resource, RetryPolicy, Role, ServiceClient};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const SERVICE_SECRET: &str = "access-test-service-secret";
+
+/// admin-service stand-in: `teacher-1` teaches every course, nobody else is enrolled.
+async fn admin_service() -> (ServiceClient, Arc<AtomicUsize>) {
+    install_service_tokens(ServiceTokens::new("teacher-service", SERVICE_SECRET));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let admin = Arc::new(ServiceTokens::new("admin-service", SERVICE_SECRET));
+    let app = Router::new().route(
+        "/internal/courses/:course_id/members/:user_id",
+        get(
+            move |Path((_, user_id)): Path<(Uuid, String)>, headers: axum::http::HeaderMap| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let token =
+                    headers["authorization"].to_str().unwrap()["Bearer ".len()..].to_string();
+                let caller = admin.verify(&token).map(|caller| caller.service);
+                async move {
+                    assert_eq!(caller.unwrap(), "teacher-service");
+                    if user_id == "teacher-1" {
+                        Ok(Json(json!({"role": "teacher", "course_archived": false})))
+                    } else {
+                        Err(ApiError::NotFound("not enrolled"))
+                    }
+                }
+            },
+        ),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = ServiceClient::new([("admin-service".to_string(), base)])
+        .with_retry_policy(RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            ..RetryPolicy::default()
+        })
+        .with_circuit_config(
+            "admin-service",
+            CircuitConfig {
+                failure_rate: 0.5,
+                min_calls: 2,
+                window: Duration::from_secs(10),
+                open_duration: Duration::from_secs(60),
+                half_open_probes: 1,
+            },
+        );
+    (client, hits)
+}
+
+#[tokio::test]
+async fn non_members_are_answers_not_upstream_failures() {
+    let (client, hits) = admin_service().await;
+    let memberships = AdminMemberships::new(&client);
+    let course = Uuid::new_v4();
+
+    // Each non-member is one request: a 404 is neither retried nor counted by the circuit.
+    for n in 0..5 {
+        let user = format!("student-{}", n);
+        assert!(memberships
+            .membership(course, &user)
+            .await
+            .unwrap()
+            .is_none());
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 5);
+    assert_eq!(
+        client.circuit_status("admin-service"),
+        Some(CircuitStatus::Closed)
+    );
+
+    let member = memberships
+        .membership(course, "teacher-1")
+        .await
+        .unwrap()
+        .expect("teacher-1 is enrolled");
+    assert_eq!(member.role, shared::Role::Teacher);
+    assert_eq!(hits.load(Ordering::SeqCst), 6);
+}
+
+/// Enrollments keyed by user, or a lookup that always fails; counts lookups.
+struct Enrollments {
+    roles: HashMap<&'static str, Role>,
+    fail: bool,
+    lookups: AtomicUsize,
+}
+
+impl Enrollments {
+    fn new(roles: &[(&'static str, Role)]) -> Self {
+        Self {
+            roles: roles.iter().copied().collect(),
+            fail: false,
+            lookups: AtomicUsize::new(0),
+        }
+    }
+
+    fn failing() -> Self {
+        Self {
+            fail: true,
+            ..Self::new(&[])
+        }
+    }
+
+    fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl Memberships for Enrollments {
+    async fn membership(
+        &self,
+        _: Uuid,
+        user_id: &str,
+    ) -> Result<Option<Membership>, MembershipError> {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        if self.fail {
+            return Err(MembershipError("admin-service unavailable".to_string()));
+        }
+        Ok(self.roles.get(user_id).map(|role| Membership {
+            role: *role,
+            course_archived: false,
+        }))
+    }
+}
+
+fn claims(sub: &str, roles: &[Role]) -> Claims {
+    Claims {
+        sub: sub.to_string(),
+        roles: roles.to_vec(),
+        exp: i64::MAX,
+        iss: None,
+        jti: None,
+    }
+}
+
+/// A submission by `student-1` in a course, reviewable by the course's teachers.
+fn submission(course: Uuid) -> Resource<'static> {
+    Resource::new("submission", "s-1")
+        .owned_by("student-1")
+        .in_course(course, Permission::SubmissionReview)
+}
+
+#[tokio::test]
+async fn owners_need_no_membership() {
+    let enrollments = Enrollments::failing();
+    let decision = claims("student-1", &[Role::Student])
+        .authorize(&submission(Uuid::new_v4()), &enrollments)
+        .await
+        .unwrap();
+    assert_eq!(decision.grant, Grant::Owner);
+    assert_eq!(enrollments.lookups(), 0);
+}
+
+#[tokio::test]
+async fn override_permissions_skip_the_course_check() {
+    let enrollments = Enrollments::failing();
+    let resource = submission(Uuid::new_v4()).or_permission(Permission::SubmissionGrade);
+    let decision = claims("teacher-9", &[Role::Teacher])
+        .authorize(&resource, &enrollments)
+        .await
+        .unwrap();
+    assert_eq!(decision.grant, Grant::Permission);
+    assert_eq!(enrollments.lookups(), 0);
+}
+
+#[tokio::test]
+async fn course_members_need_the_permission_in_their_enrollment_role() {
+    let enrollments = Enrollments::new(&[("ta-1", Role::Student), ("teacher-1", Role::Teacher)]);
+    let course = Uuid::new_v4();
+
+    let decision = claims("teacher-1", &[Role::Teacher])
+        .authorize(&submission(course), &enrollments)
+        .await
+        .unwrap();
+    assert_eq!(decision.grant, Grant::CourseMember);
+    assert_eq!(decision.membership.unwrap().role, Role::Teacher);
+
+    // A teacher elsewhere, enrolled here only as a student.
+    let err = claims("ta-1", &[Role::Teacher, Role::Student])
+        .authorize(&submission(course), &enrollments)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    assert_eq!(err.to_string(), "not enrolled in this course");
+
+    let err = claims("teacher-2", &[Role::Teacher])
+        .authorize(
+            &submission(course).deny_as(ApiError::NotFound("submission not found")),
+            &enrollments,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::NOT_FOUND);
+    assert_eq!(enrollments.lookups(), 3);
+
+    // Without the permission in the token there is nothing to look up.
+    let err = claims("student-2", &[Role::Student])
+        .authorize(&submission(course), &enrollments)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    assert_eq!(enrollments.lookups(), 3);
+}
+
+#[tokio::test]
+async fn failed_lookups_are_bad_gateway() {
+    let err = claims("teacher-1", &[Role::Teacher])
+        .authorize(&submission(Uuid::new_v4()), &Enrollments::failing())
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn unscoped_resources_rely_on_the_role_check() {
+    let caller = claims("admin-1", &[Role::Admin]);
+    let decision = caller
+        .authorize(&Resource::new("course", "c-1"), &NoMemberships)
+        .await
+        .unwrap();
+    assert_eq!(decision.grant, Grant::Unscoped);
+
+    // Someone else's resource outside any course is denied.
+    let err = caller
+        .authorize(
+            &Resource::new("submission", "s-1").owned_by("student-1"),
+            &NoMemberships,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    assert_eq!(err.to_string(), "access denied");
+}
@@ -0,0 +1,138 @@
+//! Blob stores (a temporary directory, and `S3BlobStore` over `InMemory` standing in for a
+//! bucket) and signed download links.
+
+use bytes::Bytes;
+use object_store::{memory::InMemory, path::Path as ObjectPath, Attribute, ObjectStore};
+use shared::blob::{BlobError, BlobStore, LocalBlobStore, S3BlobStore};
+use shared::UrlSigner;
+use std::sync::Arc;
+use std::time::Duration;
+
+const INVALID_KEYS: &[&str] = &["", "/etc/passwd", "../outside", "a//b", "a/./b", "a/"];
+
+/// Put, overwrite, get, delete (twice) and the errors every store must report the same way.
+async fn exercise(store: &dyn BlobStore) {
+    store
+        .put(
+            "submissions/s1/1/a",
+            Bytes::from_static(b"first"),
+            "text/plain",
+        )
+        .await
+        .unwrap();
+    store
+        .put(
+            "submissions/s1/1/a",
+            Bytes::from_static(b"second"),
+            "text/plain",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get("submissions/s1/1/a").await.unwrap(),
+        Bytes::from_static(b"second")
+    );
+
+    store.delete("submissions/s1/1/a").await.unwrap();
+    store.delete("submissions/s1/1/a").await.unwrap();
+    assert!(matches!(
+        store.get("submissions/s1/1/a").await,
+        Err(BlobError::NotFound(_))
+    ));
+
+    for key in INVALID_KEYS {
+        assert!(
+            matches!(
+                store.put(key, Bytes::new(), "text/plain").await,
+                Err(BlobError::InvalidKey(_))
+            ),
+            "{key}"
+        );
+        assert!(matches!(
+            store.get(key).await,
+            Err(BlobError::InvalidKey(_))
+        ));
+        assert!(matches!(
+            store.delete(key).await,
+            Err(BlobError::InvalidKey(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn local_store_keeps_files_under_its_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = LocalBlobStore::new(dir.path());
+    exercise(&store).await;
+
+    store
+        .put("a/b/c", Bytes::from_static(b"data"), "application/pdf")
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(dir.path().join("a/b/c")).unwrap(), b"data");
+    // The temporary file written before the rename is gone.
+    let names: Vec<_> = std::fs::read_dir(dir.path().join("a/b"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["c"]);
+}
+
+#[tokio::test]
+async fn s3_store_keeps_objects_with_their_content_type() {
+    let bucket: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+    let store = S3BlobStore::new(bucket.clone());
+    exercise(&store).await;
+
+    store
+        .put("a/b/c", Bytes::from_static(b"%PDF"), "application/pdf")
+        .await
+        .unwrap();
+    let object = bucket.get(&ObjectPath::from("a/b/c")).await.unwrap();
+    assert_eq!(
+        object
+            .attributes
+            .get(&Attribute::ContentType)
+            .map(|v| v.as_ref()),
+        Some("application/pdf")
+    );
+    assert_eq!(object.bytes().await.unwrap(), Bytes::from_static(b"%PDF"));
+}
+
+/// `(expires, signature)` from a URL made by `UrlSigner::sign`.
+fn query(url: &str) -> (i64, String) {
+    let (_, query) = url.split_once('?').unwrap();
+    let (expires, signature) = query.split_once('&').unwrap();
+    (
+        expires.strip_prefix("expires=").unwrap().parse().unwrap(),
+        signature.strip_prefix("signature=").unwrap().to_string(),
+    )
+}
+
+#[test]
+fn signed_urls_verify_only_for_their_path_and_expiry() {
+    let signer = UrlSigner::new("download-secret");
+    let (url, expires_at) = signer.sign("/files/1", Duration::from_secs(60));
+    assert!(url.starts_with("/files/1?expires="));
+    let (expires, signature) = query(&url);
+    assert_eq!(expires, expires_at.timestamp());
+    assert!(signer.verify("/files/1", expires, &signature));
+
+    assert!(!signer.verify("/files/2", expires, &signature));
+    assert!(!signer.verify("/files/1", expires + 3600, &signature));
+    let mut tampered = signature.clone().into_bytes();
+    tampered[0] = if tampered[0] == b'A' { b'B' } else { b'A' };
+    assert!(!signer.verify("/files/1", expires, &String::from_utf8(tampered).unwrap()));
+    assert!(!signer.verify("/files/1", expires, "not base64!"));
+    assert!(!UrlSigner::new("other-secret").verify("/files/1", expires, &signature));
+}
+
+#[tokio::test]
+async fn signed_urls_expire() {
+    let signer = UrlSigner::new("download-secret");
+    let (url, _) = signer.sign("/files/1", Duration::ZERO);
+    let (expires, signature) = query(&url);
+    // Expiry has a one-second resolution.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(!signer.verify("/files/1", expires, &signature));
+}
@@ -0,0 +1,157 @@
+//! `ApiError` as `application/problem+json`: status, stable code, request id and field errors,
+//! including the rejections of the shared extractors and the conversions from library errors.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::get,
+    Router,
+};
+use jsonwebtoken::errors::ErrorKind;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use shared::error::PROBLEM_JSON;
+use shared::extract::{Path, Query};
+use shared::request_id::{self, X_REQUEST_ID};
+use shared::{ApiError, AuthConfig, AuthUser, FieldError, PageParams, ServiceError};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+struct Filter {
+    archived: bool,
+}
+
+fn app() -> Router {
+    shared::auth::init_verifier(&AuthConfig {
+        jwt_secret: Some("error-test-secret".to_string()),
+        ..AuthConfig::default()
+    });
+    Router::new()
+        .route(
+            "/missing",
+            get(|| async { Err::<(), _>(ApiError::NotFound("course not found")) }),
+        )
+        .route(
+            "/invalid",
+            get(|| async {
+                Err::<(), _>(ApiError::Validation(vec![
+                    FieldError::new("title", "must not be empty"),
+                    FieldError::new("max_attempts", "must be at least 1"),
+                ]))
+            }),
+        )
+        .route(
+            "/me",
+            get(|AuthUser(claims): AuthUser| async move { claims.sub }),
+        )
+        .route("/page", get(|_: PageParams| async { "page" }))
+        .route(
+            "/courses/:id",
+            get(|Path(id): Path<Uuid>| async move { id.to_string() }),
+        )
+        .route(
+            "/courses",
+            get(|Query(filter): Query<Filter>| async move { filter.archived.to_string() }),
+        )
+        .layer(axum::middleware::from_fn(request_id::propagate))
+}
+
+async fn problem(uri: &str) -> (StatusCode, Value) {
+    let res = app()
+        .oneshot(
+            Request::get(uri)
+                .header(X_REQUEST_ID, "req-7")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn errors_render_as_problem_details() {
+    let (status, body) = problem("/missing").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        body,
+        json!({
+            "type": "about:blank",
+            "title": "Not Found",
+            "status": 404,
+            "detail": "course not found",
+            "code": "not_found",
+            "request_id": "req-7",
+        })
+    );
+
+    let (status, body) = problem("/invalid").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(
+        body["errors"],
+        json!([
+            {"field": "title", "message": "must not be empty"},
+            {"field": "max_attempts", "message": "must be at least 1"},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn extractor_rejections_are_problem_details() {
+    let (status, body) = problem("/me").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+    assert_eq!(body["detail"], "Missing Authorization header");
+    assert_eq!(body["request_id"], "req-7");
+
+    let (status, body) = problem("/page?limit=500").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+    assert_eq!(body["detail"], "limit must be between 1 and 100");
+
+    let (status, body) = problem("/courses/not-a-uuid").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+    assert_eq!(body["detail"], "invalid path parameter");
+
+    let (status, body) = problem("/courses?archived=maybe").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["detail"], "invalid query parameters");
+}
+
+#[test]
+fn library_errors_convert() {
+    let err = ApiError::from(sqlx::Error::PoolTimedOut);
+    assert_eq!(
+        (err.status(), err.code()),
+        (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable")
+    );
+    let err = ApiError::from(sqlx::Error::RowNotFound);
+    assert_eq!(
+        (err.status(), err.to_string()),
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "database error".to_string()
+        )
+    );
+
+    let err = ApiError::from(jsonwebtoken::errors::Error::from(
+        ErrorKind::ExpiredSignature,
+    ));
+    assert_eq!(
+        (err.status(), err.to_string()),
+        (StatusCode::UNAUTHORIZED, "token expired".to_string())
+    );
+
+    let err = ApiError::from(ServiceError::Timeout("admin-service".to_string()));
+    assert_eq!(err.status(), StatusCode::GATEWAY_TIMEOUT);
+    let err = ApiError::from(ServiceError::CircuitOpen("admin-service".to_string()));
+    assert_eq!(err.status(), StatusCode::BAD_GATEWAY);
+}
@@ -0,0 +1,164 @@
+//! `JwksCache` against an in-process JWKS endpoint whose document can be swapped: which keys
+//! are kept, refetching for unknown kids, rotation and the refetch throttle.
+
+use axum::{http::StatusCode, routing::get, Json, Router};
+use jsonwebtoken::Algorithm;
+use serde_json::{json, Value};
+use shared::jwks::{JwksCache, JwksError, JwksSource};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const JWKS: &str = include_str!("fixtures/oidc-jwks.json");
+const INTERVAL: Duration = Duration::from_millis(200);
+
+/// The fixture's RSA public key published under `kid`.
+fn rsa_key(kid: &str) -> Value {
+    let mut key = serde_json::from_str::<Value>(JWKS).unwrap()["keys"][0].clone();
+    key["kid"] = json!(kid);
+    key
+}
+
+fn jwks(keys: &[Value]) -> Value {
+    json!({ "keys": keys })
+}
+
+/// JWKS endpoint serving whatever `Endpoint::set` last put there.
+struct Endpoint {
+    answer: Arc<Mutex<(StatusCode, Value)>>,
+    hits: Arc<AtomicUsize>,
+    url: String,
+}
+
+impl Endpoint {
+    async fn start(document: Value) -> Self {
+        let answer = Arc::new(Mutex::new((StatusCode::OK, document)));
+        let hits = Arc::new(AtomicUsize::new(0));
+        let (served, counter) = (answer.clone(), hits.clone());
+        let app = Router::new().route(
+            "/certs",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let (status, document) = served.lock().unwrap().clone();
+                async move { (status, Json(document)) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/certs", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { answer, hits, url }
+    }
+
+    fn set(&self, status: StatusCode, document: Value) {
+        *self.answer.lock().unwrap() = (status, document);
+    }
+
+    fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+
+    fn cache(&self) -> JwksCache {
+        JwksCache::new(JwksSource::Url(self.url.clone())).with_min_refresh_interval(INTERVAL)
+    }
+}
+
+#[tokio::test]
+async fn only_usable_signing_keys_are_kept() {
+    let mut encryption = rsa_key("enc");
+    encryption["use"] = json!("enc");
+    let mut no_kid = rsa_key("");
+    no_kid.as_object_mut().unwrap().remove("kid");
+    let endpoint = Endpoint::start(jwks(&[
+        rsa_key("sig"),
+        encryption,
+        no_kid,
+        json!({"kty": "oct", "kid": "hmac", "k": "c2VjcmV0"}),
+        json!({"kty": "unknown", "kid": "odd"}),
+    ]))
+    .await;
+    let cache = endpoint.cache();
+
+    assert_eq!(cache.key("sig").await.unwrap().algorithm, Algorithm::RS256);
+    for kid in ["enc", "hmac", "odd"] {
+        assert!(matches!(
+            cache.key(kid).await,
+            Err(JwksError::UnknownKid(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn unusable_documents_are_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let empty = dir.path().join("empty.json");
+    std::fs::write(
+        &empty,
+        jwks(&[json!({"kty": "oct", "kid": "k", "k": "c2VjcmV0"})]).to_string(),
+    )
+    .unwrap();
+    let cache = JwksCache::new(JwksSource::File(empty));
+    assert!(matches!(cache.key("k").await, Err(JwksError::Empty)));
+
+    let garbage = dir.path().join("garbage.json");
+    std::fs::write(&garbage, "<html>").unwrap();
+    let cache = JwksCache::new(JwksSource::File(garbage));
+    assert!(matches!(cache.key("k").await, Err(JwksError::Parse(_))));
+}
+
+#[tokio::test]
+async fn unknown_kids_refetch_at_most_once_per_interval() {
+    let endpoint = Endpoint::start(jwks(&[rsa_key("old")])).await;
+    let cache = endpoint.cache();
+    cache.key("old").await.unwrap();
+    cache.key("old").await.unwrap();
+    assert_eq!(endpoint.hits(), 1);
+
+    // A key published after the last fetch is found once the throttle allows a refetch.
+    endpoint.set(StatusCode::OK, jwks(&[rsa_key("old"), rsa_key("new")]));
+    for _ in 0..3 {
+        assert!(matches!(
+            cache.key("new").await,
+            Err(JwksError::UnknownKid(_))
+        ));
+    }
+    assert_eq!(endpoint.hits(), 1);
+    tokio::time::sleep(INTERVAL + Duration::from_millis(50)).await;
+    cache.key("new").await.unwrap();
+    assert_eq!(endpoint.hits(), 2);
+}
+
+#[tokio::test]
+async fn rotated_out_keys_stop_verifying() {
+    let endpoint = Endpoint::start(jwks(&[rsa_key("old")])).await;
+    let cache = endpoint.cache();
+    cache.key("old").await.unwrap();
+
+    endpoint.set(StatusCode::OK, jwks(&[rsa_key("new")]));
+    tokio::time::sleep(INTERVAL + Duration::from_millis(50)).await;
+    cache.key("new").await.unwrap();
+    assert!(matches!(
+        cache.key("old").await,
+        Err(JwksError::UnknownKid(_))
+    ));
+    assert_eq!(endpoint.hits(), 2);
+}
+
+#[tokio::test]
+async fn failed_refetches_keep_the_cached_keys() {
+    let endpoint = Endpoint::start(jwks(&[rsa_key("current")])).await;
+    let cache = endpoint.cache();
+    cache.key("current").await.unwrap();
+
+    endpoint.set(StatusCode::INTERNAL_SERVER_ERROR, json!({}));
+    tokio::time::sleep(INTERVAL + Duration::from_millis(50)).await;
+    assert!(matches!(
+        cache.key("other").await,
+        Err(JwksError::UnknownKid(_))
+    ));
+    assert_eq!(endpoint.hits(), 2);
+    cache.key("current").await.unwrap();
+
+    // Without cached keys the failure is reported.
+    let cold = endpoint.cache();
+    assert!(matches!(cold.key("current").await, Err(JwksError::Http(_))));
+}
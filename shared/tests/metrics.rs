@@ -0,0 +1,110 @@
+//! `/metrics` output after requests to a router wrapped by `with_metrics`, and after
+//! `ServiceClient` calls to an upstream that fails once.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use shared::metrics::with_metrics;
+use shared::{RetryPolicy, ServiceClient};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    // Never connects; the pool gauges only read its counters.
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(7)
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap();
+    let router = Router::new()
+        .route("/items/:id", get(|| async { "item" }))
+        .route(
+            "/broken",
+            get(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "broken") }),
+        );
+    with_metrics(router, pool)
+}
+
+async fn get_body(app: &Router, uri: &str) -> (StatusCode, String) {
+    let res = app
+        .clone()
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_route_template_and_status() {
+    let app = app();
+    get_body(&app, "/items/1").await;
+    get_body(&app, "/items/2").await;
+    get_body(&app, "/broken").await;
+    get_body(&app, "/nowhere").await;
+
+    let (status, metrics) = get_body(&app, "/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    for line in [
+        r#"http_requests_total{method="GET",route="/items/:id",status="200"} 2"#,
+        r#"http_requests_total{method="GET",route="/broken",status="500"} 1"#,
+        r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#,
+        r#"http_request_duration_seconds_count{method="GET",route="/items/:id",status="200"} 2"#,
+        r#"db_pool_connections{state="in_use"} 0"#,
+        "db_pool_max_connections 7",
+    ] {
+        assert!(metrics.contains(line), "missing {line} in\n{metrics}");
+    }
+}
+
+#[tokio::test]
+async fn service_client_calls_are_counted_per_upstream() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = Router::new().route(
+        "/thing",
+        get(move || {
+            let first = hits.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                if first {
+                    StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    StatusCode::OK
+                }
+            }
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, upstream).await.unwrap() });
+
+    let client = ServiceClient::new([("metrics-upstream".to_string(), base)]).with_retry_policy(
+        RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        },
+    );
+    client
+        .get("metrics-upstream", "/thing")
+        .send()
+        .await
+        .unwrap();
+
+    let (_, metrics) = get_body(&app(), "/metrics").await;
+    for line in [
+        r#"upstream_attempts_total{status="503",upstream="metrics-upstream"} 1"#,
+        r#"upstream_attempts_total{status="200",upstream="metrics-upstream"} 1"#,
+        r#"upstream_retries_total{upstream="metrics-upstream"} 1"#,
+        r#"upstream_request_duration_seconds_count{status="200",upstream="metrics-upstream"} 1"#,
+        r#"upstream_circuit_state{state="closed",upstream="metrics-upstream"} 1"#,
+        r#"upstream_circuit_state{state="open",upstream="metrics-upstream"} 0"#,
+    ] {
+        assert!(metrics.contains(line), "missing {line} in\n{metrics}");
+    }
+}
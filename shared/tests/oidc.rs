@@ -0,0 +1,269 @@
+//! `JwtVerifier` against an in-process OIDC issuer serving discovery and JWKS documents,
+//! so no live identity provider is needed. The fixture key is test-only. HS256 tokens cover the
+//! audience, `nbf`/`exp` leeway and the detail each rejection answers with.
+
+use axum::{http::StatusCode, routing::get, Json, Router};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::{json, Value};
+use shared::auth::{JwtVerifier, VerifyError};
+use shared::{ApiError, AuthConfig, Role};
+
+const PRIVATE_KEY: &str = include_str!("fixtures/oidc-key.pem");
+const JWKS: &str = include_str!("fixtures/oidc-jwks.json");
+const KID: &str = "test-key";
+
+/// Start a mock issuer at `http://127.0.0.1:<port>/realms/edu`. Its discovery document
+/// advertises `advertised_issuer`, or its own URL when `None`.
+async fn start_issuer(advertised_issuer: Option<&str>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/realms/edu", listener.local_addr().unwrap());
+    let discovery = json!({
+        "issuer": advertised_issuer.unwrap_or(&base),
+        "jwks_uri": format!("{}/protocol/openid-connect/certs", base),
+    });
+    let jwks: Value = serde_json::from_str(JWKS).unwrap();
+    let app = Router::new()
+        .route(
+            "/realms/edu/.well-known/openid-configuration",
+            get(move || async move { Json(discovery) }),
+        )
+        .route(
+            "/realms/edu/protocol/openid-connect/certs",
+            get(move || async move { Json(jwks) }),
+        );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base
+}
+
+fn sign(claims: Value) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KID.to_string());
+    let key = EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap();
+    encode(&header, &claims, &key).unwrap()
+}
+
+fn claims(issuer: &str, extra: Value) -> Value {
+    let mut claims = json!({
+        "sub": "user-1",
+        "iss": issuer,
+        "exp": chrono::Utc::now().timestamp() + 300,
+    });
+    claims
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    claims
+}
+
+fn verifier(issuer: &str, role_claims: &[&str]) -> JwtVerifier {
+    JwtVerifier::new(&AuthConfig {
+        oidc_issuer: Some(issuer.to_string()),
+        role_claims: role_claims.iter().map(|s| s.to_string()).collect(),
+        leeway_secs: 0,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn maps_realm_roles_via_discovery() {
+    let issuer = start_issuer(None).await;
+    let token = sign(claims(
+        &issuer,
+        json!({ "realm_access": { "roles": ["offline_access", "teacher"] } }),
+    ));
+    let claims = verifier(&issuer, &["realm_access.roles"])
+        .verify(&token)
+        .await
+        .unwrap();
+    assert_eq!(claims.sub, "user-1");
+    assert_eq!(claims.roles, vec![Role::Teacher]);
+}
+
+#[tokio::test]
+async fn combines_roles_from_all_paths() {
+    let issuer = start_issuer(None).await;
+    let token = sign(claims(
+        &issuer,
+        json!({
+            "realm_access": { "roles": ["teacher"] },
+            "resource_access": { "edu-web": { "roles": ["admin", "teacher"] } },
+        }),
+    ));
+    let claims = verifier(
+        &issuer,
+        &["realm_access.roles", "resource_access.edu-web.roles"],
+    )
+    .verify(&token)
+    .await
+    .unwrap();
+    assert_eq!(claims.roles, vec![Role::Teacher, Role::Admin]);
+}
+
+#[tokio::test]
+async fn rejects_token_without_known_role() {
+    let issuer = start_issuer(None).await;
+    let token = sign(claims(
+        &issuer,
+        json!({ "realm_access": { "roles": ["offline_access"] } }),
+    ));
+    let err = verifier(&issuer, &["realm_access.roles"])
+        .verify(&token)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, VerifyError::MissingRole), "{err}");
+}
+
+#[tokio::test]
+async fn rejects_token_from_other_issuer() {
+    let issuer = start_issuer(None).await;
+    let token = sign(claims(
+        "http://elsewhere/realms/edu",
+        json!({ "role": "student" }),
+    ));
+    let err = verifier(&issuer, &["role"])
+        .verify(&token)
+        .await
+        .unwrap_err();
+    assert_eq!(err.reason(), "invalid token issuer");
+}
+
+#[tokio::test]
+async fn rejects_discovery_for_another_issuer() {
+    let issuer = start_issuer(Some("http://impostor/realms/edu")).await;
+    let token = sign(claims(&issuer, json!({ "role": "student" })));
+    let err = verifier(&issuer, &["role"])
+        .verify(&token)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+const SECRET: &str = "oidc-test-secret";
+const ISSUER: &str = "https://auth.example.test";
+
+fn sign_hs256(claims: Value, secret: &str) -> String {
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap()
+}
+
+/// Valid claims for `hs256_verifier`, with `extra` merged in and any `null` value removed.
+fn hs256_claims(extra: Value) -> Value {
+    let mut claims = json!({
+        "sub": "user-1",
+        "role": "student",
+        "iss": ISSUER,
+        "aud": "edu-api",
+        "exp": chrono::Utc::now().timestamp() + 300,
+    });
+    let object = claims.as_object_mut().unwrap();
+    for (key, value) in extra.as_object().unwrap() {
+        if value.is_null() {
+            object.remove(key);
+        } else {
+            object.insert(key.clone(), value.clone());
+        }
+    }
+    claims
+}
+
+fn hs256_verifier(leeway_secs: u64) -> JwtVerifier {
+    JwtVerifier::new(&AuthConfig {
+        jwt_secret: Some(SECRET.to_string()),
+        issuer: Some(ISSUER.to_string()),
+        audience: vec!["edu-api".to_string(), "edu-web".to_string()],
+        leeway_secs,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+/// Status and `detail` the rejection of `claims` answers with.
+async fn rejection(verifier: &JwtVerifier, claims: Value) -> (StatusCode, String) {
+    let err = ApiError::from(
+        verifier
+            .verify(&sign_hs256(claims, SECRET))
+            .await
+            .unwrap_err(),
+    );
+    (err.status(), err.to_string())
+}
+
+#[tokio::test]
+async fn audience_must_match_when_configured() {
+    let verifier = hs256_verifier(0);
+    for aud in [json!("edu-api"), json!(["other", "edu-web"])] {
+        let token = sign_hs256(hs256_claims(json!({ "aud": aud })), SECRET);
+        assert!(verifier.verify(&token).await.is_ok(), "{aud}");
+    }
+
+    assert_eq!(
+        rejection(&verifier, hs256_claims(json!({ "aud": "other-api" }))).await,
+        (
+            StatusCode::UNAUTHORIZED,
+            "invalid token audience".to_string()
+        )
+    );
+    assert_eq!(
+        rejection(&verifier, hs256_claims(json!({ "aud": null }))).await,
+        (
+            StatusCode::UNAUTHORIZED,
+            "token has no audience".to_string()
+        )
+    );
+}
+
+#[tokio::test]
+async fn not_before_and_expiry_allow_the_leeway() {
+    let now = chrono::Utc::now().timestamp();
+    let verifier = hs256_verifier(30);
+    for extra in [json!({ "nbf": now + 10 }), json!({ "exp": now - 10 })] {
+        let token = sign_hs256(hs256_claims(extra.clone()), SECRET);
+        assert!(verifier.verify(&token).await.is_ok(), "{extra}");
+    }
+
+    assert_eq!(
+        rejection(&verifier, hs256_claims(json!({ "nbf": now + 120 }))).await,
+        (StatusCode::UNAUTHORIZED, "token not yet valid".to_string())
+    );
+    assert_eq!(
+        rejection(&verifier, hs256_claims(json!({ "exp": now - 120 }))).await,
+        (StatusCode::UNAUTHORIZED, "token expired".to_string())
+    );
+    // Without leeway, a token valid in ten seconds is not valid yet.
+    assert_eq!(
+        rejection(&hs256_verifier(0), hs256_claims(json!({ "nbf": now + 10 }))).await,
+        (StatusCode::UNAUTHORIZED, "token not yet valid".to_string())
+    );
+}
+
+#[tokio::test]
+async fn each_rejection_has_its_own_detail() {
+    let verifier = hs256_verifier(0);
+    let cases = [
+        (
+            json!({ "iss": "https://elsewhere.test" }),
+            "invalid token issuer",
+        ),
+        (json!({ "iss": null }), "token has no issuer"),
+        (json!({ "exp": null }), "token is missing a required claim"),
+        (json!({ "role": "janitor" }), "token has no recognised role"),
+    ];
+    for (extra, detail) in cases {
+        assert_eq!(
+            rejection(&verifier, hs256_claims(extra.clone())).await,
+            (StatusCode::UNAUTHORIZED, detail.to_string()),
+            "{extra}"
+        );
+    }
+
+    let token = sign_hs256(hs256_claims(json!({})), "other-secret");
+    let err = ApiError::from(verifier.verify(&token).await.unwrap_err());
+    assert_eq!(err.to_string(), "invalid token signature");
+    let err = ApiError::from(verifier.verify("not-a-token").await.unwrap_err());
+    assert_eq!(err.to_string(), "malformed token");
+}
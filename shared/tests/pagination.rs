@@ -0,0 +1,149 @@
+//! Cursor encoding, the keyset SQL fragments, the `PageParams` extractor and `LIKE` escaping.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{TimeZone, Utc};
+use serde_json::Value;
+use shared::pagination::{escape_like, Cursor, SortOrder};
+use shared::{Page, PageParams};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn cursor() -> Cursor {
+    Cursor::new(
+        Utc.with_ymd_and_hms(2024, 4, 1, 9, 30, 15).unwrap()
+            + chrono::Duration::microseconds(123_456),
+        Uuid::new_v4(),
+    )
+}
+
+#[test]
+fn cursors_round_trip() {
+    let c = cursor();
+    let encoded = c.encode();
+    assert!(encoded
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_'));
+    assert_eq!(Cursor::decode(&encoded), Some(c));
+}
+
+#[test]
+fn malformed_cursors_are_rejected() {
+    let id = Uuid::new_v4();
+    for raw in [
+        "no separator".to_string(),
+        format!("yesterday|{}", id),
+        "2024-04-01T09:30:15Z|not-a-uuid".to_string(),
+        String::new(),
+    ] {
+        assert_eq!(Cursor::decode(&URL_SAFE_NO_PAD.encode(raw)), None);
+    }
+    assert_eq!(Cursor::decode("***"), None);
+    assert_eq!(Cursor::decode(&URL_SAFE_NO_PAD.encode([0xff, 0xfe])), None);
+}
+
+#[test]
+fn keyset_fragments_follow_the_sort_order() {
+    let mut page = PageParams {
+        limit: 10,
+        cursor: None,
+        sort: SortOrder::Asc,
+    };
+    assert_eq!(
+        page.keyset_predicate(3),
+        "($3::TIMESTAMPTZ IS NULL OR (created_at, id) > ($3, $4::UUID))"
+    );
+    assert_eq!(page.order_by(), "ORDER BY created_at ASC, id ASC");
+    assert_eq!(page.fetch_limit(), 11);
+
+    page.sort = SortOrder::Desc;
+    assert_eq!(
+        page.keyset_predicate(1),
+        "($1::TIMESTAMPTZ IS NULL OR (created_at, id) < ($1, $2::UUID))"
+    );
+    assert_eq!(page.order_by(), "ORDER BY created_at DESC, id DESC");
+}
+
+#[test]
+fn pages_carry_a_cursor_only_when_rows_remain() {
+    let page = PageParams {
+        limit: 2,
+        cursor: None,
+        sort: SortOrder::Asc,
+    };
+    let rows: Vec<Cursor> = (0..3).map(|_| cursor()).collect();
+    let full = Page::from_rows(rows.clone(), &page, |c| *c);
+    assert_eq!(full.items.len(), 2);
+    assert_eq!(full.next_cursor, Some(rows[1].encode()));
+
+    let last = Page::from_rows(rows[..2].to_vec(), &page, |c| *c);
+    assert_eq!(last.next_cursor, None);
+}
+
+async fn params(query: &str) -> (StatusCode, String) {
+    let app = Router::new().route(
+        "/",
+        get(|page: PageParams| async move {
+            format!("{} {:?} {:?}", page.limit, page.sort, page.cursor_id())
+        }),
+    );
+    let res = app
+        .oneshot(
+            Request::get(format!("/?{}", query))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = if status.is_success() {
+        String::from_utf8(body.to_vec()).unwrap()
+    } else {
+        let problem: Value = serde_json::from_slice(&body).unwrap();
+        problem["detail"].as_str().unwrap().to_string()
+    };
+    (status, body)
+}
+
+#[tokio::test]
+async fn page_params_are_parsed_and_checked() {
+    let c = cursor();
+    assert_eq!(
+        params(&format!("limit=5&sort=-created_at&cursor={}", c.encode())).await,
+        (StatusCode::OK, format!("5 Desc Some({})", c.id))
+    );
+    assert_eq!(
+        params("").await,
+        (StatusCode::OK, "20 Asc None".to_string())
+    );
+
+    for (query, detail) in [
+        ("limit=0", "limit must be between 1 and 100"),
+        ("limit=101", "limit must be between 1 and 100"),
+        ("limit=ten", "invalid pagination parameters"),
+        ("cursor=bogus", "invalid cursor"),
+        ("sort=name", "invalid sort"),
+    ] {
+        assert_eq!(
+            params(query).await,
+            (StatusCode::BAD_REQUEST, detail.to_string()),
+            "{query}"
+        );
+    }
+}
+
+#[test]
+fn like_wildcards_are_escaped() {
+    assert_eq!(escape_like("intro"), "intro");
+    assert_eq!(escape_like("100%"), r"100\%");
+    assert_eq!(escape_like("a_b"), r"a\_b");
+    assert_eq!(escape_like(r"c:\dir"), r"c:\\dir");
+}
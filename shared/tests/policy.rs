@@ -0,0 +1,147 @@
+//! Role-to-permission policies: the built-in grants, policy files and their errors, and the 403
+//! `Authorized` answers with.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::{json, Value};
+use shared::policy::{init_policy, perm, Permission, Policy, PolicyError};
+use shared::{AuthConfig, Authorized, Role};
+use tower::ServiceExt;
+
+const SECRET: &str = "policy-test-secret";
+
+fn policy_file(body: &str) -> (tempfile::TempDir, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("roles.json");
+    std::fs::write(&path, body).unwrap();
+    (dir, path.to_str().unwrap().to_string())
+}
+
+#[test]
+fn permission_names_round_trip() {
+    for permission in Permission::ALL {
+        assert_eq!(permission.as_str().parse::<Permission>(), Ok(*permission));
+    }
+    assert!("course:delete".parse::<Permission>().is_err());
+}
+
+#[test]
+fn default_policy_grants_by_role() {
+    let policy = Policy::default();
+    assert!(policy.allows(&[Role::Admin], Permission::UserManage));
+    assert!(!policy.allows(&[Role::Admin], Permission::SubmissionGrade));
+    assert!(policy.allows(&[Role::Teacher], Permission::SubmissionGrade));
+    assert!(policy.allows(&[Role::Student], Permission::AssignmentRead));
+    assert!(!policy.allows(&[Role::Student], Permission::AssignmentWrite));
+    // Several roles hold the union of their grants.
+    assert!(policy.allows(&[Role::Student, Role::Teacher], Permission::AssignmentWrite));
+    assert!(!policy.allows(&[], Permission::CourseRead));
+}
+
+#[test]
+fn policy_files_replace_the_defaults() {
+    let (_dir, path) = policy_file(r#"{"teacher": ["assignment:write", "grade:read"]}"#);
+    let policy = Policy::from_file(&path).unwrap();
+    assert!(policy.allows(&[Role::Teacher], Permission::AssignmentWrite));
+    assert!(!policy.allows(&[Role::Teacher], Permission::SubmissionGrade));
+    // Roles left out grant nothing.
+    assert!(!policy.allows(&[Role::Admin], Permission::CourseRead));
+
+    let policy = Policy::from_config(&AuthConfig {
+        permissions_file: Some(path),
+        ..AuthConfig::default()
+    })
+    .unwrap();
+    assert!(policy.allows(&[Role::Teacher], Permission::GradeRead));
+}
+
+#[test]
+fn broken_policy_files_are_errors() {
+    for body in [
+        r#"{"teacher": ["assignment:delete"]}"#,
+        r#"{"janitor": ["course:read"]}"#,
+        r#"{"teacher": "assignment:write"}"#,
+        "not json",
+    ] {
+        let (_dir, path) = policy_file(body);
+        assert!(
+            matches!(Policy::from_file(&path), Err(PolicyError::Parse(..))),
+            "{body}"
+        );
+    }
+    let (_dir, path) = policy_file(r#"{"teacher": ["assignment:delete"]}"#);
+    let err = Policy::from_file(&path).unwrap_err().to_string();
+    assert!(
+        err.contains(r#"unknown permission "assignment:delete""#),
+        "{err}"
+    );
+
+    assert!(matches!(
+        Policy::from_file("/nonexistent/roles.json"),
+        Err(PolicyError::Io(..))
+    ));
+}
+
+fn token(roles: &[&str]) -> String {
+    let claims = json!({
+        "sub": "user-1",
+        "roles": roles,
+        "exp": chrono::Utc::now().timestamp() + 300,
+    });
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(SECRET.as_bytes()),
+    )
+    .unwrap()
+}
+
+async fn write_course(token: &str) -> (StatusCode, Value) {
+    shared::auth::init_verifier(&AuthConfig {
+        jwt_secret: Some(SECRET.to_string()),
+        ..AuthConfig::default()
+    });
+    init_policy(&AuthConfig::default()).unwrap();
+    let app = Router::new().route(
+        "/courses",
+        get(|claims: Authorized<perm::CourseWrite>| async move { claims.sub.clone() }),
+    );
+    let res = app
+        .oneshot(
+            Request::get("/courses")
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&body)
+            .unwrap_or(Value::String(String::from_utf8(body.to_vec()).unwrap())),
+    )
+}
+
+#[tokio::test]
+async fn authorized_requires_the_permission() {
+    let (status, body) = write_course(&token(&["admin"])).await;
+    assert_eq!((status, body), (StatusCode::OK, json!("user-1")));
+
+    let (status, body) = write_course(&token(&["teacher", "student"])).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "forbidden");
+    assert_eq!(body["detail"], "course:write permission required");
+
+    // Authentication failures stay 401.
+    let (status, _) = write_course("not-a-token").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
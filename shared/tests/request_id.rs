@@ -0,0 +1,177 @@
+//! `x-request-id` assignment and forwarding, and the request span fields as they appear in JSON
+//! logs: the id, the caller's `sub` and `role`, and no credentials.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, Request, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Extension, Router,
+};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::{json, Value};
+use shared::request_id::{self, X_REQUEST_ID};
+use shared::tracing_init::json_layer;
+use shared::{AuthConfig, AuthUser, ServiceClient};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tower::{ServiceBuilder, ServiceExt};
+use tower_http::trace::TraceLayer;
+use tracing_subscriber::layer::SubscriberExt;
+
+const SECRET: &str = "request-id-test-secret";
+
+/// Log lines written by the JSON layer.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Logs {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+fn token() -> String {
+    let claims = json!({
+        "sub": "teacher-1",
+        "role": "teacher",
+        "exp": chrono::Utc::now().timestamp() + 300,
+    });
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(SECRET.as_bytes()),
+    )
+    .unwrap()
+}
+
+async fn whoami(AuthUser(claims): AuthUser) -> impl IntoResponse {
+    tracing::info!("handled");
+    let id = request_id::current().unwrap();
+    (
+        StatusCode::OK,
+        format!("{} {}", claims.sub, id.to_str().unwrap()),
+    )
+}
+
+/// Calls the `echo` upstream, which answers with the `x-request-id` it received.
+async fn forward(Extension(client): Extension<Arc<ServiceClient>>) -> String {
+    client
+        .get("echo", "/id")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn app() -> Router {
+    shared::auth::init_verifier(&AuthConfig {
+        jwt_secret: Some(SECRET.to_string()),
+        role_claims: vec!["role".to_string()],
+        ..AuthConfig::default()
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let echo = Router::new().route(
+        "/id",
+        get(|headers: HeaderMap| async move {
+            headers
+                .get(X_REQUEST_ID)
+                .map(|id| id.to_str().unwrap().to_string())
+                .unwrap_or_default()
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, echo).await.unwrap() });
+    let client = Arc::new(ServiceClient::new([("echo".to_string(), base)]));
+
+    Router::new()
+        .route("/whoami", get(whoami))
+        .route("/forward", get(forward))
+        .layer(Extension(client))
+        .layer(
+            ServiceBuilder::new()
+                .layer(axum::middleware::from_fn(request_id::propagate))
+                .layer(TraceLayer::new_for_http().make_span_with(shared::trace_context::make_span)),
+        )
+}
+
+async fn call(app: &Router, uri: &str, request_id: Option<&str>) -> (String, String) {
+    let mut req = Request::get(uri).header("authorization", format!("Bearer {}", token()));
+    if let Some(id) = request_id {
+        req = req.header(X_REQUEST_ID, id);
+    }
+    let res = app
+        .clone()
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let id = res.headers()[X_REQUEST_ID].to_str().unwrap().to_string();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (id, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn request_ids_are_kept_or_generated_and_forwarded() {
+    let app = app().await;
+
+    let (id, body) = call(&app, "/whoami", Some("req-42")).await;
+    assert_eq!(id, "req-42");
+    assert_eq!(body, "teacher-1 req-42");
+
+    // Missing or unusable ids are replaced by a UUID.
+    for sent in [None, Some("has spaces"), Some(&*"x".repeat(200))] {
+        let (id, body) = call(&app, "/whoami", sent).await;
+        assert!(uuid::Uuid::parse_str(&id).is_ok(), "{id}");
+        assert_eq!(body, format!("teacher-1 {}", id));
+    }
+
+    let (id, body) = call(&app, "/forward", Some("req-43")).await;
+    assert_eq!((id.as_str(), body.as_str()), ("req-43", "req-43"));
+}
+
+#[tokio::test]
+async fn json_logs_carry_the_request_span_without_credentials() {
+    let logs = Logs::default();
+    let writer = logs.clone();
+    let _subscriber = tracing::subscriber::set_default(
+        tracing_subscriber::registry().with(json_layer(move || writer.clone())),
+    );
+    let app = app().await;
+
+    call(&app, "/whoami?signature=s3cr3t-sig&page=2", Some("req-44")).await;
+
+    let lines = logs.lines();
+    let handled: Value = lines
+        .iter()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .find(|line| line["message"] == "handled")
+        .expect("handler log line");
+    let span = &handled["span"];
+    assert_eq!(span["request_id"], "req-44");
+    assert_eq!(span["sub"], "teacher-1");
+    assert_eq!(span["role"], "teacher");
+    assert_eq!(span["uri"], "/whoami?signature=[redacted]&page=2");
+    let all = lines.join("\n");
+    assert!(!all.contains("s3cr3t-sig"));
+    // Every JWT segment starts with the base64 of `{"`.
+    assert!(!all.contains("eyJ"));
+}
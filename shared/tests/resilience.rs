@@ -0,0 +1,156 @@
+//! The `resilience` layers one at a time, over mock services that answer from a script and count
+//! the requests they receive.
+
+use axum::http::{self, StatusCode};
+use reqwest::{Method, Request, Response};
+use shared::circuit::{CircuitBreaker, CircuitConfig, CircuitStatus};
+use shared::resilience::{CircuitBreakerLayer, RetryLayer};
+use shared::{RetryPolicy, ServiceError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tower::limit::ConcurrencyLimitLayer;
+use tower::load_shed::error::Overloaded;
+use tower::{service_fn, BoxError, Layer, Service, ServiceBuilder, ServiceExt};
+
+fn request(method: Method) -> Request {
+    Request::new(method, "http://upstream/thing".parse().unwrap())
+}
+
+fn response(status: StatusCode) -> Response {
+    http::Response::builder()
+        .status(status)
+        .body("{}")
+        .unwrap()
+        .into()
+}
+
+/// A service answering the n-th request with `statuses[n]` (the last one repeats).
+fn scripted(
+    statuses: Vec<StatusCode>,
+) -> (
+    impl Service<Request, Response = Response, Error = BoxError, Future: Send> + Clone + Send,
+    Arc<AtomicUsize>,
+) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let service = service_fn(move |_req: Request| {
+        let n = counter.fetch_add(1, Ordering::SeqCst);
+        let status = statuses[n.min(statuses.len() - 1)];
+        async move { Ok::<_, BoxError>(response(status)) }
+    });
+    (service, hits)
+}
+
+fn fast() -> RetryPolicy {
+    RetryPolicy {
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(5),
+        ..RetryPolicy::default()
+    }
+}
+
+#[tokio::test]
+async fn retry_layer_repeats_listed_statuses_of_idempotent_requests() {
+    let (inner, hits) = scripted(vec![StatusCode::SERVICE_UNAVAILABLE]);
+    let res = RetryLayer::new(fast())
+        .layer(inner)
+        .oneshot(request(Method::GET))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+
+    let (inner, hits) = scripted(vec![StatusCode::SERVICE_UNAVAILABLE, StatusCode::OK]);
+    let res = RetryLayer::new(fast())
+        .layer(inner)
+        .oneshot(request(Method::POST))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn retry_layer_retries_attempts_the_timeout_layer_cut_off() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let slow_once = service_fn(move |_req: Request| {
+        let n = counter.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if n == 0 {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+            Ok::<_, BoxError>(response(StatusCode::OK))
+        }
+    });
+    let res = ServiceBuilder::new()
+        .layer(RetryLayer::new(fast()))
+        .timeout(Duration::from_millis(50))
+        .service(slow_once)
+        .oneshot(request(Method::GET))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn circuit_breaker_layer_rejects_without_calling_the_service_once_open() {
+    let (events, _) = broadcast::channel(8);
+    let config = CircuitConfig {
+        min_calls: 3,
+        ..CircuitConfig::default()
+    };
+    let breaker = Arc::new(CircuitBreaker::new("upstream", config, events));
+    let (inner, hits) = scripted(vec![StatusCode::NOT_FOUND, StatusCode::BAD_GATEWAY]);
+    let mut service = CircuitBreakerLayer::new(breaker.clone()).layer(inner);
+
+    // 404, 502, 502: the 404 is a success, and the rate is judged from the third call on.
+    for _ in 0..3 {
+        service.ready().await.unwrap();
+        service.call(request(Method::GET)).await.unwrap();
+    }
+    assert_eq!(breaker.status(), CircuitStatus::Open);
+
+    let err = service.ready().await.unwrap().call(request(Method::GET));
+    let err = err.await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ServiceError>(),
+        Some(ServiceError::CircuitOpen(name)) if name == "upstream"
+    ));
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn calls_over_the_concurrency_limit_are_shed() {
+    let release = Arc::new(Notify::new());
+    let gate = release.clone();
+    let blocking = service_fn(move |_req: Request| {
+        let gate = gate.clone();
+        async move {
+            gate.notified().await;
+            Ok::<_, BoxError>(response(StatusCode::OK))
+        }
+    });
+    let service = ServiceBuilder::new()
+        .load_shed()
+        .layer(ConcurrencyLimitLayer::new(1))
+        .service(blocking);
+
+    let in_flight = tokio::spawn(service.clone().oneshot(request(Method::GET)));
+    tokio::task::yield_now().await;
+    let err = service
+        .clone()
+        .oneshot(request(Method::GET))
+        .await
+        .unwrap_err();
+    assert!(err.is::<Overloaded>());
+
+    release.notify_one();
+    assert_eq!(in_flight.await.unwrap().unwrap().status(), StatusCode::OK);
+    // The slot is free again.
+    release.notify_one();
+    service.oneshot(request(Method::GET)).await.unwrap();
+}
@@ -0,0 +1,205 @@
+//! Service tokens: minting and verifying, the claims a token must carry, and `ServiceCaller`
+//! refusing anything but a service token addressed to this service from an expected caller.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::{json, Value};
+use shared::service_auth::{install_service_tokens, ServiceAuthError, ServiceTokens};
+use shared::{ApiError, Claims, Role, ServiceCaller};
+use tower::ServiceExt;
+
+const SECRET: &str = "service-auth-test-secret";
+
+fn sign(claims: Value, secret: &str) -> String {
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap()
+}
+
+/// Claims of a valid token from teacher-service to admin-service, minus `remove`.
+fn service_claims(remove: &str) -> Value {
+    let now = chrono::Utc::now().timestamp();
+    let mut claims = json!({
+        "typ": "service",
+        "iss": "teacher-service",
+        "aud": "admin-service",
+        "iat": now,
+        "exp": now + 60,
+    });
+    claims.as_object_mut().unwrap().remove(remove);
+    claims
+}
+
+fn user_token() -> String {
+    sign(
+        json!({
+            "sub": "admin-1",
+            "roles": ["admin"],
+            "exp": chrono::Utc::now().timestamp() + 300,
+        }),
+        SECRET,
+    )
+}
+
+#[test]
+fn delegated_users_arrive_with_the_caller() {
+    let teacher = ServiceTokens::new("teacher-service", SECRET);
+    let admin = ServiceTokens::new("admin-service", SECRET);
+    let user = Claims {
+        sub: "teacher-1".to_string(),
+        roles: vec![Role::Teacher, Role::Student],
+        exp: i64::MAX,
+        iss: Some("auth-service".to_string()),
+        jti: Some("jti-1".to_string()),
+    };
+
+    let caller = admin
+        .verify(&teacher.mint("admin-service", Some(&user)).unwrap())
+        .unwrap();
+    assert_eq!(caller.service, "teacher-service");
+    let delegated = caller.user.unwrap();
+    assert_eq!(delegated.sub, "teacher-1");
+    assert_eq!(delegated.roles, vec![Role::Teacher, Role::Student]);
+    // The delegated user expires with the service token, not the original one.
+    assert!(delegated.exp <= chrono::Utc::now().timestamp() + 60);
+
+    let caller = admin
+        .verify(&teacher.mint("admin-service", None).unwrap())
+        .unwrap();
+    assert!(caller.user.is_none());
+}
+
+#[test]
+fn only_valid_service_tokens_for_this_service_verify() {
+    let admin = ServiceTokens::new("admin-service", SECRET);
+    let teacher = ServiceTokens::new("teacher-service", SECRET);
+    assert!(admin.verify(&sign(service_claims(""), SECRET)).is_ok());
+
+    // Addressed to another service.
+    let token = teacher.mint("student-service", None).unwrap();
+    assert!(matches!(
+        admin.verify(&token),
+        Err(ServiceAuthError::Invalid(_))
+    ));
+
+    // Signed with another secret.
+    let token = ServiceTokens::new("teacher-service", "other-secret")
+        .mint("admin-service", None)
+        .unwrap();
+    assert!(matches!(
+        admin.verify(&token),
+        Err(ServiceAuthError::Invalid(_))
+    ));
+
+    // Expired beyond the default leeway.
+    let mut claims = service_claims("");
+    claims["exp"] = json!(chrono::Utc::now().timestamp() - 300);
+    assert!(matches!(
+        admin.verify(&sign(claims, SECRET)),
+        Err(ServiceAuthError::Invalid(_))
+    ));
+
+    for claim in ["typ", "iss", "aud", "exp"] {
+        assert!(
+            matches!(
+                admin.verify(&sign(service_claims(claim), SECRET)),
+                Err(ServiceAuthError::Invalid(_))
+            ),
+            "without {claim}"
+        );
+    }
+    let mut claims = service_claims("");
+    claims["typ"] = json!("user");
+    assert!(matches!(
+        admin.verify(&sign(claims, SECRET)),
+        Err(ServiceAuthError::NotServiceToken)
+    ));
+}
+
+async fn call(path: &str, token: Option<&str>) -> (StatusCode, Value) {
+    install_service_tokens(ServiceTokens::new("admin-service", SECRET));
+    let app = Router::new()
+        .route(
+            "/internal/ping",
+            get(|caller: ServiceCaller| async move {
+                Json(json!({
+                    "service": caller.service,
+                    "user": caller.user.map(|u| u.sub),
+                }))
+            }),
+        )
+        .route(
+            "/internal/teachers-only",
+            get(|caller: ServiceCaller| async move {
+                caller.allow(&["teacher-service"])?;
+                Ok::<_, ApiError>(Json(json!({ "service": caller.service })))
+            }),
+        );
+    let mut request = Request::get(path);
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    let res = app
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn internal_routes_refuse_user_tokens() {
+    let user = Claims {
+        sub: "student-1".to_string(),
+        roles: vec![Role::Student],
+        exp: i64::MAX,
+        iss: None,
+        jti: None,
+    };
+    let token = ServiceTokens::new("student-service", SECRET)
+        .mint("admin-service", Some(&user))
+        .unwrap();
+    let (status, body) = call("/internal/ping", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({"service": "student-service", "user": "student-1"})
+    );
+
+    let (status, body) = call("/internal/ping", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["detail"], "missing service token");
+
+    // Even a user token signed with the service secret is not a service token.
+    let (status, body) = call("/internal/ping", Some(&user_token())).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["detail"], "invalid service token");
+}
+
+#[tokio::test]
+async fn internal_routes_admit_only_their_expected_callers() {
+    let teacher = ServiceTokens::new("teacher-service", SECRET)
+        .mint("admin-service", None)
+        .unwrap();
+    let (status, body) = call("/internal/teachers-only", Some(&teacher)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["service"], "teacher-service");
+
+    let student = ServiceTokens::new("student-service", SECRET)
+        .mint("admin-service", None)
+        .unwrap();
+    let (status, body) = call("/internal/teachers-only", Some(&student)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["detail"], "service not allowed");
+}
@@ -0,0 +1,334 @@
+//! `ServiceClient` retries and circuit breaking against an in-process upstream that answers with
+//! a scripted status sequence and counts the requests it receives. Probe interleavings that are
+//! hard to time over HTTP drive a `CircuitBreaker` directly.
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    routing::any,
+    Router,
+};
+use shared::circuit::{CircuitBreaker, CircuitConfig, CircuitStatus};
+use shared::{RetryPolicy, ServiceClient, ServiceError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Start an upstream answering the n-th request with `statuses[n]` (the last one repeats),
+/// adding `Retry-After: <retry_after>` to non-2xx answers when given.
+async fn start_upstream(
+    statuses: Vec<StatusCode>,
+    retry_after: Option<&'static str>,
+) -> (ServiceClient, Arc<AtomicUsize>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = Router::new().route(
+        "/thing",
+        any(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            let status = statuses[n.min(statuses.len() - 1)];
+            async move {
+                let mut headers = HeaderMap::new();
+                if let (false, Some(wait)) = (status.is_success(), retry_after) {
+                    headers.insert("retry-after", wait.parse().unwrap());
+                }
+                (status, headers, "{}")
+            }
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = ServiceClient::new([("upstream".to_string(), base)]).with_retry_policy(fast());
+    (client, hits)
+}
+
+fn fast() -> RetryPolicy {
+    RetryPolicy {
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(5),
+        ..RetryPolicy::default()
+    }
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    let (client, hits) = start_upstream(vec![StatusCode::NOT_FOUND], None).await;
+    let err = client.get("upstream", "/thing").send().await.unwrap_err();
+    assert!(err.is_not_found());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn listed_statuses_are_retried_up_to_the_limit() {
+    let (client, hits) = start_upstream(vec![StatusCode::SERVICE_UNAVAILABLE], None).await;
+    let err = client.get("upstream", "/thing").send().await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+
+    let statuses = vec![StatusCode::BAD_GATEWAY, StatusCode::OK];
+    let (client, hits) = start_upstream(statuses, None).await;
+    client.get("upstream", "/thing").send().await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn non_idempotent_requests_are_retried_only_with_an_idempotency_key() {
+    let statuses = vec![StatusCode::SERVICE_UNAVAILABLE, StatusCode::OK];
+    let (client, hits) = start_upstream(statuses.clone(), None).await;
+    client.post("upstream", "/thing").send().await.unwrap_err();
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    let (client, hits) = start_upstream(statuses, None).await;
+    client
+        .post("upstream", "/thing")
+        .idempotency_key("key-1")
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn retry_after_is_honoured_within_the_deadline() {
+    let statuses = vec![StatusCode::TOO_MANY_REQUESTS, StatusCode::OK];
+    let (client, hits) = start_upstream(statuses, Some("1")).await;
+    let started = Instant::now();
+    client.get("upstream", "/thing").send().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // Waiting 30s would overrun a 2s deadline, so the 503 is returned at once.
+    let (client, hits) = start_upstream(vec![StatusCode::SERVICE_UNAVAILABLE], Some("30")).await;
+    let policy = RetryPolicy {
+        deadline: Duration::from_secs(2),
+        ..fast()
+    };
+    let started = Instant::now();
+    let err = client
+        .get("upstream", "/thing")
+        .retry_policy(policy)
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn huge_waits_and_deadlines_do_not_overflow() {
+    let (client, hits) = start_upstream(
+        vec![StatusCode::SERVICE_UNAVAILABLE],
+        Some("18446744073709551615"),
+    )
+    .await;
+    let err = client.get("upstream", "/thing").send().await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    let statuses = vec![StatusCode::SERVICE_UNAVAILABLE, StatusCode::OK];
+    let (client, hits) = start_upstream(statuses, None).await;
+    let policy = RetryPolicy {
+        deadline: Duration::MAX,
+        ..fast()
+    };
+    client
+        .get("upstream", "/thing")
+        .retry_policy(policy)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn requests_fail_before_sending_when_they_cannot_be_built() {
+    let (client, hits) = start_upstream(vec![StatusCode::OK], None).await;
+    let err = client.get("elsewhere", "/thing").send().await.unwrap_err();
+    assert!(matches!(err, ServiceError::UnknownUpstream(name) if name == "elsewhere"));
+
+    let err = client
+        .get("upstream", "/thing")
+        .bearer("token\nInjected: header")
+        .err()
+        .unwrap();
+    assert!(matches!(err, ServiceError::InvalidHeader(name) if name == "authorization"));
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn unexpected_json_is_a_decode_error() {
+    let (client, hits) = start_upstream(vec![StatusCode::OK], None).await;
+    let err = client
+        .get("upstream", "/thing")
+        .send_json::<Vec<String>>()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ServiceError::Decode { upstream, .. } if upstream == "upstream"));
+    // A readable answer is not retried.
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+fn tripping_after(min_calls: u32) -> CircuitConfig {
+    CircuitConfig {
+        failure_rate: 0.5,
+        min_calls,
+        window: Duration::from_secs(10),
+        open_duration: Duration::from_millis(200),
+        half_open_probes: 1,
+    }
+}
+
+#[tokio::test]
+async fn circuit_opens_on_failure_rate_and_closes_after_a_probe() {
+    let statuses = vec![
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::OK,
+    ];
+    let (client, hits) = start_upstream(statuses, None).await;
+    let client = client
+        .with_retry_policy(RetryPolicy::none())
+        .with_circuit_config("upstream", tripping_after(2));
+    let mut events = client.circuit_events();
+
+    for _ in 0..2 {
+        client.get("upstream", "/thing").send().await.unwrap_err();
+    }
+    assert_eq!(client.circuit_status("upstream"), Some(CircuitStatus::Open));
+    let err = client.get("upstream", "/thing").send().await.unwrap_err();
+    assert!(matches!(err, ServiceError::CircuitOpen(_)));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    client.get("upstream", "/thing").send().await.unwrap();
+    assert_eq!(
+        client.circuit_status("upstream"),
+        Some(CircuitStatus::Closed)
+    );
+
+    let transitions: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+        .map(|e| (e.from, e.to))
+        .collect();
+    assert_eq!(
+        transitions,
+        [
+            (CircuitStatus::Closed, CircuitStatus::Open),
+            (CircuitStatus::Open, CircuitStatus::HalfOpen),
+            (CircuitStatus::HalfOpen, CircuitStatus::Closed),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn client_errors_never_trip_the_circuit() {
+    let (client, _) = start_upstream(vec![StatusCode::NOT_FOUND], None).await;
+    let client = client.with_circuit_config("upstream", tripping_after(2));
+    for _ in 0..5 {
+        assert!(client
+            .get("upstream", "/thing")
+            .send()
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+    assert_eq!(
+        client.circuit_status("upstream"),
+        Some(CircuitStatus::Closed)
+    );
+}
+
+#[tokio::test]
+async fn a_failed_probe_reopens_the_circuit() {
+    let (client, hits) = start_upstream(vec![StatusCode::BAD_GATEWAY], None).await;
+    let client = client
+        .with_retry_policy(RetryPolicy::none())
+        .with_circuit_config("upstream", tripping_after(2));
+    for _ in 0..2 {
+        client.get("upstream", "/thing").send().await.unwrap_err();
+    }
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    let err = client.get("upstream", "/thing").send().await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::BAD_GATEWAY));
+    assert_eq!(client.circuit_status("upstream"), Some(CircuitStatus::Open));
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn half_open_admits_one_probe_at_a_time() {
+    // Fails twice, then answers slowly enough for a second call to arrive during the probe.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = Router::new().route(
+        "/thing",
+        any(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n < 2 {
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                StatusCode::OK
+            }
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = ServiceClient::new([("upstream".to_string(), base)])
+        .with_retry_policy(RetryPolicy::none())
+        .with_circuit_config("upstream", tripping_after(2));
+    for _ in 0..2 {
+        client.get("upstream", "/thing").send().await.unwrap_err();
+    }
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    let probe = client.get("upstream", "/thing").send();
+    let during_probe = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.get("upstream", "/thing").send().await
+    };
+    let (probe, during_probe) = tokio::join!(probe, during_probe);
+    probe.unwrap();
+    assert!(matches!(during_probe, Err(ServiceError::CircuitOpen(_))));
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        client.circuit_status("upstream"),
+        Some(CircuitStatus::Closed)
+    );
+}
+
+#[test]
+fn probes_from_an_earlier_half_open_episode_are_ignored() {
+    let (events, _) = broadcast::channel(16);
+    let breaker = CircuitBreaker::new(
+        "upstream",
+        CircuitConfig {
+            min_calls: 1,
+            open_duration: Duration::ZERO,
+            half_open_probes: 2,
+            ..tripping_after(1)
+        },
+        events,
+    );
+    breaker.try_acquire().unwrap().failure();
+    assert_eq!(breaker.status(), CircuitStatus::Open);
+
+    // First half-open episode: one probe hangs while the other fails and reopens the circuit.
+    let slow = breaker.try_acquire().unwrap();
+    breaker.try_acquire().unwrap().failure();
+    assert_eq!(breaker.status(), CircuitStatus::Open);
+
+    // Second episode: the slow probe finishing must neither free a slot nor count as a success.
+    let current = breaker.try_acquire().unwrap();
+    slow.success();
+    assert_eq!(breaker.status(), CircuitStatus::HalfOpen);
+    let second = breaker.try_acquire().unwrap();
+    assert!(breaker.try_acquire().is_none());
+    second.success();
+    assert_eq!(breaker.status(), CircuitStatus::HalfOpen);
+    current.success();
+    assert_eq!(breaker.status(), CircuitStatus::Closed);
+}
@@ -0,0 +1,163 @@
+//! HTTPS with certificates generated per test: mutual TLS between `server_config` and
+//! `client`, required versus optional client certificates, and `watch` picking up replaced
+//! certificate files.
+
+use axum::{routing::get, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair,
+};
+use shared::config::{ClientAuth, TlsConfig};
+use shared::tls::{client, server_config, watch};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+struct Ca {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new(name: &str) -> Self {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+        let key = KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+        Self { cert, key }
+    }
+
+    /// Write the CA certificate and a leaf certificate and key for `127.0.0.1` under `dir`.
+    fn issue(&self, dir: &Path, name: &str, purpose: ExtendedKeyUsagePurpose) -> TlsConfig {
+        let mut params = CertificateParams::new(vec!["127.0.0.1".to_string()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, name);
+        params.extended_key_usages = vec![purpose];
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+        let path = |file: &str| {
+            dir.join(format!("{name}-{file}"))
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        let tls = TlsConfig {
+            cert_file: path("cert.pem"),
+            key_file: path("key.pem"),
+            ca_file: Some(path("ca.pem")),
+            client_auth: ClientAuth::Required,
+            reload_secs: 0,
+        };
+        std::fs::write(&tls.cert_file, cert.pem()).unwrap();
+        std::fs::write(&tls.key_file, key.serialize_pem()).unwrap();
+        std::fs::write(tls.ca_file.as_ref().unwrap(), self.cert.pem()).unwrap();
+        tls
+    }
+}
+
+/// Serve "ok" over HTTPS with `config`; returns the base URL.
+fn serve(config: RustlsConfig) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("https://{}", listener.local_addr().unwrap());
+    let app = Router::new().route("/", get(|| async { "ok" }));
+    tokio::spawn(async move {
+        axum_server::from_tcp_rustls(listener, config)
+            .serve(app.into_make_service())
+            .await
+            .unwrap()
+    });
+    base
+}
+
+async fn get_ok(client: &reqwest::Client, base: &str) -> bool {
+    match client.get(base).send().await {
+        Ok(res) => res.text().await.unwrap() == "ok",
+        Err(_) => false,
+    }
+}
+
+/// Client trusting `ca_file` only, without a certificate of its own.
+fn anonymous_client(ca_file: &str) -> reqwest::Client {
+    let ca = std::fs::read(ca_file).unwrap();
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .tls_built_in_root_certs(false)
+        .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap())
+        .pool_max_idle_per_host(0)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn services_authenticate_each_other_with_certificates() {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = Ca::new("test CA");
+    let server_tls = ca.issue(dir.path(), "server", ExtendedKeyUsagePurpose::ServerAuth);
+    let client_tls = ca.issue(dir.path(), "client", ExtendedKeyUsagePurpose::ClientAuth);
+    let base = serve(RustlsConfig::from_config(Arc::new(
+        server_config(&server_tls).unwrap(),
+    )));
+
+    assert!(get_ok(&client(&client_tls).unwrap(), &base).await);
+    // Required client certificates: no certificate, no handshake.
+    let anonymous = anonymous_client(client_tls.ca_file.as_ref().unwrap());
+    assert!(!get_ok(&anonymous, &base).await);
+
+    // A certificate from another CA is refused too.
+    let other =
+        Ca::new("other CA").issue(dir.path(), "stranger", ExtendedKeyUsagePurpose::ClientAuth);
+    let stranger = client(&TlsConfig {
+        ca_file: client_tls.ca_file.clone(),
+        ..other
+    })
+    .unwrap();
+    assert!(!get_ok(&stranger, &base).await);
+}
+
+#[tokio::test]
+async fn optional_client_auth_admits_anonymous_clients() {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = Ca::new("test CA");
+    let server_tls = TlsConfig {
+        client_auth: ClientAuth::Optional,
+        ..ca.issue(dir.path(), "server", ExtendedKeyUsagePurpose::ServerAuth)
+    };
+    let client_tls = ca.issue(dir.path(), "client", ExtendedKeyUsagePurpose::ClientAuth);
+    let base = serve(RustlsConfig::from_config(Arc::new(
+        server_config(&server_tls).unwrap(),
+    )));
+
+    assert!(get_ok(&client(&client_tls).unwrap(), &base).await);
+    let anonymous = anonymous_client(client_tls.ca_file.as_ref().unwrap());
+    assert!(get_ok(&anonymous, &base).await);
+}
+
+#[tokio::test]
+async fn replaced_certificates_are_reloaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let old_ca = Ca::new("old CA");
+    let server_tls = TlsConfig {
+        ca_file: None,
+        reload_secs: 1,
+        ..old_ca.issue(dir.path(), "server", ExtendedKeyUsagePurpose::ServerAuth)
+    };
+    let rustls = RustlsConfig::from_config(Arc::new(server_config(&server_tls).unwrap()));
+    let reloadable = rustls.clone();
+    watch(&server_tls, "server", move |tls| {
+        reloadable.reload_from_config(Arc::new(server_config(tls)?));
+        Ok(())
+    });
+    let base = serve(rustls);
+
+    // Clients trusting only the new CA reject the server until its certificate is replaced.
+    let new_ca = Ca::new("new CA");
+    let staged = new_ca.issue(dir.path(), "staged", ExtendedKeyUsagePurpose::ServerAuth);
+    let trusting_new = anonymous_client(staged.ca_file.as_ref().unwrap());
+    assert!(!get_ok(&trusting_new, &base).await);
+
+    std::fs::copy(&staged.cert_file, &server_tls.cert_file).unwrap();
+    std::fs::copy(&staged.key_file, &server_tls.key_file).unwrap();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert!(get_ok(&trusting_new, &base).await);
+}
@@ -0,0 +1,78 @@
+//! Trace context propagation: a `ServiceClient` call and the request span of an in-process
+//! upstream end up in one trace, recorded by an in-memory exporter.
+
+use axum::{http::HeaderMap, routing::get, Router};
+use opentelemetry::trace::{SpanKind, TraceId};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SpanData};
+use shared::tracing_init::{otel_layer, tracer_provider_builder};
+use shared::ServiceClient;
+use std::time::Duration;
+use tower_http::trace::TraceLayer;
+use tracing::Instrument;
+use tracing_subscriber::layer::SubscriberExt;
+
+fn span<'a>(spans: &'a [SpanData], name: &str) -> Option<&'a SpanData> {
+    spans.iter().find(|s| s.name == name)
+}
+
+#[tokio::test]
+async fn upstream_request_span_continues_the_callers_trace() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = tracer_provider_builder("test")
+        .with_simple_exporter(exporter.clone())
+        .build();
+    // The current-thread runtime runs the upstream on this thread, under the same subscriber.
+    let _subscriber = tracing::subscriber::set_default(
+        tracing_subscriber::registry().with(otel_layer(&provider)),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let app = Router::new()
+        .route(
+            "/things/:id",
+            get(|headers: HeaderMap| async move {
+                headers["traceparent"].to_str().unwrap().to_string()
+            }),
+        )
+        .layer(TraceLayer::new_for_http().make_span_with(shared::trace_context::make_span));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = ServiceClient::new([("upstream".to_string(), base)]);
+    let traceparent = client
+        .get("upstream", "/things/1")
+        .send()
+        .instrument(tracing::info_span!("caller"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // The server span ends once its response body is done, just after the client has read it.
+    let mut spans = Vec::new();
+    for _ in 0..50 {
+        spans = exporter.get_finished_spans().unwrap();
+        if span(&spans, "GET /things/:id").is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let caller = span(&spans, "caller").expect("caller span");
+    let client = span(&spans, "GET upstream").expect("client span");
+    let server = span(&spans, "GET /things/:id").expect("server span");
+
+    let trace_id = caller.span_context.trace_id();
+    assert_ne!(trace_id, TraceId::INVALID);
+    assert_eq!(client.span_context.trace_id(), trace_id);
+    assert_eq!(server.span_context.trace_id(), trace_id);
+    assert_eq!(client.parent_span_id, caller.span_context.span_id());
+    assert_eq!(server.parent_span_id, client.span_context.span_id());
+    assert_eq!(client.span_kind, SpanKind::Client);
+    assert_eq!(server.span_kind, SpanKind::Server);
+    assert!(server.parent_span_is_remote);
+    assert_eq!(
+        traceparent,
+        format!("00-{}-{}-01", trace_id, client.span_context.span_id())
+    );
+}
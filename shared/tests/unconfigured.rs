@@ -0,0 +1,35 @@
+//! `AuthUser` in a process where `init_verifier` was never called: a generic 500 that does not
+//! reveal which settings are missing. Kept in its own binary because the verifier is global.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use serde_json::Value;
+use shared::AuthUser;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn missing_verifier_is_a_generic_server_error() {
+    let app = Router::new().route(
+        "/me",
+        get(|AuthUser(claims): AuthUser| async move { claims.sub }),
+    );
+    let res = app
+        .oneshot(
+            Request::get("/me")
+                .header("Authorization", "Bearer some-token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["detail"], "authentication is not configured");
+}
@@ -0,0 +1,141 @@
+//! `ValidJson`: declared rules on the body struct, trimming, nested field paths, schema rules
+//! relating several fields, and 422 problem details listing every invalid field.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::post,
+    Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use shared::error::PROBLEM_JSON;
+use shared::validation::{schema_error, trim};
+use shared::ValidJson;
+use tower::ServiceExt;
+use validator::{Validate, ValidationError};
+
+#[derive(Deserialize, Validate)]
+struct Item {
+    #[validate(length(min = 1))]
+    label: String,
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "attempts_within_title"))]
+struct CreateBody {
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(min = 1, max = 10))]
+    title: String,
+    #[validate(range(min = 1, max = 5))]
+    attempts: i32,
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    content: Option<String>,
+    #[serde(default)]
+    #[validate(nested)]
+    items: Vec<Item>,
+}
+
+/// Arbitrary rule relating two fields: no more attempts than the title has characters.
+fn attempts_within_title(body: &CreateBody) -> Result<(), ValidationError> {
+    if body.attempts as usize > body.title.len() {
+        return Err(schema_error("attempts", "must not exceed the title length"));
+    }
+    Ok(())
+}
+
+fn app() -> Router {
+    Router::new().route(
+        "/",
+        post(|ValidJson(body): ValidJson<CreateBody>| async move {
+            format!("{}:{}:{}", body.title, body.attempts, body.items.len())
+        }),
+    )
+}
+
+async fn send(body: &str) -> (StatusCode, String) {
+    let res = app()
+        .oneshot(
+            Request::post("/")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    if !status.is_success() {
+        assert_eq!(res.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+    }
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn valid_bodies_are_trimmed_and_accepted() {
+    let (status, body) =
+        send(r#"{"title": "  Essay  ", "attempts": 3, "items": [{"label": "a"}]}"#).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "Essay:3:1"));
+}
+
+#[tokio::test]
+async fn every_invalid_field_is_listed() {
+    let long = "x".repeat(21);
+    let (status, body) = send(
+        &json!({
+            "title": "   ",
+            "attempts": 0,
+            "content": long,
+            "items": [{"label": "ok"}, {"label": ""}],
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(
+        body["errors"],
+        json!([
+            {"field": "attempts", "message": "must be between 1 and 5"},
+            {"field": "content", "message": "length must be at most 20"},
+            {"field": "items[1].label", "message": "length must be at least 1"},
+            {"field": "title", "message": "length must be between 1 and 10"},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn schema_rules_report_their_field() {
+    let (status, body) = send(r#"{"title": "Ess", "attempts": 4}"#).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        body["errors"],
+        json!([{"field": "attempts", "message": "must not exceed the title length"}])
+    );
+
+    // Schema rules only run once every field is valid on its own.
+    let (_, body) = send(r#"{"title": "", "attempts": 4}"#).await;
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        body["errors"],
+        json!([{"field": "title", "message": "length must be between 1 and 10"}])
+    );
+}
+
+#[tokio::test]
+async fn malformed_bodies_are_rejected_before_validation() {
+    let (status, body) = send(r#"{"title": "Essay""#).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["code"], "bad_request");
+
+    let (status, body) = send(r#"{"title": "Essay"}"#).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["code"], "unprocessable_entity");
+}
@@ -0,0 +1,384 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequest, Multipart, Request, State},
+    http::header,
+    response::Response,
+    Json,
+};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use shared::blob::BlobError;
+use shared::extract::{Path, Query};
+use shared::{ApiError, AuthUser, ValidJson};
+use std::time::Duration;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::routes::{load_readable_submission, CreateSubmissionBody};
+use crate::AppState;
+
+/// Request body cap for submission uploads; per-assignment limits are checked per file.
+pub const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+
+/// Lifetime of the signed links returned by the attachment list.
+const DOWNLOAD_URL_TTL: Duration = Duration::from_secs(15 * 60);
+
+pub struct UploadedFile {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+/// Body of `POST /api/student/assignments/:assignment_id/submissions`: either the JSON
+/// `CreateSubmissionBody`, or `multipart/form-data` with an optional `content` text field and
+/// any number of `file` parts.
+pub struct SubmissionUpload {
+    pub content: Option<String>,
+    pub files: Vec<UploadedFile>,
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for SubmissionUpload
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_multipart = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("multipart/form-data"));
+        if !is_multipart {
+            let ValidJson(body) =
+                ValidJson::<CreateSubmissionBody>::from_request(req, state).await?;
+            return Ok(Self {
+                content: body.content,
+                files: Vec::new(),
+            });
+        }
+
+        let mut multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(|e| ApiError::for_status(e.status(), "invalid multipart body"))?;
+        let mut upload = Self {
+            content: None,
+            files: Vec::new(),
+        };
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| ApiError::for_status(e.status(), "invalid multipart body"))?
+        {
+            match field.name() {
+                Some("content") => {
+                    let text = field
+                        .text()
+                        .await
+                        .map_err(|e| ApiError::for_status(e.status(), "invalid multipart body"))?;
+                    upload.content = Some(text);
+                }
+                Some("file") => {
+                    // Browsers may send a full client path; keep only the last component.
+                    let filename = field
+                        .file_name()
+                        .and_then(|n| n.rsplit(['/', '\\']).next())
+                        .filter(|n| !n.is_empty())
+                        .ok_or(ApiError::BadRequest("file parts need a filename"))?
+                        .to_string();
+                    let content_type = field
+                        .content_type()
+                        .unwrap_or("application/octet-stream")
+                        .to_ascii_lowercase();
+                    let data = field
+                        .bytes()
+                        .await
+                        .map_err(|e| ApiError::for_status(e.status(), "invalid multipart body"))?;
+                    upload.files.push(UploadedFile {
+                        filename,
+                        content_type,
+                        data,
+                    });
+                }
+                _ => return Err(ApiError::BadRequest("unexpected multipart field")),
+            }
+        }
+        // The `content` field follows the same rules as the JSON body.
+        let body = CreateSubmissionBody {
+            content: upload.content.take(),
+        };
+        body.validate()?;
+        upload.content = body.content;
+        Ok(upload)
+    }
+}
+
+/// Enforce an assignment's attachment limits; `allowed` entries are `type/subtype` or `type/*`.
+pub fn check_limits(
+    files: &[UploadedFile],
+    max_bytes: Option<i64>,
+    allowed: Option<&[String]>,
+) -> Result<(), ApiError> {
+    for file in files {
+        if max_bytes.is_some_and(|max| file.data.len() as i64 > max) {
+            return Err(ApiError::PayloadTooLarge(
+                "attachment exceeds the assignment's size limit",
+            ));
+        }
+        let essence = file.content_type.split(';').next().unwrap_or("").trim();
+        let permitted = allowed.is_none_or(|types| {
+            types.iter().any(|t| match t.strip_suffix("/*") {
+                Some(prefix) => essence.split('/').next() == Some(prefix),
+                None => t.eq_ignore_ascii_case(essence),
+            })
+        });
+        if !permitted {
+            return Err(ApiError::UnsupportedMediaType(
+                "attachment type is not allowed for this assignment",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// An uploaded file in the blob store, not yet recorded against a submission version.
+pub(crate) struct StoredAttachment {
+    id: Uuid,
+    filename: String,
+    content_type: String,
+    size_bytes: i64,
+    sha256: String,
+    key: String,
+}
+
+/// Upload `files` to the blob store for a submission to `assignment_id`. Runs before the
+/// submission's transaction, so a slow store never holds a connection or the submission's row
+/// lock; if one upload fails the files already stored are removed.
+pub(crate) async fn store_attachments(
+    state: &AppState,
+    assignment_id: Uuid,
+    files: Vec<UploadedFile>,
+) -> Result<Vec<StoredAttachment>, ApiError> {
+    let mut stored = Vec::with_capacity(files.len());
+    for file in files {
+        let id = Uuid::new_v4();
+        // Keys are never reused, so a blob left behind by a failed cleanup is inert.
+        let key = format!("submissions/{}/{}", assignment_id, id);
+        let attachment = StoredAttachment {
+            id,
+            filename: file.filename,
+            content_type: file.content_type,
+            size_bytes: file.data.len() as i64,
+            sha256: format!("{:x}", Sha256::digest(&file.data)),
+            key,
+        };
+        if let Err(e) = state
+            .blob_store
+            .put(&attachment.key, file.data, &attachment.content_type)
+            .await
+        {
+            tracing::error!("store_attachments: {}", e);
+            discard_attachments(state, &stored).await;
+            return Err(ApiError::Internal("storage error"));
+        }
+        stored.push(attachment);
+    }
+    Ok(stored)
+}
+
+/// Remove uploaded files whose submission was not recorded; failures are only logged.
+pub(crate) async fn discard_attachments(state: &AppState, attachments: &[StoredAttachment]) {
+    for attachment in attachments {
+        if let Err(e) = state.blob_store.delete(&attachment.key).await {
+            tracing::warn!("discard_attachments: {}: {}", attachment.key, e);
+        }
+    }
+}
+
+/// Record uploaded files against a submission version.
+pub(crate) async fn record_attachments(
+    conn: &mut sqlx::PgConnection,
+    submission_id: Uuid,
+    version: i32,
+    attachments: &[StoredAttachment],
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), ApiError> {
+    for attachment in attachments {
+        sqlx::query(
+            r#"
+            INSERT INTO student.submission_attachments
+                (id, submission_id, version, filename, content_type, size_bytes, sha256,
+                 storage_key, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(attachment.id)
+        .bind(submission_id)
+        .bind(version)
+        .bind(&attachment.filename)
+        .bind(&attachment.content_type)
+        .bind(attachment.size_bytes)
+        .bind(&attachment.sha256)
+        .bind(&attachment.key)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub version: i32,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    /// Hex-encoded SHA-256 of the uploaded bytes.
+    pub sha256: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Signed link that works without a bearer token until `download_expires_at`.
+    pub download_url: String,
+    pub download_expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+type AttachmentRow = (
+    Uuid,
+    Uuid,
+    i32,
+    String,
+    String,
+    i64,
+    String,
+    chrono::DateTime<chrono::Utc>,
+);
+
+const ATTACHMENT_COLUMNS: &str =
+    "id, submission_id, version, filename, content_type, size_bytes, sha256, created_at";
+
+fn download_path(attachment_id: Uuid) -> String {
+    format!("/api/student/attachments/{}/download", attachment_id)
+}
+
+/// `version` defaults to the submission's latest version.
+#[derive(Deserialize)]
+pub struct ListAttachmentsParams {
+    pub version: Option<i32>,
+}
+
+pub async fn list_attachments(
+    State(state): State<AppState>,
+    AuthUser(auth): AuthUser,
+    Path(submission_id): Path<Uuid>,
+    Query(params): Query<ListAttachmentsParams>,
+) -> Result<Json<Vec<Attachment>>, ApiError> {
+    let submission = load_readable_submission(&state, &auth, submission_id).await?;
+    let version = params.version.unwrap_or(submission.version);
+    let rows = sqlx::query_as::<_, AttachmentRow>(&format!(
+        r#"
+        SELECT {} FROM student.submission_attachments
+        WHERE submission_id = $1 AND version = $2
+        ORDER BY created_at, id
+        "#,
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(submission_id)
+    .bind(version)
+    .fetch_all(&state.pool)
+    .await?;
+    let attachments = rows
+        .into_iter()
+        .map(
+            |(
+                id,
+                submission_id,
+                version,
+                filename,
+                content_type,
+                size_bytes,
+                sha256,
+                created_at,
+            )| {
+                let (download_url, download_expires_at) =
+                    state.url_signer.sign(&download_path(id), DOWNLOAD_URL_TTL);
+                Attachment {
+                    id,
+                    submission_id,
+                    version,
+                    filename,
+                    content_type,
+                    size_bytes,
+                    sha256,
+                    created_at,
+                    download_url,
+                    download_expires_at,
+                }
+            },
+        )
+        .collect();
+    Ok(Json(attachments))
+}
+
+#[derive(Deserialize)]
+pub struct DownloadParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+/// Serves an attachment to anyone holding a valid signed link; no bearer token is needed.
+pub async fn download_attachment(
+    State(state): State<AppState>,
+    Path(attachment_id): Path<Uuid>,
+    Query(params): Query<DownloadParams>,
+) -> Result<Response, ApiError> {
+    if !state.url_signer.verify(
+        &download_path(attachment_id),
+        params.expires,
+        &params.signature,
+    ) {
+        return Err(ApiError::Forbidden("invalid or expired download link"));
+    }
+    let row = sqlx::query_as::<_, (String, String, String)>(
+        "SELECT filename, content_type, storage_key FROM student.submission_attachments WHERE id = $1",
+    )
+    .bind(attachment_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    let (filename, content_type, key) = row.ok_or(ApiError::NotFound("attachment not found"))?;
+    let data = state.blob_store.get(&key).await.map_err(|e| match e {
+        BlobError::NotFound(_) => {
+            tracing::error!("download_attachment: blob {} is missing", key);
+            ApiError::NotFound("attachment not found")
+        }
+        e => {
+            tracing::error!("download_attachment: {}", e);
+            ApiError::Internal("storage error")
+        }
+    })?;
+    let safe_name: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", safe_name),
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .body(Body::from(data))
+        .map_err(|e| {
+            tracing::error!("download_attachment: {}", e);
+            ApiError::Internal("invalid attachment metadata")
+        })
+}
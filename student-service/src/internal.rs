@@ -0,0 +1,24 @@
+//! Submission lookups for teacher-service, which grades the version returned here after its
+//! own access check. Service token (`ServiceCaller`) only; the ingress does not route here.
+
+use axum::{extract::State, Json};
+use shared::extract::Path;
+use shared::{ApiError, ServiceCaller};
+use uuid::Uuid;
+
+use crate::routes::{fetch_submission, Submission};
+use crate::AppState;
+
+/// A submission's assignment, author and latest version, e.g. for teacher-service when grading.
+/// The caller checks that its user may see it.
+pub async fn get_submission(
+    State(state): State<AppState>,
+    caller: ServiceCaller,
+    Path(submission_id): Path<Uuid>,
+) -> Result<Json<Submission>, ApiError> {
+    caller.allow(&["teacher-service"])?;
+    let submission = fetch_submission(&state, submission_id).await?;
+    Ok(Json(
+        submission.ok_or(ApiError::NotFound("submission not found"))?,
+    ))
+}
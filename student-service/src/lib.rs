@@ -1,7 +1,13 @@
+mod attachments;
+mod internal;
 mod routes;
+mod versions;
 
-use axum::{routing::get, Router};
-use shared::ServiceClient;
+pub use attachments::{check_limits, UploadedFile};
+pub use routes::{LatePolicy, ReleasedGrade, SubmissionWindow};
+
+use axum::{extract::DefaultBodyLimit, routing::get, Router};
+use shared::{BlobStore, ServiceClient, UrlSigner};
 use sqlx::PgPool;
 use std::time::Duration;
 use tower::ServiceBuilder;
@@ -11,20 +17,75 @@ use tower_http::timeout::TimeoutLayer;
 pub struct AppState {
     pub pool: PgPool,
     pub http_client: std::sync::Arc<ServiceClient>,
+    pub blob_store: std::sync::Arc<dyn BlobStore>,
+    pub url_signer: UrlSigner,
 }
 
-pub fn app(pool: PgPool, http_client: std::sync::Arc<ServiceClient>) -> Router {
-    Router::new()
+pub fn app(
+    pool: PgPool,
+    http_client: std::sync::Arc<ServiceClient>,
+    blob_store: std::sync::Arc<dyn BlobStore>,
+    url_signer: UrlSigner,
+) -> Router {
+    let router = Router::new()
         .route("/health", get(routes::health))
         .route("/ready", get(routes::ready))
         .route(
             "/api/student/assignments/:assignment_id/submissions",
-            axum::routing::post(routes::create_submission),
+            get(routes::list_assignment_submissions)
+                .post(routes::create_submission)
+                .layer(DefaultBodyLimit::max(attachments::MAX_UPLOAD_BYTES)),
+        )
+        .route(
+            "/api/student/submissions/:submission_id",
+            get(routes::get_submission),
+        )
+        .route(
+            "/api/student/submissions/:submission_id/grade",
+            get(routes::get_submission_grade),
+        )
+        .route(
+            "/api/student/submissions/:submission_id/versions",
+            get(versions::list_versions),
+        )
+        .route(
+            "/api/student/submissions/:submission_id/versions/:version",
+            get(versions::get_version),
+        )
+        .route(
+            "/api/student/submissions/:submission_id/diff",
+            get(versions::diff_versions),
+        )
+        .route(
+            "/api/student/submissions/:submission_id/attachments",
+            get(attachments::list_attachments),
+        )
+        .route(
+            "/api/student/attachments/:attachment_id/download",
+            get(attachments::download_attachment),
         )
-        .with_state(AppState { pool, http_client })
+        .route(
+            "/api/student/me/submissions",
+            get(routes::list_my_submissions),
+        )
+        .route(
+            "/internal/submissions/:submission_id",
+            get(internal::get_submission),
+        )
+        .with_state(AppState {
+            pool: pool.clone(),
+            http_client,
+            blob_store,
+            url_signer,
+        })
         .layer(
             ServiceBuilder::new()
-                .layer(tower_http::trace::TraceLayer::new_for_http())
+                .layer(axum::middleware::from_fn(shared::request_id::propagate))
+                .layer(
+                    tower_http::trace::TraceLayer::new_for_http()
+                        .make_span_with(shared::trace_context::make_span),
+                )
                 .layer(TimeoutLayer::new(Duration::from_secs(30))),
-        )
+        );
+    shared::metrics::with_metrics(router, pool)
 }
@@ -1,30 +1,34 @@
 use shared::{init_tracing, Config};
 use student_service::app;
-use std::net::SocketAddr;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let config = Config::from_env().map_err(|e| format!("config: {}", e))?;
-    init_tracing();
+    let _tracing = init_tracing(&config).map_err(|e| format!("tracing: {}", e))?;
+    shared::auth::init_verifier(&config.auth);
+    shared::policy::init_policy(&config.auth)?;
+    shared::service_auth::init_service_tokens(&config)?;
     tracing::info!("starting {} on port {}", config.service_name, config.http_port);
 
     let pool = sqlx::postgres::PgPoolOptions::new()
         .connect(&config.database_url)
         .await?;
 
-    let admin_base = std::env::var("ADMIN_SERVICE_URL")
-        .unwrap_or_else(|_| "http://admin-service:8080".to_string());
-    let teacher_base = std::env::var("TEACHER_SERVICE_URL")
-        .unwrap_or_else(|_| "http://teacher-service:8080".to_string());
-    let client = std::sync::Arc::new(shared::ServiceClient::new(admin_base, teacher_base));
+    let client = shared::ServiceClient::from_config(&config).map_err(|e| format!("tls: {}", e))?;
+    let client = std::sync::Arc::new(client);
 
-    let app = app(pool, client);
+    let blob_store =
+        shared::blob::blob_store_from_env().map_err(|e| format!("blob store: {}", e))?;
+    // Download links get a key of their own, never the token signing key.
+    let url_secret =
+        std::env::var("DOWNLOAD_URL_SECRET").map_err(|_| "DOWNLOAD_URL_SECRET must be set")?;
+    if config.auth.jwt_secret.as_deref() == Some(url_secret.as_str()) {
+        return Err("DOWNLOAD_URL_SECRET must differ from JWT_SECRET".into());
+    }
+    let url_signer = shared::UrlSigner::new(url_secret);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.http_port));
-    axum::serve(
-        tokio::net::TcpListener::bind(addr).await?,
-        app.into_make_service(),
-    )
-    .await?;
+    let app = app(pool, client, blob_store, url_signer);
+
+    shared::tls::serve(app, &config).await?;
     Ok(())
 }
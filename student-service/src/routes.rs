@@ -1,26 +1,212 @@
-use axum::{
-    extract::{Path, State},
-    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
-    Json,
-};
+use axum::{extract::State, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
-use shared::{AuthUser, Role};
+use shared::access::NoMemberships;
+use shared::extract::{Path, Query};
+use shared::pagination::Cursor;
+use shared::{
+    perm, AdminMemberships, ApiError, AuthUser, Authorized, Claims, Page, PageParams, Permission,
+    Resource,
+};
 use uuid::Uuid;
+use validator::Validate;
 
+use crate::attachments::{
+    check_limits, discard_attachments, record_attachments, store_attachments, StoredAttachment,
+    SubmissionUpload,
+};
 use crate::AppState;
 
-#[derive(Deserialize)]
+/// Longest submission text, in characters.
+pub const MAX_CONTENT_CHARS: u64 = 100_000;
+
+#[derive(Deserialize, Validate)]
 pub struct CreateSubmissionBody {
+    #[validate(length(max = MAX_CONTENT_CHARS))]
     pub content: Option<String>,
 }
 
+/// Subset of teacher-service's assignment payload.
+#[derive(Deserialize)]
+struct AssignmentRef {
+    course_id: Uuid,
+    #[serde(flatten)]
+    window: SubmissionWindow,
+    max_attempts: Option<i32>,
+    max_attachment_bytes: Option<i64>,
+    allowed_mime_types: Option<Vec<String>>,
+}
+
+/// When an assignment accepts submissions, as published by teacher-service.
+#[derive(Deserialize)]
+pub struct SubmissionWindow {
+    pub opens_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub due_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub closes_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub late_policy: LatePolicy,
+}
+
+/// Mirrors teacher-service's late policy; only `Reject` changes what student-service does.
+#[derive(Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LatePolicy {
+    Reject,
+    AcceptFlag,
+    Penalty,
+}
+
+impl SubmissionWindow {
+    /// Whether a submission at `now` is late; an error if the window does not accept it.
+    pub fn check(&self, now: chrono::DateTime<chrono::Utc>) -> Result<bool, ApiError> {
+        if self.opens_at.is_some_and(|t| now < t) {
+            return Err(ApiError::Conflict("assignment is not open yet"));
+        }
+        if self.closes_at.is_some_and(|t| now > t) {
+            return Err(ApiError::Conflict("assignment is closed"));
+        }
+        let is_late = self.due_at.is_some_and(|t| now > t);
+        if is_late && self.late_policy == LatePolicy::Reject {
+            return Err(ApiError::Conflict("submission deadline has passed"));
+        }
+        Ok(is_late)
+    }
+}
+
+/// One logical submission per (assignment, student). `content`, `is_late` and `version` mirror
+/// the latest entry of its version history.
 #[derive(Serialize)]
 pub struct Submission {
     pub id: Uuid,
     pub assignment_id: Uuid,
     pub student_id: String,
     pub content: Option<String>,
+    pub is_late: bool,
+    pub version: i32,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A released grade as published by teacher-service.
+#[derive(Deserialize, Serialize)]
+pub struct ReleasedGrade {
+    pub submission_id: Uuid,
+    /// Submission version the grade was given for.
+    pub submission_version: i32,
+    pub score: f64,
+    pub max_points: f64,
+    pub feedback: Option<String>,
+    pub late_penalty_percent: Option<i16>,
+    pub final_score: f64,
+    pub released_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether a newer version was submitted after grading; set by `check_stale`.
+    #[serde(default)]
+    pub stale: bool,
+}
+
+impl ReleasedGrade {
+    /// Flag the grade stale when `latest_version` is newer than the graded one.
+    pub fn check_stale(mut self, latest_version: i32) -> Self {
+        self.stale = self.submission_version < latest_version;
+        self
+    }
+}
+
+/// Filters for `GET /api/student/me/submissions`; paging is handled by `PageParams`.
+#[derive(Deserialize)]
+pub struct ListSubmissionsFilter {
+    pub assignment_id: Option<Uuid>,
+}
+
+/// Filters for `GET /api/student/assignments/:assignment_id/submissions`.
+#[derive(Deserialize)]
+pub struct ListAssignmentSubmissionsFilter {
+    pub student_id: Option<String>,
+}
+
+type SubmissionRow = (
+    Uuid,
+    Uuid,
+    String,
+    Option<String>,
+    bool,
+    i32,
+    chrono::DateTime<chrono::Utc>,
+    chrono::DateTime<chrono::Utc>,
+);
+
+const SUBMISSION_COLUMNS: &str =
+    "id, assignment_id, student_id, content, is_late, version, created_at, updated_at";
+
+impl From<SubmissionRow> for Submission {
+    fn from(
+        (id, assignment_id, student_id, content, is_late, version, created_at, updated_at): SubmissionRow,
+    ) -> Self {
+        Self {
+            id,
+            assignment_id,
+            student_id,
+            content,
+            is_late,
+            version,
+            created_at,
+            updated_at,
+        }
+    }
+}
+
+/// Fetch an assignment from teacher-service (existence check and owning course), acting for
+/// `user`.
+async fn fetch_assignment(
+    state: &AppState,
+    user: &Claims,
+    assignment_id: Uuid,
+) -> Result<AssignmentRef, ApiError> {
+    let path = format!("/internal/assignments/{}", assignment_id);
+    match state
+        .http_client
+        .get_json("teacher-service", &path, Some(user))
+        .await
+    {
+        Err(e) if e.is_not_found() => Err(ApiError::NotFound("assignment not found")),
+        result => Ok(result?),
+    }
+}
+
+/// Load a submission the caller may read: their own work, or, with `submission:review`, any
+/// submission to an assignment of a course they teach. Others' submissions are reported as
+/// missing.
+pub(crate) async fn load_readable_submission(
+    state: &AppState,
+    auth: &Claims,
+    submission_id: Uuid,
+) -> Result<Submission, ApiError> {
+    auth.require(Permission::SubmissionRead)?;
+    let submission = fetch_submission(state, submission_id)
+        .await?
+        .ok_or(ApiError::NotFound("submission not found"))?;
+    let mut resource = Resource::new("submission", submission.id)
+        .owned_by(&submission.student_id)
+        .deny_as(ApiError::NotFound("submission not found"));
+    if !resource.is_owned_by(&auth.sub) && auth.can(Permission::SubmissionReview) {
+        let assignment = fetch_assignment(state, auth, submission.assignment_id).await?;
+        resource = resource.in_course(assignment.course_id, Permission::SubmissionReview);
+    }
+    auth.authorize(&resource, &AdminMemberships::new(&state.http_client))
+        .await?;
+    Ok(submission)
+}
+
+pub(crate) async fn fetch_submission(
+    state: &AppState,
+    submission_id: Uuid,
+) -> Result<Option<Submission>, ApiError> {
+    let row = sqlx::query_as::<_, SubmissionRow>(&format!(
+        "SELECT {} FROM student.submissions WHERE id = $1",
+        SUBMISSION_COLUMNS
+    ))
+    .bind(submission_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    Ok(row.map(Submission::from))
 }
 
 pub async fn health() -> &'static str {
@@ -37,53 +223,257 @@ pub async fn ready(State(state): State<AppState>) -> Result<&'static str, Status
 
 pub async fn create_submission(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    AuthUser(auth): AuthUser,
+    auth: Authorized<perm::SubmissionCreate>,
     Path(assignment_id): Path<Uuid>,
-    Json(body): Json<CreateSubmissionBody>,
-) -> Result<(StatusCode, Json<Submission>), (StatusCode, &'static str)> {
-    if auth.role != Role::Student {
-        return Err((StatusCode::FORBIDDEN, "student role required"));
+    upload: SubmissionUpload,
+) -> Result<(StatusCode, Json<Submission>), ApiError> {
+    // Verify assignment exists via teacher-service (K8s DNS) and that the caller is enrolled
+    // in its course via admin-service.
+    let assignment = fetch_assignment(&state, &auth, assignment_id).await?;
+    let course = Resource::new("assignment", assignment_id)
+        .in_course(assignment.course_id, Permission::SubmissionCreate);
+    let decision = auth
+        .authorize(&course, &AdminMemberships::new(&state.http_client))
+        .await?;
+    if decision.membership.is_some_and(|m| m.course_archived) {
+        return Err(ApiError::Conflict("course is archived"));
+    }
+    let now = chrono::Utc::now();
+    let is_late = assignment.window.check(now)?;
+    check_limits(
+        &upload.files,
+        assignment.max_attachment_bytes,
+        assignment.allowed_mime_types.as_deref(),
+    )?;
+
+    // Checked again under the row lock; refusing here avoids uploading files for nothing.
+    if let Some(max) = assignment.max_attempts {
+        let attempts = sqlx::query_scalar::<_, i32>(
+            "SELECT version FROM student.submissions WHERE assignment_id = $1 AND student_id = $2",
+        )
+        .bind(assignment_id)
+        .bind(&auth.sub)
+        .fetch_optional(&state.pool)
+        .await?;
+        if attempts.is_some_and(|attempts| attempts >= max) {
+            return Err(ApiError::Conflict("maximum attempts reached"));
+        }
     }
-    let bearer = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
-    // Verify assignment exists via teacher-service (K8s DNS)
-    let path = format!("/api/teacher/assignments/{}", assignment_id);
-    let res = state.http_client.get_teacher(&path, bearer).await.map_err(|e| {
-        tracing::warn!("teacher-service call failed: {}", e);
-        (StatusCode::BAD_GATEWAY, "assignment service unavailable")
-    })?;
-    if !res.status().is_success() {
-        return Err((StatusCode::NOT_FOUND, "assignment not found"));
+
+    let attachments = store_attachments(&state, assignment_id, upload.files).await?;
+    let version = NewVersion {
+        content: upload.content.as_deref(),
+        is_late,
+        attachments: &attachments,
+        created_at: now,
+    };
+    let recorded = insert_version(
+        &state,
+        assignment_id,
+        &auth.sub,
+        assignment.max_attempts,
+        version,
+    )
+    .await;
+    if recorded.is_err() {
+        discard_attachments(&state, &attachments).await;
     }
+    Ok((StatusCode::CREATED, Json(Submission::from(recorded?))))
+}
 
-    let id = Uuid::new_v4();
-    let now = chrono::Utc::now();
-    let student_id = auth.sub.clone();
+/// A submission version about to be recorded, with its files already in the blob store.
+struct NewVersion<'a> {
+    content: Option<&'a str>,
+    is_late: bool,
+    attachments: &'a [StoredAttachment],
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Append `new` to the student's submission, creating the submission on the first attempt.
+async fn insert_version(
+    state: &AppState,
+    assignment_id: Uuid,
+    student_id: &str,
+    max_attempts: Option<i32>,
+    new: NewVersion<'_>,
+) -> Result<SubmissionRow, ApiError> {
+    let now = new.created_at;
+    // One logical submission per (assignment, student): create it on the first POST, then lock
+    // it so concurrent resubmissions get consecutive version numbers.
+    let mut tx = state.pool.begin().await?;
     sqlx::query(
         r#"
-        INSERT INTO student.submissions (id, assignment_id, student_id, content, created_at)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO student.submissions
+            (id, assignment_id, student_id, content, is_late, version, created_at, updated_at)
+        VALUES ($1, $2, $3, NULL, FALSE, 0, $4, $4)
+        ON CONFLICT (assignment_id, student_id) DO NOTHING
         "#,
     )
-    .bind(id)
+    .bind(Uuid::new_v4())
     .bind(assignment_id)
-    .bind(&student_id)
-    .bind(body.content.as_deref())
+    .bind(student_id)
     .bind(now)
-    .execute(&state.pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("create_submission: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "database error")
-    })?;
-    Ok((
-        StatusCode::CREATED,
-        Json(Submission {
-            id,
-            assignment_id,
-            student_id,
-            content: body.content,
-            created_at: now,
-        }),
+    .execute(&mut *tx)
+    .await?;
+    let (id, version) = sqlx::query_as::<_, (Uuid, i32)>(
+        r#"
+        SELECT id, version FROM student.submissions
+        WHERE assignment_id = $1 AND student_id = $2
+        FOR UPDATE
+        "#,
+    )
+    .bind(assignment_id)
+    .bind(student_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if max_attempts.is_some_and(|max| version >= max) {
+        return Err(ApiError::Conflict("maximum attempts reached"));
+    }
+    let version = version + 1;
+    sqlx::query(
+        r#"
+        INSERT INTO student.submission_versions
+            (id, submission_id, version, content, is_late, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(id)
+    .bind(version)
+    .bind(new.content)
+    .bind(new.is_late)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+    record_attachments(&mut tx, id, version, new.attachments, now).await?;
+    let row = sqlx::query_as::<_, SubmissionRow>(&format!(
+        r#"
+        UPDATE student.submissions
+        SET content = $2, is_late = $3, version = $4, updated_at = $5
+        WHERE id = $1
+        RETURNING {}
+        "#,
+        SUBMISSION_COLUMNS
     ))
+    .bind(id)
+    .bind(new.content)
+    .bind(new.is_late)
+    .bind(version)
+    .bind(now)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(row)
+}
+
+pub async fn list_my_submissions(
+    State(state): State<AppState>,
+    auth: Authorized<perm::SubmissionRead>,
+    page: PageParams,
+    Query(filter): Query<ListSubmissionsFilter>,
+) -> Result<Json<Page<Submission>>, ApiError> {
+    let sql = format!(
+        r#"
+        SELECT {}
+        FROM student.submissions
+        WHERE student_id = $1
+          AND ($2::UUID IS NULL OR assignment_id = $2)
+          AND {}
+        {}
+        LIMIT $5
+        "#,
+        SUBMISSION_COLUMNS,
+        page.keyset_predicate(3),
+        page.order_by(),
+    );
+    let rows = sqlx::query_as::<_, SubmissionRow>(&sql)
+        .bind(&auth.sub)
+        .bind(filter.assignment_id)
+        .bind(page.cursor_created_at())
+        .bind(page.cursor_id())
+        .bind(page.fetch_limit())
+        .fetch_all(&state.pool)
+        .await?;
+    let items = rows.into_iter().map(Submission::from).collect();
+    Ok(Json(Page::from_rows(items, &page, |s: &Submission| {
+        Cursor::new(s.created_at, s.id)
+    })))
+}
+
+pub async fn list_assignment_submissions(
+    State(state): State<AppState>,
+    auth: Authorized<perm::SubmissionReview>,
+    Path(assignment_id): Path<Uuid>,
+    page: PageParams,
+    Query(filter): Query<ListAssignmentSubmissionsFilter>,
+) -> Result<Json<Page<Submission>>, ApiError> {
+    let assignment = fetch_assignment(&state, &auth, assignment_id).await?;
+    let resource = Resource::new("assignment", assignment_id)
+        .in_course(assignment.course_id, Permission::SubmissionReview);
+    auth.authorize(&resource, &AdminMemberships::new(&state.http_client))
+        .await?;
+
+    let sql = format!(
+        r#"
+        SELECT {}
+        FROM student.submissions
+        WHERE assignment_id = $1
+          AND ($2::TEXT IS NULL OR student_id = $2)
+          AND {}
+        {}
+        LIMIT $5
+        "#,
+        SUBMISSION_COLUMNS,
+        page.keyset_predicate(3),
+        page.order_by(),
+    );
+    let rows = sqlx::query_as::<_, SubmissionRow>(&sql)
+        .bind(assignment_id)
+        .bind(filter.student_id.as_deref())
+        .bind(page.cursor_created_at())
+        .bind(page.cursor_id())
+        .bind(page.fetch_limit())
+        .fetch_all(&state.pool)
+        .await?;
+    let items = rows.into_iter().map(Submission::from).collect();
+    Ok(Json(Page::from_rows(items, &page, |s: &Submission| {
+        Cursor::new(s.created_at, s.id)
+    })))
+}
+
+pub async fn get_submission(
+    State(state): State<AppState>,
+    AuthUser(auth): AuthUser,
+    Path(submission_id): Path<Uuid>,
+) -> Result<Json<Submission>, ApiError> {
+    let submission = load_readable_submission(&state, &auth, submission_id).await?;
+    Ok(Json(submission))
+}
+
+pub async fn get_submission_grade(
+    State(state): State<AppState>,
+    auth: Authorized<perm::GradeRead>,
+    Path(submission_id): Path<Uuid>,
+) -> Result<Json<ReleasedGrade>, ApiError> {
+    let (student_id, version) = sqlx::query_as::<_, (String, i32)>(
+        "SELECT student_id, version FROM student.submissions WHERE id = $1",
+    )
+    .bind(submission_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound("submission not found"))?;
+    let resource = Resource::new("submission", submission_id)
+        .owned_by(&student_id)
+        .deny_as(ApiError::NotFound("submission not found"));
+    auth.authorize(&resource, &NoMemberships).await?;
+    // teacher-service only answers for the delegated student's own released grades.
+    let path = format!("/internal/submissions/{}/grade", submission_id);
+    match state
+        .http_client
+        .get_json::<ReleasedGrade>("teacher-service", &path, Some(&auth))
+        .await
+    {
+        Err(e) if e.is_not_found() => Err(ApiError::NotFound("grade not released")),
+        result => Ok(Json(result?.check_stale(version))),
+    }
 }
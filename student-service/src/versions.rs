@@ -0,0 +1,126 @@
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use shared::extract::{Path, Query};
+use shared::{ApiError, AuthUser};
+use similar::TextDiff;
+use uuid::Uuid;
+
+use crate::routes::load_readable_submission;
+use crate::AppState;
+
+/// An immutable snapshot appended on every (re)submission.
+#[derive(Serialize)]
+pub struct SubmissionVersion {
+    pub submission_id: Uuid,
+    pub version: i32,
+    pub content: Option<String>,
+    pub is_late: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+type SubmissionVersionRow = (
+    Uuid,
+    i32,
+    Option<String>,
+    bool,
+    chrono::DateTime<chrono::Utc>,
+);
+
+const VERSION_COLUMNS: &str = "submission_id, version, content, is_late, created_at";
+
+impl From<SubmissionVersionRow> for SubmissionVersion {
+    fn from((submission_id, version, content, is_late, created_at): SubmissionVersionRow) -> Self {
+        Self {
+            submission_id,
+            version,
+            content,
+            is_late,
+            created_at,
+        }
+    }
+}
+
+/// `to` defaults to the latest version.
+#[derive(Deserialize)]
+pub struct DiffParams {
+    pub from: i32,
+    pub to: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct VersionDiff {
+    pub submission_id: Uuid,
+    pub from: i32,
+    pub to: i32,
+    /// Unified diff of the two versions' content.
+    pub diff: String,
+}
+
+async fn fetch_version(
+    state: &AppState,
+    submission_id: Uuid,
+    version: i32,
+) -> Result<SubmissionVersion, ApiError> {
+    let row = sqlx::query_as::<_, SubmissionVersionRow>(&format!(
+        "SELECT {} FROM student.submission_versions WHERE submission_id = $1 AND version = $2",
+        VERSION_COLUMNS
+    ))
+    .bind(submission_id)
+    .bind(version)
+    .fetch_optional(&state.pool)
+    .await?;
+    row.map(SubmissionVersion::from)
+        .ok_or(ApiError::NotFound("version not found"))
+}
+
+pub async fn list_versions(
+    State(state): State<AppState>,
+    AuthUser(auth): AuthUser,
+    Path(submission_id): Path<Uuid>,
+) -> Result<Json<Vec<SubmissionVersion>>, ApiError> {
+    load_readable_submission(&state, &auth, submission_id).await?;
+    let rows = sqlx::query_as::<_, SubmissionVersionRow>(&format!(
+        "SELECT {} FROM student.submission_versions WHERE submission_id = $1 ORDER BY version",
+        VERSION_COLUMNS
+    ))
+    .bind(submission_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(
+        rows.into_iter().map(SubmissionVersion::from).collect(),
+    ))
+}
+
+pub async fn get_version(
+    State(state): State<AppState>,
+    AuthUser(auth): AuthUser,
+    Path((submission_id, version)): Path<(Uuid, i32)>,
+) -> Result<Json<SubmissionVersion>, ApiError> {
+    load_readable_submission(&state, &auth, submission_id).await?;
+    Ok(Json(fetch_version(&state, submission_id, version).await?))
+}
+
+pub async fn diff_versions(
+    State(state): State<AppState>,
+    AuthUser(auth): AuthUser,
+    Path(submission_id): Path<Uuid>,
+    Query(params): Query<DiffParams>,
+) -> Result<Json<VersionDiff>, ApiError> {
+    let submission = load_readable_submission(&state, &auth, submission_id).await?;
+    let to = params.to.unwrap_or(submission.version);
+    let old = fetch_version(&state, submission_id, params.from).await?;
+    let new = fetch_version(&state, submission_id, to).await?;
+
+    let old_content = old.content.unwrap_or_default();
+    let new_content = new.content.unwrap_or_default();
+    let diff = TextDiff::from_lines(&old_content, &new_content)
+        .unified_diff()
+        .header(&format!("v{}", params.from), &format!("v{}", to))
+        .to_string();
+    Ok(Json(VersionDiff {
+        submission_id,
+        from: params.from,
+        to,
+        diff,
+    }))
+}
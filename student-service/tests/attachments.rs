@@ -0,0 +1,77 @@
+//! Per-assignment attachment limits: size per file and allowed content types.
+
+use axum::http::StatusCode;
+use bytes::Bytes;
+use student_service::{check_limits, UploadedFile};
+
+fn file(content_type: &str, len: usize) -> UploadedFile {
+    UploadedFile {
+        filename: "report".to_string(),
+        content_type: content_type.to_string(),
+        data: Bytes::from(vec![0; len]),
+    }
+}
+
+fn status(files: &[UploadedFile], max_bytes: Option<i64>, allowed: Option<&[&str]>) -> StatusCode {
+    let allowed: Option<Vec<String>> =
+        allowed.map(|types| types.iter().map(|t| t.to_string()).collect());
+    match check_limits(files, max_bytes, allowed.as_deref()) {
+        Ok(()) => StatusCode::OK,
+        Err(e) => e.status(),
+    }
+}
+
+#[test]
+fn unlimited_assignments_accept_anything() {
+    let files = [file("application/x-anything", 10_000), file("", 0)];
+    assert_eq!(status(&files, None, None), StatusCode::OK);
+    assert_eq!(status(&[], Some(1), Some(&[])), StatusCode::OK);
+}
+
+#[test]
+fn every_file_is_held_to_the_size_limit() {
+    assert_eq!(
+        status(&[file("text/plain", 100)], Some(100), None),
+        StatusCode::OK
+    );
+    let files = [file("text/plain", 10), file("text/plain", 101)];
+    assert_eq!(
+        status(&files, Some(100), None),
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+}
+
+#[test]
+fn content_types_match_exactly_or_by_wildcard() {
+    let allowed = Some(&["application/pdf", "image/*"][..]);
+    for content_type in [
+        "application/pdf",
+        "Application/PDF",
+        "application/pdf; name=report.pdf",
+        "image/png",
+        "image/svg+xml",
+    ] {
+        assert_eq!(
+            status(&[file(content_type, 1)], None, allowed),
+            StatusCode::OK,
+            "{content_type}"
+        );
+    }
+    for content_type in [
+        "application/pdfx",
+        "text/plain",
+        "imagex/png",
+        "application/octet-stream",
+    ] {
+        assert_eq!(
+            status(&[file(content_type, 1)], None, allowed),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "{content_type}"
+        );
+    }
+    // No type is allowed by an empty list.
+    assert_eq!(
+        status(&[file("application/pdf", 1)], None, Some(&[])),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+}
@@ -0,0 +1,37 @@
+//! Released grades as relayed from teacher-service: flagged stale once the student has
+//! submitted a newer version than the one graded.
+
+use serde_json::json;
+use student_service::ReleasedGrade;
+
+fn released_for_version(version: i32) -> ReleasedGrade {
+    // teacher-service's payload, including fields student-service does not relay.
+    serde_json::from_value(json!({
+        "submission_id": "5b7f8a52-8f6e-4f57-9a2c-3a4d8e0c1b2a",
+        "submission_version": version,
+        "assignment_id": "0d4c7f1f-8b21-4d0e-9a52-6f0c1f3e2b7a",
+        "student_id": "student-1",
+        "score": 8.0,
+        "max_points": 10.0,
+        "feedback": null,
+        "late_penalty_percent": null,
+        "final_score": 8.0,
+        "status": "released",
+        "released_at": "2026-04-01T12:00:00Z",
+    }))
+    .unwrap()
+}
+
+#[test]
+fn grades_of_the_latest_version_are_current() {
+    assert!(!released_for_version(2).check_stale(2).stale);
+}
+
+#[test]
+fn resubmitting_makes_the_grade_stale() {
+    let grade = released_for_version(2).check_stale(3);
+    assert!(grade.stale);
+    let body = serde_json::to_value(&grade).unwrap();
+    assert_eq!(body["submission_version"], 2);
+    assert_eq!(body["stale"], true);
+}
@@ -0,0 +1,78 @@
+//! Submission windows: before `opens_at`, after `closes_at`, and late submissions under each
+//! late policy, including submissions exactly at each boundary.
+
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeZone, Utc};
+use student_service::{LatePolicy, SubmissionWindow};
+
+fn at(hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 4, 1, hour, 0, 0).unwrap()
+}
+
+/// Opens at 09:00, due at 12:00, closes at 15:00.
+fn window(late_policy: LatePolicy) -> SubmissionWindow {
+    SubmissionWindow {
+        opens_at: Some(at(9)),
+        due_at: Some(at(12)),
+        closes_at: Some(at(15)),
+        late_policy,
+    }
+}
+
+/// `Ok(is_late)`, or the rejection's status and detail.
+fn check(window: &SubmissionWindow, now: DateTime<Utc>) -> Result<bool, (StatusCode, String)> {
+    window.check(now).map_err(|e| (e.status(), e.to_string()))
+}
+
+fn conflict(detail: &str) -> Result<bool, (StatusCode, String)> {
+    Err((StatusCode::CONFLICT, detail.to_string()))
+}
+
+#[test]
+fn submissions_outside_the_window_are_refused() {
+    let window = window(LatePolicy::AcceptFlag);
+    let just_before = at(9) - chrono::Duration::seconds(1);
+    assert_eq!(
+        check(&window, just_before),
+        conflict("assignment is not open yet")
+    );
+    assert_eq!(check(&window, at(9)), Ok(false));
+
+    assert_eq!(check(&window, at(15)), Ok(true));
+    let just_after = at(15) + chrono::Duration::seconds(1);
+    assert_eq!(check(&window, just_after), conflict("assignment is closed"));
+}
+
+#[test]
+fn submissions_at_the_deadline_are_on_time() {
+    for policy in [
+        LatePolicy::Reject,
+        LatePolicy::AcceptFlag,
+        LatePolicy::Penalty,
+    ] {
+        assert_eq!(check(&window(policy), at(12)), Ok(false));
+    }
+}
+
+#[test]
+fn late_submissions_follow_the_late_policy() {
+    let late = at(12) + chrono::Duration::seconds(1);
+    assert_eq!(
+        check(&window(LatePolicy::Reject), late),
+        conflict("submission deadline has passed")
+    );
+    assert_eq!(check(&window(LatePolicy::AcceptFlag), late), Ok(true));
+    assert_eq!(check(&window(LatePolicy::Penalty), late), Ok(true));
+}
+
+#[test]
+fn unset_bounds_do_not_restrict() {
+    let open = SubmissionWindow {
+        opens_at: None,
+        due_at: None,
+        closes_at: None,
+        late_policy: LatePolicy::Reject,
+    };
+    assert_eq!(check(&open, at(0)), Ok(false));
+    assert_eq!(check(&open, at(23)), Ok(false));
+}
@@ -0,0 +1,327 @@
+//! Grading: teachers record a score and feedback per submission as a draft and release it when
+//! ready. Students only ever see released grades (directly or through student-service).
+
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use shared::extract::Path;
+use shared::validation::schema_error;
+use shared::{
+    perm, AdminMemberships, ApiError, Authorized, Claims, Permission, Resource, ValidJson,
+};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+use crate::routes::LatePolicy;
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GradeStatus {
+    Draft,
+    Released,
+}
+
+impl GradeStatus {
+    fn parse(s: &str) -> Result<Self, ApiError> {
+        match s {
+            "draft" => Ok(GradeStatus::Draft),
+            "released" => Ok(GradeStatus::Released),
+            _ => {
+                tracing::error!("teacher.grades has unknown status {:?}", s);
+                Err(ApiError::Internal("database error"))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "score_within_max_points"))]
+pub struct GradeBody {
+    #[validate(range(min = 0.0))]
+    pub score: f64,
+    #[validate(range(exclusive_min = 0.0))]
+    pub max_points: f64,
+    #[validate(length(max = 10000))]
+    pub feedback: Option<String>,
+}
+
+fn score_within_max_points(body: &GradeBody) -> Result<(), ValidationError> {
+    if body.score > body.max_points {
+        return Err(schema_error("score", "must not exceed max_points"));
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct Grade {
+    pub submission_id: Uuid,
+    /// Submission version the grade was given for (the latest at grading time).
+    pub submission_version: i32,
+    pub assignment_id: Uuid,
+    pub student_id: String,
+    pub score: f64,
+    pub max_points: f64,
+    pub feedback: Option<String>,
+    /// Deducted from `score` because the submission was late under the penalty policy.
+    pub late_penalty_percent: Option<i16>,
+    pub final_score: f64,
+    pub status: GradeStatus,
+    pub graded_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub released_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the student has submitted a newer version since the grade was given. Omitted
+    /// for student-service, which checks it against its own copy of the versions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale: Option<bool>,
+}
+
+type GradeRow = (
+    Uuid,
+    i32,
+    Uuid,
+    String,
+    f64,
+    f64,
+    Option<String>,
+    Option<i16>,
+    String,
+    String,
+    chrono::DateTime<chrono::Utc>,
+    chrono::DateTime<chrono::Utc>,
+    Option<chrono::DateTime<chrono::Utc>>,
+);
+
+const GRADE_COLUMNS: &str =
+    "submission_id, submission_version, assignment_id, student_id, score, max_points, \
+     feedback, late_penalty_percent, status, graded_by, created_at, updated_at, released_at";
+
+impl TryFrom<GradeRow> for Grade {
+    type Error = ApiError;
+
+    fn try_from(
+        (
+            submission_id,
+            submission_version,
+            assignment_id,
+            student_id,
+            score,
+            max_points,
+            feedback,
+            late_penalty_percent,
+            status,
+            graded_by,
+            created_at,
+            updated_at,
+            released_at,
+        ): GradeRow,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            submission_id,
+            submission_version,
+            assignment_id,
+            student_id,
+            score,
+            max_points,
+            feedback,
+            late_penalty_percent,
+            final_score: score * (1.0 - f64::from(late_penalty_percent.unwrap_or(0)) / 100.0),
+            status: GradeStatus::parse(&status)?,
+            graded_by,
+            created_at,
+            updated_at,
+            released_at,
+            stale: None,
+        })
+    }
+}
+
+/// Subset of student-service's submission payload.
+#[derive(Deserialize)]
+struct SubmissionRef {
+    assignment_id: Uuid,
+    student_id: String,
+    version: i32,
+    #[serde(default)]
+    is_late: bool,
+}
+
+/// Look up the submission in student-service (K8s DNS) to learn its assignment, author and
+/// latest version.
+async fn fetch_submission(
+    state: &AppState,
+    user: &Claims,
+    submission_id: Uuid,
+) -> Result<SubmissionRef, ApiError> {
+    let path = format!("/internal/submissions/{}", submission_id);
+    match state
+        .http_client
+        .get_json("student-service", &path, Some(user))
+        .await
+    {
+        Err(e) if e.is_not_found() => Err(ApiError::NotFound("submission not found")),
+        result => Ok(result?),
+    }
+}
+
+/// Flag `grade` stale if a newer version was submitted after it was given.
+async fn check_stale(state: &AppState, user: &Claims, grade: &mut Grade) -> Result<(), ApiError> {
+    let submission = fetch_submission(state, user, grade.submission_id).await?;
+    grade.stale = Some(grade.submission_version < submission.version);
+    Ok(())
+}
+
+/// Course of an assignment; grades are written, and read in draft, only by its teachers.
+async fn assignment_course(state: &AppState, assignment_id: Uuid) -> Result<Uuid, ApiError> {
+    sqlx::query_scalar::<_, Uuid>("SELECT course_id FROM teacher.assignments WHERE id = $1")
+        .bind(assignment_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(ApiError::NotFound("assignment not found"))
+}
+
+/// Penalty to apply to a late submission, per the assignment's late policy.
+async fn late_penalty(state: &AppState, assignment_id: Uuid) -> Result<Option<i16>, ApiError> {
+    let (policy, percent) = sqlx::query_as::<_, (String, Option<i16>)>(
+        "SELECT late_policy, late_penalty_percent FROM teacher.assignments WHERE id = $1",
+    )
+    .bind(assignment_id)
+    .fetch_one(&state.pool)
+    .await?;
+    Ok(match LatePolicy::parse(&policy)? {
+        LatePolicy::Penalty => percent,
+        LatePolicy::Reject | LatePolicy::AcceptFlag => None,
+    })
+}
+
+pub(crate) async fn fetch_grade(
+    state: &AppState,
+    submission_id: Uuid,
+) -> Result<Option<Grade>, ApiError> {
+    let row = sqlx::query_as::<_, GradeRow>(&format!(
+        "SELECT {} FROM teacher.grades WHERE submission_id = $1",
+        GRADE_COLUMNS
+    ))
+    .bind(submission_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    row.map(Grade::try_from).transpose()
+}
+
+/// Create or update the grade of a submission. Editing a released grade returns it to draft, so
+/// students only see the change once it is released again.
+pub async fn put_grade(
+    State(state): State<AppState>,
+    auth: Authorized<perm::SubmissionGrade>,
+    Path(submission_id): Path<Uuid>,
+    ValidJson(body): ValidJson<GradeBody>,
+) -> Result<Json<Grade>, ApiError> {
+    let submission = fetch_submission(&state, &auth, submission_id).await?;
+    let course_id = assignment_course(&state, submission.assignment_id).await?;
+    let resource = Resource::new("submission", submission_id)
+        .in_course(course_id, Permission::SubmissionGrade);
+    auth.authorize(&resource, &AdminMemberships::new(&state.http_client))
+        .await?;
+    let late_penalty_percent = if submission.is_late {
+        late_penalty(&state, submission.assignment_id).await?
+    } else {
+        None
+    };
+
+    let now = chrono::Utc::now();
+    let row = sqlx::query_as::<_, GradeRow>(&format!(
+        r#"
+        INSERT INTO teacher.grades
+            (submission_id, submission_version, assignment_id, student_id, score, max_points,
+             feedback, late_penalty_percent, status, graded_by, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'draft', $9, $10, $10)
+        ON CONFLICT (submission_id) DO UPDATE
+        SET submission_version = EXCLUDED.submission_version,
+            score = EXCLUDED.score,
+            max_points = EXCLUDED.max_points,
+            feedback = EXCLUDED.feedback,
+            late_penalty_percent = EXCLUDED.late_penalty_percent,
+            graded_by = EXCLUDED.graded_by,
+            updated_at = EXCLUDED.updated_at,
+            status = 'draft',
+            released_at = NULL
+        RETURNING {}
+        "#,
+        GRADE_COLUMNS
+    ))
+    .bind(submission_id)
+    .bind(submission.version)
+    .bind(submission.assignment_id)
+    .bind(&submission.student_id)
+    .bind(body.score)
+    .bind(body.max_points)
+    .bind(body.feedback.as_deref())
+    .bind(late_penalty_percent)
+    .bind(&auth.sub)
+    .bind(now)
+    .fetch_one(&state.pool)
+    .await?;
+    let mut grade = Grade::try_from(row)?;
+    // Always the latest version, as just looked up.
+    grade.stale = Some(false);
+    Ok(Json(grade))
+}
+
+pub async fn release_grade(
+    State(state): State<AppState>,
+    auth: Authorized<perm::SubmissionGrade>,
+    Path(submission_id): Path<Uuid>,
+) -> Result<Json<Grade>, ApiError> {
+    let grade = fetch_grade(&state, submission_id)
+        .await?
+        .ok_or(ApiError::NotFound("grade not found"))?;
+    let course_id = assignment_course(&state, grade.assignment_id).await?;
+    let resource =
+        Resource::new("grade", submission_id).in_course(course_id, Permission::SubmissionGrade);
+    auth.authorize(&resource, &AdminMemberships::new(&state.http_client))
+        .await?;
+
+    let now = chrono::Utc::now();
+    let row = sqlx::query_as::<_, GradeRow>(&format!(
+        r#"
+        UPDATE teacher.grades
+        SET status = 'released',
+            released_at = COALESCE(released_at, $2),
+            updated_at = $2
+        WHERE submission_id = $1
+        RETURNING {}
+        "#,
+        GRADE_COLUMNS
+    ))
+    .bind(submission_id)
+    .bind(now)
+    .fetch_one(&state.pool)
+    .await?;
+    let mut grade = Grade::try_from(row)?;
+    check_stale(&state, &auth, &mut grade).await?;
+    Ok(Json(grade))
+}
+
+pub async fn get_grade(
+    State(state): State<AppState>,
+    auth: Authorized<perm::GradeRead>,
+    Path(submission_id): Path<Uuid>,
+) -> Result<Json<Grade>, ApiError> {
+    let mut grade = fetch_grade(&state, submission_id)
+        .await?
+        .ok_or(ApiError::NotFound("grade not found"))?;
+    // Students own their grade only once it is released; drafts are for the course's teachers.
+    let mut resource =
+        Resource::new("grade", submission_id).deny_as(ApiError::NotFound("grade not found"));
+    if grade.status == GradeStatus::Released {
+        resource = resource.owned_by(&grade.student_id);
+    }
+    if !resource.is_owned_by(&auth.sub) {
+        let course_id = assignment_course(&state, grade.assignment_id).await?;
+        resource = resource.in_course(course_id, Permission::SubmissionGrade);
+    }
+    auth.authorize(&resource, &AdminMemberships::new(&state.http_client))
+        .await?;
+    check_stale(&state, &auth, &mut grade).await?;
+    Ok(Json(grade))
+}
@@ -0,0 +1,48 @@
+//! Assignment and grade lookups for student-service. A grade is only returned to the student
+//! it belongs to, named as the delegated user of the service token (`ServiceCaller`), so the
+//! service token alone does not unlock it. Not routed by the ingress.
+
+use axum::{extract::State, Json};
+use shared::access::NoMemberships;
+use shared::extract::Path;
+use shared::{ApiError, Resource, ServiceCaller};
+use uuid::Uuid;
+
+use crate::grades::{fetch_grade, Grade, GradeStatus};
+use crate::routes::{fetch_assignment, Assignment};
+use crate::AppState;
+
+/// Assignment schedule and limits, e.g. for student-service before accepting a submission.
+pub async fn get_assignment(
+    State(state): State<AppState>,
+    caller: ServiceCaller,
+    Path(assignment_id): Path<Uuid>,
+) -> Result<Json<Assignment>, ApiError> {
+    caller.allow(&["student-service"])?;
+    let assignment = fetch_assignment(&state, assignment_id).await?;
+    Ok(Json(
+        assignment.ok_or(ApiError::NotFound("assignment not found"))?,
+    ))
+}
+
+/// A released grade, for the student it belongs to as the delegated user. Drafts and other
+/// students' grades are 404.
+pub async fn get_released_grade(
+    State(state): State<AppState>,
+    caller: ServiceCaller,
+    Path(submission_id): Path<Uuid>,
+) -> Result<Json<Grade>, ApiError> {
+    caller.allow(&["student-service"])?;
+    let user = caller
+        .user
+        .ok_or(ApiError::Forbidden("a delegated user is required"))?;
+    let grade = fetch_grade(&state, submission_id)
+        .await?
+        .filter(|g| g.status == GradeStatus::Released)
+        .ok_or(ApiError::NotFound("grade not found"))?;
+    let resource = Resource::new("grade", submission_id)
+        .owned_by(&grade.student_id)
+        .deny_as(ApiError::NotFound("grade not found"));
+    user.authorize(&resource, &NoMemberships).await?;
+    Ok(Json(grade))
+}
@@ -1,5 +1,9 @@
+mod grades;
+mod internal;
 mod routes;
 
+pub use routes::{CreateAssignmentBody, LatePolicy};
+
 use axum::{routing::get, Router};
 use shared::ServiceClient;
 use sqlx::PgPool;
@@ -14,21 +18,45 @@ pub struct AppState {
 }
 
 pub fn app(pool: PgPool, http_client: std::sync::Arc<ServiceClient>) -> Router {
-    Router::new()
+    let router = Router::new()
         .route("/health", get(routes::health))
         .route("/ready", get(routes::ready))
         .route(
             "/api/teacher/courses/:course_id/assignments",
-            axum::routing::post(routes::create_assignment),
+            get(routes::list_assignments).post(routes::create_assignment),
         )
         .route(
             "/api/teacher/assignments/:assignment_id",
             get(routes::get_assignment),
         )
-        .with_state(AppState { pool, http_client })
+        .route(
+            "/api/teacher/submissions/:submission_id/grade",
+            get(grades::get_grade).put(grades::put_grade),
+        )
+        .route(
+            "/api/teacher/submissions/:submission_id/grade/release",
+            axum::routing::post(grades::release_grade),
+        )
+        .route(
+            "/internal/assignments/:assignment_id",
+            get(internal::get_assignment),
+        )
+        .route(
+            "/internal/submissions/:submission_id/grade",
+            get(internal::get_released_grade),
+        )
+        .with_state(AppState {
+            pool: pool.clone(),
+            http_client,
+        })
         .layer(
             ServiceBuilder::new()
-                .layer(tower_http::trace::TraceLayer::new_for_http())
+                .layer(axum::middleware::from_fn(shared::request_id::propagate))
+                .layer(
+                    tower_http::trace::TraceLayer::new_for_http()
+                        .make_span_with(shared::trace_context::make_span),
+                )
                 .layer(TimeoutLayer::new(Duration::from_secs(30))),
-        )
+        );
+    shared::metrics::with_metrics(router, pool)
 }
@@ -1,17 +1,133 @@
-use axum::{
-    extract::{Path, State},
-    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
-    Json,
-};
+use axum::{extract::State, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
-use shared::{AuthUser, Role};
+use shared::extract::{Path, Query};
+use shared::pagination::{escape_like, Cursor};
+use shared::validation::{schema_error, trim};
+use shared::{
+    perm, AdminMemberships, ApiError, Authorized, Page, PageParams, Permission, Resource, ValidJson,
+};
 use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 use crate::AppState;
 
-#[derive(Deserialize)]
+/// What happens to a submission made after `due_at` (and before `closes_at`, if set).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LatePolicy {
+    /// Late submissions are refused.
+    Reject,
+    /// Late submissions are accepted and flagged `is_late`.
+    #[default]
+    AcceptFlag,
+    /// Late submissions are accepted, flagged, and graded with `late_penalty_percent` deducted.
+    Penalty,
+}
+
+impl LatePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LatePolicy::Reject => "reject",
+            LatePolicy::AcceptFlag => "accept_flag",
+            LatePolicy::Penalty => "penalty",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Result<Self, ApiError> {
+        match s {
+            "reject" => Ok(LatePolicy::Reject),
+            "accept_flag" => Ok(LatePolicy::AcceptFlag),
+            "penalty" => Ok(LatePolicy::Penalty),
+            _ => {
+                tracing::error!("teacher.assignments has unknown late_policy {:?}", s);
+                Err(ApiError::Internal("database error"))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "schedule"))]
 pub struct CreateAssignmentBody {
+    #[serde(deserialize_with = "trim")]
+    #[validate(length(min = 1, max = 200))]
     pub title: String,
+    pub opens_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub due_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub closes_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub late_policy: LatePolicy,
+    /// Required with `late_policy: "penalty"` (0-100), rejected otherwise.
+    #[validate(range(min = 0, max = 100))]
+    pub late_penalty_percent: Option<i16>,
+    /// Versions a student may submit; unlimited when omitted.
+    #[validate(range(min = 1))]
+    pub max_attempts: Option<i32>,
+    /// Per-file attachment size limit; student-service's upload cap applies when omitted.
+    #[validate(range(min = 1))]
+    pub max_attachment_bytes: Option<i64>,
+    /// Accepted attachment MIME types (`type/subtype` or `type/*`); any type when omitted.
+    #[validate(length(max = MAX_MIME_TYPES), custom(function = "mime_patterns"))]
+    pub allowed_mime_types: Option<Vec<String>>,
+}
+
+/// Most entries in `allowed_mime_types`; every upload is checked against each of them.
+const MAX_MIME_TYPES: u64 = 50;
+/// Longest entry: RFC 6838 allows 127 characters for the type and for the subtype.
+const MAX_MIME_TYPE_LEN: usize = 255;
+
+fn mime_patterns(types: &[String]) -> Result<(), ValidationError> {
+    if types.iter().any(|m| m.len() > MAX_MIME_TYPE_LEN) {
+        return Err(ValidationError::new("mime_type").with_message(
+            format!("entries must be at most {} characters", MAX_MIME_TYPE_LEN).into(),
+        ));
+    }
+    // Uploads are matched on the essence alone, so parameters or spaces could never match.
+    let valid = |m: &String| {
+        !m.contains(|c: char| c == ';' || c.is_whitespace())
+            && matches!(m.split_once('/'), Some((t, s)) if !t.is_empty() && !s.is_empty() && t != "*")
+    };
+    if types.iter().all(valid) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("mime_type")
+            .with_message("entries must be type/subtype or type/*, without parameters".into()))
+    }
+}
+
+/// Rules relating several fields; single-field limits are declared on the fields.
+fn schedule(body: &CreateAssignmentBody) -> Result<(), ValidationError> {
+    let in_order = |a: Option<chrono::DateTime<chrono::Utc>>,
+                    b: Option<chrono::DateTime<chrono::Utc>>| match (a, b) {
+        (Some(a), Some(b)) => a <= b,
+        _ => true,
+    };
+    if !in_order(body.opens_at, body.due_at) {
+        return Err(schema_error("due_at", "must not be before opens_at"));
+    }
+    if !in_order(body.due_at, body.closes_at) || !in_order(body.opens_at, body.closes_at) {
+        return Err(schema_error(
+            "closes_at",
+            "must not be before opens_at or due_at",
+        ));
+    }
+    match (body.late_policy, body.late_penalty_percent) {
+        (LatePolicy::Penalty, None) => Err(schema_error(
+            "late_penalty_percent",
+            "is required for the penalty policy",
+        )),
+        (LatePolicy::Penalty, Some(_)) | (_, None) => Ok(()),
+        (_, Some(_)) => Err(schema_error(
+            "late_penalty_percent",
+            "is only allowed with the penalty policy",
+        )),
+    }
+}
+
+/// Subset of admin-service's course payload needed for the existence check.
+#[derive(Deserialize)]
+struct CourseRef {
+    archived_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Serialize)]
@@ -19,9 +135,79 @@ pub struct Assignment {
     pub id: Uuid,
     pub course_id: Uuid,
     pub title: String,
+    pub opens_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub due_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub closes_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub late_policy: LatePolicy,
+    pub late_penalty_percent: Option<i16>,
+    pub max_attempts: Option<i32>,
+    pub max_attachment_bytes: Option<i64>,
+    pub allowed_mime_types: Option<Vec<String>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Filters for `GET /api/teacher/courses/:course_id/assignments`; paging is handled by `PageParams`.
+#[derive(Deserialize)]
+pub struct ListAssignmentsFilter {
+    /// Case-insensitive substring match on the assignment title.
+    pub q: Option<String>,
+}
+
+type AssignmentRow = (
+    Uuid,
+    Uuid,
+    String,
+    Option<chrono::DateTime<chrono::Utc>>,
+    Option<chrono::DateTime<chrono::Utc>>,
+    Option<chrono::DateTime<chrono::Utc>>,
+    String,
+    Option<i16>,
+    Option<i32>,
+    Option<i64>,
+    Option<Vec<String>>,
+    chrono::DateTime<chrono::Utc>,
+);
+
+const ASSIGNMENT_COLUMNS: &str = "id, course_id, title, opens_at, due_at, closes_at, \
+     late_policy, late_penalty_percent, max_attempts, max_attachment_bytes, allowed_mime_types, \
+     created_at";
+
+impl TryFrom<AssignmentRow> for Assignment {
+    type Error = ApiError;
+
+    fn try_from(
+        (
+            id,
+            course_id,
+            title,
+            opens_at,
+            due_at,
+            closes_at,
+            late_policy,
+            late_penalty_percent,
+            max_attempts,
+            max_attachment_bytes,
+            allowed_mime_types,
+            created_at,
+        ): AssignmentRow,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id,
+            course_id,
+            title,
+            opens_at,
+            due_at,
+            closes_at,
+            late_policy: LatePolicy::parse(&late_policy)?,
+            late_penalty_percent,
+            max_attempts,
+            max_attachment_bytes,
+            allowed_mime_types,
+            created_at,
+        })
+    }
+}
+
 pub async fn health() -> &'static str {
     "ok"
 }
@@ -36,79 +222,144 @@ pub async fn ready(State(state): State<AppState>) -> Result<&'static str, Status
 
 pub async fn create_assignment(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    AuthUser(auth): AuthUser,
+    auth: Authorized<perm::AssignmentWrite>,
     Path(course_id): Path<Uuid>,
-    Json(body): Json<CreateAssignmentBody>,
-) -> Result<(StatusCode, Json<Assignment>), (StatusCode, &'static str)> {
-    if auth.role != Role::Teacher {
-        return Err((StatusCode::FORBIDDEN, "teacher role required"));
-    }
-    let bearer = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+    ValidJson(body): ValidJson<CreateAssignmentBody>,
+) -> Result<(StatusCode, Json<Assignment>), ApiError> {
+    // Authorize before the lookup so non-members cannot probe other courses.
+    let course =
+        Resource::new("course", course_id).in_course(course_id, Permission::AssignmentWrite);
+    auth.authorize(&course, &AdminMemberships::new(&state.http_client))
+        .await?;
     // Verify course exists via admin-service (K8s DNS)
-    let path = format!("/api/admin/courses/{}", course_id);
-    let res = state.http_client.get_admin(&path, bearer).await.map_err(|e| {
-        tracing::warn!("admin-service call failed: {}", e);
-        (StatusCode::BAD_GATEWAY, "course service unavailable")
-    })?;
-    if !res.status().is_success() {
-        return Err((StatusCode::NOT_FOUND, "course not found"));
+    let path = format!("/internal/courses/{}", course_id);
+    let course: CourseRef = match state
+        .http_client
+        .get_json("admin-service", &path, Some(&auth))
+        .await
+    {
+        Err(e) if e.is_not_found() => {
+            return Err(ApiError::NotFound("course not found"));
+        }
+        result => result?,
+    };
+    if course.archived_at.is_some() {
+        return Err(ApiError::Conflict("course is archived"));
     }
 
     let id = Uuid::new_v4();
     let now = chrono::Utc::now();
     sqlx::query(
         r#"
-        INSERT INTO teacher.assignments (id, course_id, title, created_at)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO teacher.assignments
+            (id, course_id, title, opens_at, due_at, closes_at, late_policy,
+             late_penalty_percent, max_attempts, max_attachment_bytes, allowed_mime_types,
+             created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
         "#,
     )
     .bind(id)
     .bind(course_id)
     .bind(&body.title)
+    .bind(body.opens_at)
+    .bind(body.due_at)
+    .bind(body.closes_at)
+    .bind(body.late_policy.as_str())
+    .bind(body.late_penalty_percent)
+    .bind(body.max_attempts)
+    .bind(body.max_attachment_bytes)
+    .bind(body.allowed_mime_types.as_deref())
     .bind(now)
     .execute(&state.pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("create_assignment: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "database error")
-    })?;
+    .await?;
     Ok((
         StatusCode::CREATED,
         Json(Assignment {
             id,
             course_id,
             title: body.title,
+            opens_at: body.opens_at,
+            due_at: body.due_at,
+            closes_at: body.closes_at,
+            late_policy: body.late_policy,
+            late_penalty_percent: body.late_penalty_percent,
+            max_attempts: body.max_attempts,
+            max_attachment_bytes: body.max_attachment_bytes,
+            allowed_mime_types: body.allowed_mime_types,
             created_at: now,
         }),
     ))
 }
 
+pub async fn list_assignments(
+    State(state): State<AppState>,
+    auth: Authorized<perm::AssignmentList>,
+    Path(course_id): Path<Uuid>,
+    page: PageParams,
+    Query(filter): Query<ListAssignmentsFilter>,
+) -> Result<Json<Page<Assignment>>, ApiError> {
+    let course =
+        Resource::new("course", course_id).in_course(course_id, Permission::AssignmentList);
+    auth.authorize(&course, &AdminMemberships::new(&state.http_client))
+        .await?;
+
+    let sql = format!(
+        r#"
+        SELECT {}
+        FROM teacher.assignments
+        WHERE course_id = $1
+          AND ($2::TEXT IS NULL OR title ILIKE '%' || $2 || '%' ESCAPE '\')
+          AND {}
+        {}
+        LIMIT $5
+        "#,
+        ASSIGNMENT_COLUMNS,
+        page.keyset_predicate(3),
+        page.order_by(),
+    );
+    let rows = sqlx::query_as::<_, AssignmentRow>(&sql)
+        .bind(course_id)
+        .bind(filter.q.as_deref().map(escape_like))
+        .bind(page.cursor_created_at())
+        .bind(page.cursor_id())
+        .bind(page.fetch_limit())
+        .fetch_all(&state.pool)
+        .await?;
+    let items = rows
+        .into_iter()
+        .map(Assignment::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(Page::from_rows(items, &page, |a: &Assignment| {
+        Cursor::new(a.created_at, a.id)
+    })))
+}
+
+/// Readable by members of the assignment's course whose enrollment grants `assignment:read`.
 pub async fn get_assignment(
     State(state): State<AppState>,
-    AuthUser(auth): AuthUser,
+    auth: Authorized<perm::AssignmentRead>,
     Path(assignment_id): Path<Uuid>,
-) -> Result<Json<Assignment>, (StatusCode, &'static str)> {
-    // Teacher: full access. Student: allowed for assignment-existence check (e.g. when submitting).
-    if auth.role != Role::Teacher && auth.role != Role::Student {
-        return Err((StatusCode::FORBIDDEN, "teacher or student role required"));
-    }
-    let row = sqlx::query_as::<_, (Uuid, Uuid, String, chrono::DateTime<chrono::Utc>)>(
-        "SELECT id, course_id, title, created_at FROM teacher.assignments WHERE id = $1",
-    )
+) -> Result<Json<Assignment>, ApiError> {
+    let assignment = fetch_assignment(&state, assignment_id)
+        .await?
+        .ok_or(ApiError::NotFound("assignment not found"))?;
+    let resource = Resource::new("assignment", assignment.id)
+        .in_course(assignment.course_id, Permission::AssignmentRead);
+    auth.authorize(&resource, &AdminMemberships::new(&state.http_client))
+        .await?;
+    Ok(Json(assignment))
+}
+
+pub(crate) async fn fetch_assignment(
+    state: &AppState,
+    assignment_id: Uuid,
+) -> Result<Option<Assignment>, ApiError> {
+    let row = sqlx::query_as::<_, AssignmentRow>(&format!(
+        "SELECT {} FROM teacher.assignments WHERE id = $1",
+        ASSIGNMENT_COLUMNS
+    ))
     .bind(assignment_id)
     .fetch_optional(&state.pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("get_assignment: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "database error")
-    })?;
-    let (id, course_id, title, created_at) =
-        row.ok_or((StatusCode::NOT_FOUND, "assignment not found"))?;
-    Ok(Json(Assignment {
-        id,
-        course_id,
-        title,
-        created_at,
-    }))
+    .await?;
+    row.map(Assignment::try_from).transpose()
 }
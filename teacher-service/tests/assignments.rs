@@ -0,0 +1,110 @@
+//! Assignment bodies: the schedule and late-penalty rules relating several fields, and the
+//! limits on `allowed_mime_types`.
+
+use serde_json::{json, Value};
+use shared::ApiError;
+use teacher_service::CreateAssignmentBody;
+use validator::Validate;
+
+/// The `(field, message)` pairs a body is rejected with; empty when it is valid.
+fn errors(body: Value) -> Vec<(String, String)> {
+    let body: CreateAssignmentBody = serde_json::from_value(body).unwrap();
+    match body.validate().map_err(ApiError::from) {
+        Ok(()) => Vec::new(),
+        Err(ApiError::Validation(fields)) => {
+            fields.into_iter().map(|f| (f.field, f.message)).collect()
+        }
+        Err(e) => panic!("unexpected error {e}"),
+    }
+}
+
+fn error(field: &str, message: &str) -> Vec<(String, String)> {
+    vec![(field.to_string(), message.to_string())]
+}
+
+#[test]
+fn schedule_must_be_in_order() {
+    assert!(errors(json!({
+        "title": "Essay",
+        "opens_at": "2026-04-01T09:00:00Z",
+        "due_at": "2026-04-01T09:00:00Z",
+        "closes_at": "2026-04-02T09:00:00Z",
+    }))
+    .is_empty());
+    assert_eq!(
+        errors(json!({
+            "title": "Essay",
+            "opens_at": "2026-04-02T09:00:00Z",
+            "due_at": "2026-04-01T09:00:00Z",
+        })),
+        error("due_at", "must not be before opens_at")
+    );
+    assert_eq!(
+        errors(json!({
+            "title": "Essay",
+            "opens_at": "2026-04-02T09:00:00Z",
+            "closes_at": "2026-04-01T09:00:00Z",
+        })),
+        error("closes_at", "must not be before opens_at or due_at")
+    );
+}
+
+#[test]
+fn penalty_percent_goes_with_the_penalty_policy() {
+    assert!(errors(json!({
+        "title": "Essay",
+        "late_policy": "penalty",
+        "late_penalty_percent": 10,
+    }))
+    .is_empty());
+    assert_eq!(
+        errors(json!({"title": "Essay", "late_policy": "penalty"})),
+        error("late_penalty_percent", "is required for the penalty policy")
+    );
+    assert_eq!(
+        errors(json!({"title": "Essay", "late_penalty_percent": 10})),
+        error(
+            "late_penalty_percent",
+            "is only allowed with the penalty policy"
+        )
+    );
+}
+
+#[test]
+fn mime_types_are_bare_essences_within_limits() {
+    assert!(errors(json!({
+        "title": "Essay",
+        "allowed_mime_types": ["application/pdf", "image/*"],
+    }))
+    .is_empty());
+
+    let malformed = "entries must be type/subtype or type/*, without parameters";
+    for entry in [
+        "pdf",
+        "*/*",
+        "text/",
+        "text/plain; charset=utf-8",
+        "text/plain;charset=utf-8",
+        "text/ plain",
+    ] {
+        assert_eq!(
+            errors(json!({"title": "Essay", "allowed_mime_types": [entry]})),
+            error("allowed_mime_types", malformed),
+            "{entry}"
+        );
+    }
+
+    let long = format!("application/{}", "x".repeat(244));
+    assert_eq!(
+        errors(json!({"title": "Essay", "allowed_mime_types": [long]})),
+        error(
+            "allowed_mime_types",
+            "entries must be at most 255 characters"
+        )
+    );
+    let many: Vec<String> = (0..51).map(|i| format!("application/x-{i}")).collect();
+    assert_eq!(
+        errors(json!({"title": "Essay", "allowed_mime_types": many})),
+        error("allowed_mime_types", "length must be at most 50")
+    );
+}